@@ -5,5 +5,6 @@ set -e
 export RUSTFLAGS="-D warnings"
 
 cargo build --verbose &&
+cargo build --verbose --features std &&
 cargo test --verbose &&
 cargo doc
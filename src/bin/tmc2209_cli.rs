@@ -0,0 +1,354 @@
+//! Commission drivers by hand over a USB-UART dongle
+//!
+//! Usage: tmc2209-cli [options] <command>
+//!
+//! Run with `--help` for commands and options
+
+extern crate tmc2209uart;
+
+// Serial ports are opened through termios
+#[cfg(unix)]
+fn main() {
+    cli::main();
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("tmc2209-cli needs a unix host");
+    std::process::exit(1);
+}
+
+#[cfg(unix)]
+mod cli {
+    use std::process;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use tmc2209uart::structures::{
+        bus_scan::NodeProbe, config_toml::ConfigTomlError,
+        register_view::RegisterView,
+    };
+    use tmc2209uart::utils::config_toml::config_from_toml;
+    use tmc2209uart::utils::fd_uart::FdUart;
+    use tmc2209uart::utils::host_transport::{
+        parse_number, PortOptions, SharedUart,
+    };
+    use tmc2209uart::TMC2209UART;
+
+    const USAGE: &str = "usage: tmc2209-cli [options] <command>
+
+commands:
+  scan                  probe node addresses 0..3
+  status                chip, fault flags and actual state
+  dump                  all readable registers with fields
+  read <reg>            one register (name like CHOPCONF, or address)
+  write <reg> <value>   write register (saved config is updated)
+  apply <config.toml>   apply TMC2209_Config and print changed fields
+  vactual <v>           move with internal step generator (0 stops)
+  sg-monitor            print SG_RESULT, CS_ACTUAL and TSTEP
+
+options:
+  --port <path>         serial device (default $TMC2209_PORT or /dev/ttyUSB0)
+  --baud <rate>         baud rate (default 115200)
+  --node <address>      node address 0..3 (default 0)
+  --echo                single wire adapter, drop echo of sent bytes
+  --count <n>           sg-monitor: stop after n samples
+  --interval-ms <ms>    sg-monitor: time between samples (default 100)";
+
+    /// Registers that can be read over UART
+    const READABLE: [u8; 15] = [
+        0x00, 0x01, 0x02, 0x05, 0x06, 0x07, 0x12, 0x41, 0x6A, 0x6B, 0x6C, 0x6F,
+        0x70, 0x71, 0x72,
+    ];
+
+    struct Options {
+        transport: PortOptions,
+        count: Option<u64>,
+        interval_ms: u64,
+        command: Vec<String>,
+    }
+
+    pub fn main() {
+        let options = parse_args().unwrap_or_else(|message| {
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        });
+        if let Err(message) = run(&options) {
+            eprintln!("error: {}", message);
+            process::exit(1);
+        }
+    }
+
+    fn parse_args() -> Result<Options, String> {
+        let mut options = Options {
+            transport: PortOptions::default(),
+            count: None,
+            interval_ms: 100,
+            command: Vec::new(),
+        };
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next().ok_or_else(|| format!("{} needs a value", name))
+            };
+            if options.transport.parse_arg(&arg, &mut value)? {
+                continue;
+            }
+            match arg.as_str() {
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    process::exit(0);
+                }
+                "--count" => {
+                    options.count = Some(parse_number(&value("--count")?)?)
+                }
+                "--interval-ms" => {
+                    options.interval_ms =
+                        parse_number(&value("--interval-ms")?)?
+                }
+                _ => options.command.push(arg),
+            }
+        }
+
+        if options.command.is_empty() {
+            return Err("missing command".to_string());
+        }
+        Ok(options)
+    }
+
+    fn parse_register(text: &str) -> Result<u8, String> {
+        RegisterView::address_by_name(text)
+            .map(Ok)
+            .unwrap_or_else(|| parse_number(text))
+            .map_err(|_| format!("unknown register `{}`", text))
+    }
+
+    fn run(options: &Options) -> Result<(), String> {
+        let uart = options.transport.open()?;
+        let mut driver =
+            TMC2209UART::new(&uart, options.transport.base_config());
+
+        let args: Vec<&str> =
+            options.command.iter().map(|s| s.as_str()).collect();
+        match args.as_slice() {
+            ["scan"] => scan(&uart, options.transport.baud_rate),
+            ["status"] => status(&mut driver, options.transport.node),
+            ["dump"] => dump(&mut driver),
+            ["read", register] => {
+                let address = parse_register(register)?;
+                let value = driver
+                    .read_raw(address)
+                    .map_err(|_| no_reply(options.transport.node))?;
+                println!("{}", RegisterView::new(address, value));
+                Ok(())
+            }
+            ["write", register, value] => {
+                let address = parse_register(register)?;
+                let value = parse_number(value)?;
+                driver
+                    .write_raw(address, value)
+                    .map_err(|_| "write failed".to_string())?;
+                println!("{}", RegisterView::new(address, value));
+                Ok(())
+            }
+            ["apply", path] => apply(&mut driver, path),
+            ["vactual", velocity] => {
+                let velocity = velocity
+                    .parse()
+                    .map_err(|_| format!("invalid velocity `{}`", velocity))?;
+                driver
+                    .vactual(velocity)
+                    .map_err(|_| "write failed".to_string())
+            }
+            ["sg-monitor"] => sg_monitor(&mut driver, options),
+            _ => Err(format!("unknown command `{}`", args.join(" "))),
+        }
+    }
+
+    fn no_reply(node: u8) -> String {
+        format!("no reply from node {}", node)
+    }
+
+    fn scan(uart: &SharedUart, baud_rate: u32) -> Result<(), String> {
+        let scan = TMC2209UART::scan_bus(uart, baud_rate)
+            .map_err(|_| "serial port is not available".to_string())?;
+        for (address, probe) in scan.nodes.iter().enumerate() {
+            match probe {
+                NodeProbe::Responding(info) => println!(
+                    "node {}: version 0x{:02X}, IFCNT {}",
+                    address, info.version, info.ifcnt
+                ),
+                NodeProbe::NoReply => println!("node {}: no reply", address),
+                NodeProbe::Collision => println!(
+                    "node {}: collision (several drivers with this address?)",
+                    address
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    fn status(
+        driver: &mut TMC2209UART<FdUart>,
+        node: u8,
+    ) -> Result<(), String> {
+        let chip = driver.identify().map_err(|_| no_reply(node))?;
+        let gstat = driver.read_gstat().map_err(|_| no_reply(node))?;
+        let drv_status =
+            driver.read_drv_status().map_err(|_| no_reply(node))?;
+        let tstep = driver.read_tstep().map_err(|_| no_reply(node))?;
+
+        println!("chip:        {:?}", chip);
+        println!(
+            "gstat:       reset={} drv_err={} uv_cp={}",
+            gstat.reset as u8, gstat.drv_err as u8, gstat.uv_cp as u8
+        );
+        println!("temperature: {:?}", drv_status.temperature_level());
+        let faults: Vec<&str> = [
+            (drv_status.s2ga, "s2ga"),
+            (drv_status.s2gb, "s2gb"),
+            (drv_status.s2vsa, "s2vsa"),
+            (drv_status.s2vsb, "s2vsb"),
+            (drv_status.ola, "ola"),
+            (drv_status.olb, "olb"),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, name)| *name)
+        .collect();
+        if faults.is_empty() {
+            println!("faults:      none");
+        } else {
+            println!("faults:      {}", faults.join(" "));
+        }
+        println!(
+            "mode:        {}{}",
+            if drv_status.stealth {
+                "stealthChop"
+            } else {
+                "spreadCycle"
+            },
+            if drv_status.stst { ", standstill" } else { "" }
+        );
+        println!("cs_actual:   {}", drv_status.cs_actual);
+        println!("tstep:       {}", tstep);
+        // TMC2208 and TMC2225 have no StallGuard
+        if chip.is_tmc2209() {
+            let sg_result =
+                driver.read_sg_result().map_err(|_| no_reply(node))?;
+            println!("sg_result:   {}", sg_result);
+        }
+        Ok(())
+    }
+
+    fn dump(driver: &mut TMC2209UART<FdUart>) -> Result<(), String> {
+        for address in READABLE.iter() {
+            match driver.read_raw(*address) {
+                Ok(value) => println!("{}", RegisterView::new(*address, value)),
+                Err(()) => println!(
+                    "{}: no reply",
+                    RegisterView::new(*address, 0).name().unwrap_or("?")
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(
+        driver: &mut TMC2209UART<FdUart>,
+        path: &str,
+    ) -> Result<(), String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("can not read {}: {}", path, error))?;
+        let config = config_from_toml(&text).map_err(|error| match error {
+            ConfigTomlError::Syntax { line } => {
+                format!("{}:{}: expected `key = value`", path, line)
+            }
+            ConfigTomlError::UnknownKey { line, key } => {
+                format!("{}:{}: unknown field `{}`", path, line, key)
+            }
+            ConfigTomlError::InvalidValue { line, key } => {
+                format!("{}:{}: invalid value of `{}`", path, line, key)
+            }
+        })?;
+        let violations = config.validate();
+        if !violations.is_empty() {
+            let violations: Vec<String> =
+                violations.iter().map(|v| format!("{:?}", v)).collect();
+            return Err(format!(
+                "invalid config:\n  {}",
+                violations.join("\n  ")
+            ));
+        }
+        // IHOLD_IRUN is write-only, a fresh driver does not know the run
+        // current. Hold settings alone would be refused or not written
+        let is_hold_changed = config.hold_current.is_some()
+            || config.ihold_delay.is_some()
+            || config.ihold_multiplier.is_some();
+        if is_hold_changed && config.rms_current.is_none() {
+            return Err("hold_current, ihold_delay and ihold_multiplier need \
+                    rms_current in the same config"
+                .to_string());
+        }
+
+        driver
+            .init_saved_config()
+            .map_err(|_| "can not read config".to_string())?;
+        let before = driver
+            .debug_read_config_from_driver()
+            .map_err(|_| "can not read config".to_string())?;
+        driver
+            .apply_config(&config)
+            .map_err(|error| format!("can not apply config: {:?}", error))?;
+        let after = driver
+            .debug_read_config_from_driver()
+            .map_err(|_| "can not read config".to_string())?;
+
+        let changes = before.diff(&after);
+        if changes.is_empty() {
+            println!("no register field changed");
+        } else {
+            println!("{}", changes);
+        }
+        // Not readable, shown as written
+        if config.rms_current.is_some() {
+            let saved_config = driver.get_saved_config();
+            println!(
+                "rms_current: {} mA, hold_current: {} mA",
+                saved_config.rms_current, saved_config.hold_current
+            );
+        }
+        Ok(())
+    }
+
+    fn sg_monitor(
+        driver: &mut TMC2209UART<FdUart>,
+        options: &Options,
+    ) -> Result<(), String> {
+        let start = Instant::now();
+        let mut samples = 0;
+        println!("time_ms sg_result cs_actual tstep");
+        loop {
+            if let Some(count) = options.count {
+                if samples >= count {
+                    return Ok(());
+                }
+            }
+            let sg_result = driver.read_sg_result();
+            let drv_status = driver.read_drv_status();
+            let tstep = driver.read_tstep();
+            match (sg_result, drv_status, tstep) {
+                (Ok(sg_result), Ok(drv_status), Ok(tstep)) => println!(
+                    "{} {} {} {}",
+                    start.elapsed().as_millis(),
+                    sg_result,
+                    drv_status.cs_actual,
+                    tstep
+                ),
+                _ => return Err(no_reply(options.transport.node)),
+            }
+            samples += 1;
+            thread::sleep(Duration::from_millis(options.interval_ms));
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Decode captured UART traffic (text log of `CapturedByte`s, one per line)
+//! into TMC2209 datagrams
+//!
+//! Usage: tmc2209-decode [--fields] [capture.log]
+//!
+//! Reads stdin without file. `--fields` prints register fields of written
+//! and read values
+
+extern crate tmc2209uart;
+
+use std::io::Read;
+use std::process;
+use tmc2209uart::structures::register_view::RegisterView;
+use tmc2209uart::utils::capture::{parse_capture_log, Direction};
+use tmc2209uart::utils::datagram::{decode_capture, DatagramKind};
+
+const USAGE: &str = "usage: tmc2209-decode [--fields] [capture.log]";
+
+fn main() {
+    let mut fields = false;
+    let mut path = None;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--fields" => fields = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => fail(USAGE),
+        }
+    }
+
+    let mut text = String::new();
+    let result = match path {
+        Some(ref path) => std::fs::File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut text)),
+        None => std::io::stdin().read_to_string(&mut text),
+    };
+    if let Err(error) = result {
+        fail(&format!("can not read capture: {}", error));
+    }
+    let capture = match parse_capture_log(&text) {
+        Ok(capture) => capture,
+        Err(line) => {
+            fail(&format!("line {}: expected `<us> TX|RX <hex>`", line))
+        }
+    };
+
+    let mut datagrams = decode_capture(&capture);
+    for datagram in datagrams.by_ref() {
+        let direction = match datagram.direction {
+            Direction::Tx => "TX",
+            Direction::Rx => "RX",
+        };
+        let kind = match datagram.kind {
+            DatagramKind::ReadRequest => "read",
+            DatagramKind::WriteRequest => "write",
+            DatagramKind::Reply => "reply",
+        };
+        let view = RegisterView::new(
+            datagram.register,
+            datagram.value.unwrap_or_default(),
+        );
+        let register = match view.name() {
+            Some(name) => name.to_string(),
+            None => format!("0x{:02X}", datagram.register),
+        };
+        let node = if datagram.kind == DatagramKind::Reply {
+            "master".to_string()
+        } else {
+            format!("node {}", datagram.address)
+        };
+        let value = match datagram.value {
+            Some(value) => format!(" = 0x{:08X}", value),
+            None => String::new(),
+        };
+        println!(
+            "{:>12} {} {:<5} {:<6} {}{}",
+            datagram.timestamp_us, direction, kind, node, register, value
+        );
+        if fields && datagram.value.is_some() && view.name().is_some() {
+            // First line of the view repeats name and value
+            for line in view.to_string().lines().skip(1) {
+                println!("{:>12} {}", "", line);
+            }
+        }
+    }
+
+    if datagrams.skipped() > 0 {
+        // Noise, cut datagrams and datagrams with bad CRC
+        eprintln!("bytes outside of datagrams: {}", datagrams.skipped());
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(2);
+}
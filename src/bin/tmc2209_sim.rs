@@ -0,0 +1,150 @@
+//! Simulated drivers behind a pseudo-terminal, to try host tools without
+//! hardware
+//!
+//! Usage: tmc2209-sim [--nodes 0,1] [--echo] [--set <reg>=<value>]...
+//!
+//! Prints path of the terminal (`/dev/pts/N`) and serves requests until
+//! killed. `--set` presets a register of all drivers (for example
+//! `--set 0x41=0x80` for SG_RESULT)
+
+extern crate libc;
+extern crate tmc2209uart;
+
+// Pseudo-terminal and termios are unix only
+#[cfg(unix)]
+fn main() {
+    sim::main();
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("tmc2209-sim needs a unix host");
+    std::process::exit(1);
+}
+
+#[cfg(unix)]
+mod sim {
+    use std::ffi::CStr;
+    use std::fs::{File, OpenOptions};
+    use std::io::{self, Read, Write};
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::FromRawFd;
+    use std::process;
+    use tmc2209uart::embedded_io::{Read as _, Write as _};
+    use tmc2209uart::utils::fd_uart::set_raw_mode;
+    use tmc2209uart::utils::simulated_device::{
+        SimulatedBus, SimulatedTmc2209,
+    };
+
+    const USAGE: &str =
+        "usage: tmc2209-sim [--nodes 0,1] [--echo] [--set <reg>=<value>]...";
+
+    pub fn main() {
+        let mut nodes = vec![0u8];
+        let mut echo = false;
+        let mut presets = Vec::new();
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--nodes" => {
+                    nodes = args
+                        .next()
+                        .and_then(|list| {
+                            list.split(',')
+                                .map(|node| {
+                                    node.trim().parse().ok().filter(|n| *n < 4)
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_else(|| fail(USAGE));
+                }
+                "--echo" => echo = true,
+                "--set" => {
+                    let preset =
+                        args.next().and_then(|preset| parse_preset(&preset));
+                    presets.push(preset.unwrap_or_else(|| fail(USAGE)));
+                }
+                _ => fail(USAGE),
+            }
+        }
+
+        let mut bus = SimulatedBus::new();
+        for node in nodes.iter() {
+            let mut device = SimulatedTmc2209::new(*node);
+            device.set_echo(echo);
+            for (register, value) in presets.iter() {
+                device.set_register(*register, *value);
+            }
+            bus.connect(*node as usize, device);
+        }
+
+        let (mut master, slave_path) =
+            open_pty().unwrap_or_else(|error| fail(&format!("pty: {}", error)));
+        // Keep slave open, so the master does not see hangup between clients
+        let _slave = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(&slave_path)
+            .and_then(|slave| set_raw_mode(&slave, None).map(|_| slave))
+            .unwrap_or_else(|error| {
+                fail(&format!("{}: {}", slave_path, error))
+            });
+
+        println!("{}", slave_path);
+        let _ = io::stdout().flush();
+
+        if let Err(error) = serve(&mut master, &mut bus) {
+            fail(&format!("pty: {}", error));
+        }
+    }
+
+    fn serve(master: &mut File, bus: &mut SimulatedBus) -> io::Result<()> {
+        let mut request = [0u8; 64];
+        let mut reply = [0u8; 64];
+        loop {
+            let count = master.read(&mut request)?;
+            let _ = bus.write(&request[..count]);
+            while let Ok(count) = bus.read(&mut reply) {
+                master.write_all(&reply[..count])?;
+            }
+        }
+    }
+
+    fn parse_preset(preset: &str) -> Option<(u8, u32)> {
+        let (register, value) = preset.split_once('=')?;
+        Some((parse_number(register)? as u8, parse_number(value)?))
+    }
+
+    fn parse_number(text: &str) -> Option<u32> {
+        match text.trim().strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => text.trim().parse().ok(),
+        }
+    }
+
+    fn open_pty() -> io::Result<(File, String)> {
+        unsafe {
+            let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let master = File::from_raw_fd(fd);
+            if libc::grantpt(fd) != 0 || libc::unlockpt(fd) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let name = libc::ptsname(fd);
+            if name.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            let path = CStr::from_ptr(name).to_string_lossy().into_owned();
+            Ok((master, path))
+        }
+    }
+
+    fn fail(message: &str) -> ! {
+        eprintln!("{}", message);
+        process::exit(2);
+    }
+}
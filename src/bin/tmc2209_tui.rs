@@ -0,0 +1,586 @@
+//! Live tuning of StallGuard, coolStep and chopper settings in terminal
+//!
+//! Usage: tmc2209-tui [options]
+//!
+//! Motor runs with VACTUAL while SG_RESULT, CS_ACTUAL, PWM_SCALE_SUM and
+//! TSTEP are plotted. Tuned settings are written to the driver at once and
+//! can be exported as TOML config (see `tmc2209-cli apply`)
+
+extern crate ratatui;
+extern crate tmc2209uart;
+
+// Serial ports are opened through termios
+#[cfg(unix)]
+fn main() {
+    tui::main();
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("tmc2209-tui needs a unix host");
+    std::process::exit(1);
+}
+
+#[cfg(unix)]
+mod tui {
+    use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+    use ratatui::layout::{Constraint, Layout, Rect};
+    use ratatui::style::{Color, Modifier, Style};
+    use ratatui::symbols::Marker;
+    use ratatui::text::Line;
+    use ratatui::widgets::{
+        Axis, Block, Chart, Dataset, GraphType, List, ListItem, ListState,
+        Paragraph,
+    };
+    use ratatui::{DefaultTerminal, Frame};
+    use std::collections::VecDeque;
+    use std::process;
+    use std::time::{Duration, Instant};
+    use tmc2209uart::structures::config::TMC2209_Config;
+    use tmc2209uart::utils::config_toml::{config_from_toml, config_to_toml};
+    use tmc2209uart::utils::fd_uart::FdUart;
+    use tmc2209uart::utils::host_transport::{parse_number, PortOptions};
+    use tmc2209uart::TMC2209UART;
+
+    const USAGE: &str = "usage: tmc2209-tui [options]
+
+options:
+  --port <path>         serial device (default $TMC2209_PORT or /dev/ttyUSB0)
+  --baud <rate>         baud rate (default 115200)
+  --node <address>      node address 0..3 (default 0)
+  --echo                single wire adapter, drop echo of sent bytes
+  --config <file>       apply TOML config before tuning
+  --export <file>       where `e` exports tuned config (default tmc2209.toml)
+  --interval-ms <ms>    time between samples (default 50)
+
+keys:
+  up/down               select setting
+  left/right            change setting (page up/down: 10 steps)
+  space                 start/stop motor
+  e                     export config
+  q                     stop motor and quit";
+
+    /// Plotted time span
+    const WINDOW_S: f64 = 30.0;
+
+    struct Options {
+        transport: PortOptions,
+        config: Option<String>,
+        export: String,
+        interval_ms: u64,
+    }
+
+    /// Setting changed with left/right keys
+    #[derive(Clone, Copy, PartialEq)]
+    enum Setting {
+        Vactual,
+        RmsCurrent,
+        Sgthrs,
+        Tcoolthrs,
+        Tpwmthrs,
+        Spreadcycle,
+        Toff,
+        HysteresisStart,
+        HysteresisEnd,
+        BlankTime,
+    }
+
+    const SETTINGS: [Setting; 10] = [
+        Setting::Vactual,
+        Setting::RmsCurrent,
+        Setting::Sgthrs,
+        Setting::Tcoolthrs,
+        Setting::Tpwmthrs,
+        Setting::Spreadcycle,
+        Setting::Toff,
+        Setting::HysteresisStart,
+        Setting::HysteresisEnd,
+        Setting::BlankTime,
+    ];
+
+    impl Setting {
+        fn name(self) -> &'static str {
+            match self {
+                Setting::Vactual => "vactual",
+                Setting::RmsCurrent => "rms_current",
+                Setting::Sgthrs => "sgthrs",
+                Setting::Tcoolthrs => "tcoolthrs",
+                Setting::Tpwmthrs => "tpwmthrs",
+                Setting::Spreadcycle => "en_spreadcycle",
+                Setting::Toff => "toff",
+                Setting::HysteresisStart => "hysteresis_start",
+                Setting::HysteresisEnd => "hysteresis_end",
+                Setting::BlankTime => "blank_time",
+            }
+        }
+
+        /// Change of one key press
+        fn step(self) -> i64 {
+            match self {
+                Setting::Vactual => 1000,
+                Setting::RmsCurrent => 50,
+                Setting::Sgthrs => 5,
+                Setting::Tcoolthrs | Setting::Tpwmthrs => 10,
+                _ => 1,
+            }
+        }
+
+        fn range(self) -> (i64, i64) {
+            match self {
+                Setting::Vactual => (-0x7F_FFFF, 0x7F_FFFF),
+                Setting::RmsCurrent => (50, 2000),
+                Setting::Sgthrs => (0, 255),
+                Setting::Tcoolthrs | Setting::Tpwmthrs => (0, 0xF_FFFF),
+                Setting::Spreadcycle => (0, 1),
+                Setting::Toff | Setting::HysteresisEnd => (0, 15),
+                Setting::HysteresisStart => (0, 7),
+                Setting::BlankTime => (0, 3),
+            }
+        }
+
+        /// Value of config field (not for Vactual)
+        fn get(self, config: &TMC2209_Config) -> Option<i64> {
+            match self {
+                Setting::Vactual => None,
+                Setting::RmsCurrent => config.rms_current.map(i64::from),
+                Setting::Sgthrs => config.sgthrs.map(i64::from),
+                Setting::Tcoolthrs => config.tcoolthrs.map(i64::from),
+                Setting::Tpwmthrs => config.tpwmthrs.map(i64::from),
+                Setting::Spreadcycle => config.en_spreadcycle.map(i64::from),
+                Setting::Toff => config.toff.map(i64::from),
+                Setting::HysteresisStart => {
+                    config.hysteresis_start.map(i64::from)
+                }
+                Setting::HysteresisEnd => config.hysteresis_end.map(i64::from),
+                Setting::BlankTime => config.blank_time.map(i64::from),
+            }
+        }
+
+        /// Set config field, value must be in range()
+        fn set(self, config: &mut TMC2209_Config, value: Option<i64>) {
+            match self {
+                Setting::Vactual => {}
+                Setting::RmsCurrent => {
+                    config.rms_current = value.map(|v| v as u16)
+                }
+                Setting::Sgthrs => config.sgthrs = value.map(|v| v as u32),
+                Setting::Tcoolthrs => {
+                    config.tcoolthrs = value.map(|v| v as u32)
+                }
+                Setting::Tpwmthrs => config.tpwmthrs = value.map(|v| v as u32),
+                Setting::Spreadcycle => {
+                    config.en_spreadcycle = value.map(|v| v != 0)
+                }
+                Setting::Toff => config.toff = value.map(|v| v as u32),
+                Setting::HysteresisStart => {
+                    config.hysteresis_start = value.map(|v| v as u32)
+                }
+                Setting::HysteresisEnd => {
+                    config.hysteresis_end = value.map(|v| v as u32)
+                }
+                Setting::BlankTime => {
+                    config.blank_time = value.map(|v| v as u32)
+                }
+            }
+        }
+
+        /// Field of a readable register (GCONF, CHOPCONF). Write-only
+        /// fields are unknown until set by config file or key
+        fn is_readable(self) -> bool {
+            matches!(
+                self,
+                Setting::Spreadcycle
+                    | Setting::Toff
+                    | Setting::HysteresisStart
+                    | Setting::HysteresisEnd
+                    | Setting::BlankTime
+            )
+        }
+
+        /// Start value for fields missing in config
+        fn default(self) -> i64 {
+            match self {
+                Setting::RmsCurrent => 400,
+                _ => 0,
+            }
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    struct Sample {
+        time_s: f64,
+        sg_result: u16,
+        cs_actual: u8,
+        pwm_scale_sum: u8,
+        tstep: u32,
+    }
+
+    /// Plotted value: title, value of sample
+    type Plot = (&'static str, fn(&Sample) -> f64);
+
+    const PLOTS: [Plot; 4] = [
+        ("SG_RESULT", |sample| sample.sg_result as f64),
+        ("CS_ACTUAL", |sample| sample.cs_actual as f64),
+        ("PWM_SCALE_SUM", |sample| sample.pwm_scale_sum as f64),
+        ("TSTEP", |sample| sample.tstep as f64),
+    ];
+
+    struct App<'a> {
+        driver: TMC2209UART<'a, FdUart>,
+        /// Tuned config, exported with `e`
+        config: TMC2209_Config,
+        vactual: i32,
+        running: bool,
+        selected: usize,
+        history: VecDeque<Sample>,
+        start: Instant,
+        export: String,
+        message: String,
+    }
+
+    pub fn main() {
+        let options = parse_args().unwrap_or_else(|message| {
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        });
+        if let Err(message) = run(&options) {
+            eprintln!("error: {}", message);
+            process::exit(1);
+        }
+    }
+
+    fn parse_args() -> Result<Options, String> {
+        let mut options = Options {
+            transport: PortOptions::default(),
+            config: None,
+            export: "tmc2209.toml".to_string(),
+            interval_ms: 50,
+        };
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next().ok_or_else(|| format!("{} needs a value", name))
+            };
+            if options.transport.parse_arg(&arg, &mut value)? {
+                continue;
+            }
+            match arg.as_str() {
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    process::exit(0);
+                }
+                "--config" => options.config = Some(value("--config")?),
+                "--export" => options.export = value("--export")?,
+                "--interval-ms" => {
+                    options.interval_ms =
+                        parse_number(&value("--interval-ms")?)?
+                }
+                _ => return Err(format!("unknown argument `{}`", arg)),
+            }
+        }
+        Ok(options)
+    }
+
+    fn run(options: &Options) -> Result<(), String> {
+        let uart = options.transport.open()?;
+        let mut driver =
+            TMC2209UART::new(&uart, options.transport.base_config());
+        let no_reply =
+            |_| format!("no reply from node {}", options.transport.node);
+
+        driver.init_saved_config().map_err(no_reply)?;
+        let mut config = TMC2209_Config::default();
+        if let Some(path) = options.config.as_ref() {
+            let text = std::fs::read_to_string(path)
+                .map_err(|error| format!("can not read {}: {}", path, error))?;
+            config = config_from_toml(&text)
+                .map_err(|error| format!("{}: {:?}", path, error))?;
+            driver.apply_config(&config).map_err(|error| {
+                format!("can not apply {}: {:?}", path, error)
+            })?;
+        }
+        // Only config file fields, read back fields and changed fields are
+        // exported, write-only registers are not guessed
+        let read_back = TMC2209_Config::from_saved_config(
+            driver.get_saved_config(),
+            driver.get_base_config(),
+        );
+        for setting in SETTINGS.iter() {
+            if setting.is_readable() && setting.get(&config).is_none() {
+                setting.set(&mut config, setting.get(&read_back));
+            }
+        }
+
+        let mut app = App {
+            driver,
+            config,
+            vactual: 0,
+            running: false,
+            selected: 0,
+            history: VecDeque::new(),
+            start: Instant::now(),
+            export: options.export.clone(),
+            message: String::new(),
+        };
+
+        let mut terminal = ratatui::init();
+        let result =
+            app.run(&mut terminal, Duration::from_millis(options.interval_ms));
+        ratatui::restore();
+        // Do not leave motor running
+        let stopped = app.driver.vactual(0);
+        result.map_err(|error| format!("terminal: {}", error))?;
+        stopped.map_err(no_reply)
+    }
+
+    impl<'a> App<'a> {
+        fn run(
+            &mut self,
+            terminal: &mut DefaultTerminal,
+            interval: Duration,
+        ) -> std::io::Result<()> {
+            let mut next_sample = Instant::now();
+            loop {
+                terminal.draw(|frame| self.draw(frame))?;
+
+                let timeout =
+                    next_sample.saturating_duration_since(Instant::now());
+                if event::poll(timeout)? {
+                    if let Event::Key(key) = event::read()? {
+                        if key.kind == KeyEventKind::Press
+                            && !self.on_key(key.code)
+                        {
+                            return Ok(());
+                        }
+                    }
+                }
+                if Instant::now() >= next_sample {
+                    self.sample();
+                    next_sample += interval;
+                }
+            }
+        }
+
+        /// Returns false to quit
+        fn on_key(&mut self, key: KeyCode) -> bool {
+            match key {
+                KeyCode::Char('q') | KeyCode::Esc => return false,
+                KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+                KeyCode::Down => {
+                    self.selected = (self.selected + 1).min(SETTINGS.len() - 1)
+                }
+                KeyCode::Left | KeyCode::Char('-') => self.change(-1),
+                KeyCode::Right | KeyCode::Char('+') => self.change(1),
+                KeyCode::PageDown => self.change(-10),
+                KeyCode::PageUp => self.change(10),
+                KeyCode::Char(' ') => {
+                    self.running = !self.running;
+                    self.send_vactual();
+                }
+                KeyCode::Char('e') => self.export(),
+                _ => {}
+            }
+            true
+        }
+
+        fn change(&mut self, steps: i64) {
+            let setting = SETTINGS[self.selected];
+            let (min, max) = setting.range();
+
+            if setting == Setting::Vactual {
+                let vactual = self.vactual as i64 + steps * setting.step();
+                self.vactual = vactual.clamp(min, max) as i32;
+                if self.running {
+                    self.send_vactual();
+                }
+                return;
+            }
+
+            let before = setting.get(&self.config);
+            let value = before.unwrap_or_else(|| setting.default())
+                + steps * setting.step();
+            let value = value.clamp(min, max);
+            setting.set(&mut self.config, Some(value));
+
+            let violations = self.config.validate();
+            if let Some(violation) = violations.iter().next() {
+                self.message = format!("{:?}", violation);
+                setting.set(&mut self.config, before);
+                return;
+            }
+
+            // Only the changed field is written
+            let mut change = TMC2209_Config::default();
+            setting.set(&mut change, Some(value));
+            match self.driver.apply_config(&change) {
+                Ok(()) => {
+                    self.message = format!("{} = {}", setting.name(), value)
+                }
+                Err(error) => {
+                    self.message = format!(
+                        "can not write {}: {:?}",
+                        setting.name(),
+                        error
+                    );
+                    setting.set(&mut self.config, before);
+                }
+            }
+        }
+
+        fn send_vactual(&mut self) {
+            let vactual = if self.running { self.vactual } else { 0 };
+            self.message = match self.driver.vactual(vactual) {
+                Ok(()) => format!("vactual = {}", vactual),
+                Err(()) => "can not write vactual".to_string(),
+            };
+        }
+
+        fn export(&mut self) {
+            self.message = match std::fs::write(
+                &self.export,
+                config_to_toml(&self.config),
+            ) {
+                Ok(()) => format!("exported to {}", self.export),
+                Err(error) => format!("{}: {}", self.export, error),
+            };
+        }
+
+        fn sample(&mut self) {
+            let sg_result = self.driver.read_sg_result();
+            let drv_status = self.driver.read_drv_status();
+            let pwm_scale = self.driver.read_pwm_scale();
+            let tstep = self.driver.read_tstep();
+            let (sg_result, drv_status, pwm_scale, tstep) =
+                match (sg_result, drv_status, pwm_scale, tstep) {
+                    (Ok(sg), Ok(drv), Ok(pwm), Ok(tstep)) => {
+                        (sg, drv, pwm, tstep)
+                    }
+                    _ => {
+                        self.message = "no reply".to_string();
+                        return;
+                    }
+                };
+
+            let time_s = self.start.elapsed().as_secs_f64();
+            self.history.push_back(Sample {
+                time_s,
+                sg_result,
+                cs_actual: drv_status.cs_actual,
+                pwm_scale_sum: pwm_scale.sum,
+                tstep,
+            });
+            while self
+                .history
+                .front()
+                .is_some_and(|sample| sample.time_s < time_s - WINDOW_S)
+            {
+                self.history.pop_front();
+            }
+        }
+
+        fn draw(&self, frame: &mut Frame) {
+            let [plots, side] = Layout::horizontal([
+                Constraint::Min(40),
+                Constraint::Length(34),
+            ])
+            .areas(frame.area());
+            let plot_areas =
+                Layout::vertical([Constraint::Ratio(1, 4); 4]).split(plots);
+            for (plot, area) in PLOTS.iter().zip(plot_areas.iter()) {
+                self.draw_plot(frame, *area, plot.0, plot.1);
+            }
+
+            let [settings, help] =
+                Layout::vertical([Constraint::Min(12), Constraint::Length(9)])
+                    .areas(side);
+            self.draw_settings(frame, settings);
+            let help_text = vec![
+                Line::from("up/down     select"),
+                Line::from("left/right  change (pg x10)"),
+                Line::from("space       start/stop motor"),
+                Line::from("e           export TOML"),
+                Line::from("q           quit"),
+                Line::from(""),
+                Line::from(self.message.as_str()),
+            ];
+            frame.render_widget(
+                Paragraph::new(help_text)
+                    .block(Block::bordered().title("keys")),
+                help,
+            );
+        }
+
+        fn draw_plot(
+            &self,
+            frame: &mut Frame,
+            area: Rect,
+            title: &str,
+            value: fn(&Sample) -> f64,
+        ) {
+            let points: Vec<(f64, f64)> = self
+                .history
+                .iter()
+                .map(|sample| (sample.time_s, value(sample)))
+                .collect();
+            let end = points
+                .last()
+                .map_or(WINDOW_S, |point| point.0.max(WINDOW_S));
+            let max =
+                points.iter().fold(1.0, |max: f64, point| max.max(point.1));
+            let last = points
+                .last()
+                .map_or(String::new(), |point| format!(" {}", point.1));
+
+            let dataset = Dataset::default()
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(Color::Cyan))
+                .data(&points);
+            let chart = Chart::new(vec![dataset])
+                .block(Block::bordered().title(format!("{}{}", title, last)))
+                .x_axis(Axis::default().bounds([end - WINDOW_S, end]))
+                .y_axis(
+                    Axis::default()
+                        .bounds([0.0, max])
+                        .labels(["0".to_string(), format!("{}", max)]),
+                );
+            frame.render_widget(chart, area);
+        }
+
+        fn draw_settings(&self, frame: &mut Frame, area: Rect) {
+            let items: Vec<ListItem> = SETTINGS
+                .iter()
+                .map(|setting| {
+                    let value = match *setting {
+                        Setting::Vactual if self.running => {
+                            format!("{}", self.vactual)
+                        }
+                        Setting::Vactual => {
+                            format!("{} (stopped)", self.vactual)
+                        }
+                        Setting::Spreadcycle => {
+                            match self.config.en_spreadcycle {
+                                Some(true) => "on".to_string(),
+                                Some(false) => "off".to_string(),
+                                None => "-".to_string(),
+                            }
+                        }
+                        _ => setting
+                            .get(&self.config)
+                            .map_or("-".to_string(), |value| value.to_string()),
+                    };
+                    ListItem::new(format!("{:<17}{}", setting.name(), value))
+                })
+                .collect();
+            let list = List::new(items)
+                .block(Block::bordered().title("settings"))
+                .highlight_style(
+                    Style::default().add_modifier(Modifier::REVERSED),
+                );
+            let mut state =
+                ListState::default().with_selected(Some(self.selected));
+            frame.render_stateful_widget(list, area, &mut state);
+        }
+    }
+}
@@ -1,4 +1,9 @@
-use crate::structures::base_config::TMC2209_BaseConfig;
+use crate::structures::{
+    base_config::TMC2209_BaseConfig, chip_variant::ChipVariant,
+};
+use crate::utils::calc::{
+    analog_current_scale, internal_rsense_equivalent, CurrentScaling,
+};
 
 impl<'a> Default for TMC2209_BaseConfig {
     fn default() -> Self {
@@ -6,6 +11,37 @@ impl<'a> Default for TMC2209_BaseConfig {
             uart_address: 0,
             r_sense: 0.11, // Default for SilentStepStick series drivers
             ihold_multiplier: 0.5, // Decreas hold current with 50%
+            fclk: 12_000_000.0, // Internal clock
+            full_steps_per_rotation: 200,
+            chip_variant: ChipVariant::Tmc2209,
+            vref: 2.5, // Full scale, no analog scaling
+            rref: 0.0, // Internal sense resistors not used
+            baud_rate: 115_200,
+        }
+    }
+}
+
+impl TMC2209_BaseConfig {
+    /// Sense resistor and VREF scaling for current calculations, according
+    /// to GCONF i_scale_analog and internal_rsense. `r_sense` is used if
+    /// `rref` is not set or out of range
+    pub fn current_scaling(
+        &self,
+        i_scale_analog: bool,
+        internal_rsense: bool,
+    ) -> CurrentScaling {
+        let internal_r_sense = if internal_rsense {
+            internal_rsense_equivalent(self.rref)
+        } else {
+            None
+        };
+        CurrentScaling {
+            r_sense: internal_r_sense.unwrap_or(self.r_sense),
+            analog_scale: if i_scale_analog {
+                analog_current_scale(self.vref)
+            } else {
+                1.0
+            },
         }
     }
 }
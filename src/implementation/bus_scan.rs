@@ -0,0 +1,28 @@
+use crate::structures::bus_scan::{BusScan, NodeProbe, TMC2209_NodeInfo};
+
+impl BusScan {
+    /// Drivers which answered without errors
+    pub fn responding(&self) -> impl Iterator<Item = &TMC2209_NodeInfo> {
+        self.nodes.iter().filter_map(|node| match node {
+            NodeProbe::Responding(info) => Some(info),
+            _ => None,
+        })
+    }
+
+    /// Addresses with garbled replies
+    pub fn collisions(&self) -> impl Iterator<Item = u8> + '_ {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| **node == NodeProbe::Collision)
+            .map(|(address, _)| address as u8)
+    }
+
+    pub fn has_collision(&self) -> bool {
+        self.collisions().next().is_some()
+    }
+
+    pub fn probe(&self, address: u8) -> Option<NodeProbe> {
+        self.nodes.get(address as usize).copied()
+    }
+}
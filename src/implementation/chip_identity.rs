@@ -0,0 +1,34 @@
+use crate::structures::{
+    chip_identity::ChipIdentity, chip_variant::ChipVariant,
+    ioin::TMC2209_IoinState,
+};
+
+const TMC2209_VERSION: u8 = 0x21;
+const TMC2208_VERSION: u8 = 0x20;
+
+impl ChipIdentity {
+    pub fn from_ioin(ioin: &TMC2209_IoinState) -> ChipIdentity {
+        match ioin.version {
+            TMC2209_VERSION => ChipIdentity::Tmc2209,
+            // Bit 8 of TMC2208 IOIN is SEL_A instead of SPREAD_EN
+            TMC2208_VERSION if ioin.spread_en => ChipIdentity::Tmc2208,
+            TMC2208_VERSION => ChipIdentity::Tmc2225,
+            version => ChipIdentity::Unknown { version },
+        }
+    }
+
+    pub fn is_tmc2209(&self) -> bool {
+        *self == ChipIdentity::Tmc2209
+    }
+
+    /// Detected chip is (or can not be told apart from) `variant`
+    pub fn matches(&self, variant: ChipVariant) -> bool {
+        matches!(
+            (self, variant),
+            (ChipIdentity::Tmc2209, ChipVariant::Tmc2209)
+                | (ChipIdentity::Tmc2209, ChipVariant::Tmc2226)
+                | (ChipIdentity::Tmc2208, ChipVariant::Tmc2208)
+                | (ChipIdentity::Tmc2225, ChipVariant::Tmc2225)
+        )
+    }
+}
@@ -0,0 +1,43 @@
+use crate::structures::chip_variant::ChipVariant;
+
+impl ChipVariant {
+    /// SGTHRS and SG_RESULT are available
+    pub fn has_stallguard(self) -> bool {
+        match self {
+            ChipVariant::Tmc2209 | ChipVariant::Tmc2226 => true,
+            ChipVariant::Tmc2208 | ChipVariant::Tmc2225 => false,
+        }
+    }
+
+    /// TCOOLTHRS and COOLCONF are available
+    pub fn has_coolstep(self) -> bool {
+        self.has_stallguard()
+    }
+
+    /// Highest RMS motor current the chip is rated for, mA
+    pub fn max_rms_current(self) -> u16 {
+        match self {
+            ChipVariant::Tmc2209 | ChipVariant::Tmc2226 => 2000,
+            ChipVariant::Tmc2208 | ChipVariant::Tmc2225 => 1400,
+        }
+    }
+
+    pub(crate) fn to_u8(self) -> u8 {
+        match self {
+            ChipVariant::Tmc2209 => 0,
+            ChipVariant::Tmc2226 => 1,
+            ChipVariant::Tmc2208 => 2,
+            ChipVariant::Tmc2225 => 3,
+        }
+    }
+
+    pub(crate) fn from_u8(value: u8) -> Option<ChipVariant> {
+        match value {
+            0 => Some(ChipVariant::Tmc2209),
+            1 => Some(ChipVariant::Tmc2226),
+            2 => Some(ChipVariant::Tmc2208),
+            3 => Some(ChipVariant::Tmc2225),
+            _ => None,
+        }
+    }
+}
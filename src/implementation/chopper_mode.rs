@@ -0,0 +1,83 @@
+use crate::structures::{
+    base_config::TMC2209_BaseConfig, chopper_mode::ChopperMode,
+    config::TMC2209_Config,
+};
+use crate::utils::calc::{
+    rpm_to_tstep, spreadcycle_chopper_settings, stealthchop_pwm_settings,
+    MotorParameters,
+};
+
+// stealthChop PWM settings that do not depend on the motor
+const PWM_FREQ: u8 = 1; // 2/683 fclk, ~35kHz with internal clock
+const PWM_REG: u8 = 8;
+const PWM_LIM: u8 = 12;
+
+impl ChopperMode {
+    /// Consistent chopper config for this mode and motor. TOFF, TBL, HSTRT
+    /// and HEND are calculated by [`spreadcycle_chopper_settings`] (used in
+    /// every mode, stealthChop needs TOFF > 0 too), PWM_OFS and PWM_GRAD
+    /// start values by [`stealthchop_pwm_settings`].
+    /// `base_config` gives sense resistor, motor full steps and driver
+    /// clock. Returns Err if motor parameters are not positive
+    pub fn preset_config(
+        &self,
+        base_config: &TMC2209_BaseConfig,
+        motor: &MotorParameters,
+    ) -> Result<TMC2209_Config, ()> {
+        let chopper = spreadcycle_chopper_settings(
+            motor,
+            base_config.r_sense,
+            base_config.fclk,
+        )?;
+        let mut config = TMC2209_Config {
+            interpolation: Some(true),
+            toff: Some(chopper.toff),
+            blank_time: Some(chopper.blank_time),
+            hysteresis_start: Some(chopper.hysteresis_start),
+            hysteresis_end: Some(chopper.hysteresis_end),
+            ..Default::default()
+        };
+
+        match *self {
+            ChopperMode::SpreadCycle => {
+                config.en_spreadcycle = Some(true);
+            }
+            ChopperMode::StealthChop => {
+                set_stealthchop_pwm(&mut config, base_config, motor)?;
+                // TSTEP is never below 0, so stealthChop is used at any speed
+                config.tpwmthrs = Some(0);
+            }
+            ChopperMode::Hybrid { switch_rpm } => {
+                set_stealthchop_pwm(&mut config, base_config, motor)?;
+                config.tpwmthrs = Some(rpm_to_tstep(
+                    switch_rpm,
+                    base_config.full_steps_per_rotation,
+                    base_config.fclk,
+                ));
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn set_stealthchop_pwm(
+    config: &mut TMC2209_Config,
+    base_config: &TMC2209_BaseConfig,
+    motor: &MotorParameters,
+) -> Result<(), ()> {
+    let pwm = stealthchop_pwm_settings(
+        motor,
+        base_config.full_steps_per_rotation,
+        base_config.fclk,
+    )?;
+    config.en_spreadcycle = Some(false);
+    config.pwm_autoscale = Some(true);
+    config.pwm_autograd = Some(true);
+    config.pwm_freq = Some(PWM_FREQ);
+    config.pwm_ofs = Some(pwm.pwm_ofs);
+    config.pwm_grad = Some(pwm.pwm_grad);
+    config.pwm_reg = Some(PWM_REG);
+    config.pwm_lim = Some(PWM_LIM);
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use crate::structures::{
-    config::TMC2209_Config,
+    base_config::TMC2209_BaseConfig, config::TMC2209_Config,
     registers_collection::TMC2209_ConfigRegistersChangesDetected,
+    saved_config::TMC2209_SavedConfig,
 };
 
 impl Default for TMC2209_Config {
@@ -10,6 +11,7 @@ impl Default for TMC2209_Config {
             r_sense: None,
             rms_current: None,
             ihold_multiplier: None,
+            hold_current: None,
             ihold_delay: None,
             interpolation: None,
             microsteps: None,
@@ -55,6 +57,97 @@ impl Default for TMC2209_Config {
 }
 
 impl TMC2209_Config {
+    /// Config that brings driver into the state described by saved config
+    /// (for example restored from flash). Only fields of registers that
+    /// were read from or written to the driver are set (see
+    /// `known_registers`), run current is skipped if it was never set,
+    /// fields the chip does not support are skipped too
+    pub fn from_saved_config(
+        saved_config: &TMC2209_SavedConfig,
+        base_config: &TMC2209_BaseConfig,
+    ) -> TMC2209_Config {
+        let stallguard = base_config.chip_variant.has_stallguard();
+        let coolstep = base_config.chip_variant.has_coolstep();
+        let known = &saved_config.known_registers;
+        let run_current_known =
+            known.ihold_irun && saved_config.rms_current > 0;
+
+        TMC2209_Config {
+            uart_address: Some(base_config.uart_address),
+            r_sense: Some(base_config.r_sense),
+            rms_current: Some(saved_config.rms_current)
+                .filter(|_| run_current_known),
+            ihold_multiplier: Some(base_config.ihold_multiplier),
+            hold_current: Some(saved_config.hold_current)
+                .filter(|hold| run_current_known && *hold > 0),
+            // IHOLD_IRUN can only be sent with known run current
+            ihold_delay: Some(saved_config.ihold_delay)
+                .filter(|_| run_current_known),
+            microsteps: Some(saved_config.microsteps)
+                .filter(|_| known.chopconf),
+            interpolation: Some(saved_config.interpolation)
+                .filter(|_| known.chopconf),
+            blank_time: Some(saved_config.blank_time)
+                .filter(|_| known.chopconf),
+            hysteresis_end: Some(saved_config.hysteresis_end)
+                .filter(|_| known.chopconf),
+            hysteresis_start: Some(saved_config.hysteresis_start)
+                .filter(|_| known.chopconf),
+            tpowerdown: Some(saved_config.tpowerdown)
+                .filter(|_| known.tpowerdown),
+            tpwmthrs: Some(saved_config.tpwmthrs).filter(|_| known.tpwmthrs),
+            sgthrs: Some(saved_config.sgthrs)
+                .filter(|_| stallguard && known.sgthrs),
+            tcoolthrs: Some(saved_config.tcoolthrs)
+                .filter(|_| coolstep && known.tcoolthrs),
+            en_spreadcycle: Some(saved_config.en_spreadcycle)
+                .filter(|_| known.gconf),
+            pdn_disable: Some(saved_config.pdn_disable).filter(|_| known.gconf),
+            pwm_ofs: Some(saved_config.pwm_ofs).filter(|_| known.pwmconf),
+            pwm_grad: Some(saved_config.pwm_grad).filter(|_| known.pwmconf),
+            pwm_freq: Some(saved_config.pwm_freq).filter(|_| known.pwmconf),
+            pwm_autoscale: Some(saved_config.pwm_autoscale)
+                .filter(|_| known.pwmconf),
+            pwm_autograd: Some(saved_config.pwm_autograd)
+                .filter(|_| known.pwmconf),
+            pwm_reg: Some(saved_config.pwm_reg).filter(|_| known.pwmconf),
+            pwm_lim: Some(saved_config.pwm_lim).filter(|_| known.pwmconf),
+            freewheel: Some(saved_config.freewheel).filter(|_| known.pwmconf),
+            internal_rsense: Some(saved_config.internal_rsense)
+                .filter(|_| known.gconf),
+            i_scale_analog: Some(saved_config.i_scale_analog)
+                .filter(|_| known.gconf),
+            mstep_reg_select: Some(saved_config.mstep_reg_select)
+                .filter(|_| known.gconf),
+            multistep_filt: Some(saved_config.multistep_filt)
+                .filter(|_| known.gconf),
+            index_otpw: Some(saved_config.index_otpw).filter(|_| known.gconf),
+            index_step: Some(saved_config.index_step).filter(|_| known.gconf),
+            senddelay: Some(saved_config.senddelay).filter(|_| known.slaveconf),
+            semin: Some(saved_config.semin)
+                .filter(|_| coolstep && known.coolconf),
+            seup: Some(saved_config.seup)
+                .filter(|_| coolstep && known.coolconf),
+            semax: Some(saved_config.semax)
+                .filter(|_| coolstep && known.coolconf),
+            sedn: Some(saved_config.sedn)
+                .filter(|_| coolstep && known.coolconf),
+            seimin: Some(saved_config.seimin)
+                .filter(|_| coolstep && known.coolconf),
+            toff: Some(saved_config.toff).filter(|_| known.chopconf),
+            vsense: Some(saved_config.vsense).filter(|_| known.chopconf),
+            dedge: Some(saved_config.dedge).filter(|_| known.chopconf),
+            diss2g: Some(saved_config.diss2g).filter(|_| known.chopconf),
+            diss2vs: Some(saved_config.diss2vs).filter(|_| known.chopconf),
+            // Factory clock and temperature trim, only known if read from
+            // the chip (or set by the user)
+            fclktrim: Some(saved_config.fclktrim)
+                .filter(|_| known.factory_conf),
+            ottrim: Some(saved_config.ottrim).filter(|_| known.factory_conf),
+            shaft: Some(saved_config.shaft).filter(|_| known.gconf),
+        }
+    }
+
     pub fn which_registers_changed(
         &self,
     ) -> TMC2209_ConfigRegistersChangesDetected {
@@ -102,10 +195,19 @@ fn is_chopconf_changed(config: &TMC2209_Config) -> bool {
         || config.blank_time.is_some()
         || config.toff.is_some()
         || config.vsense.is_some()
+        // Run current also selects vsense in CHOPCONF, and IHOLD_IRUN
+        // needs vsense
+        || is_ihold_irun_changed(config)
 }
 
 fn is_ihold_irun_changed(config: &TMC2209_Config) -> bool {
-    config.ihold_delay.is_some() || config.rms_current.is_some()
+    config.ihold_delay.is_some()
+        || config.rms_current.is_some()
+        || config.hold_current.is_some()
+        || config.ihold_multiplier.is_some()
+        // Current scaling changes, IRUN/IHOLD keep the same current
+        || config.i_scale_analog.is_some()
+        || config.internal_rsense.is_some()
 }
 
 fn is_slaveconf_changed(config: &TMC2209_Config) -> bool {
@@ -0,0 +1,264 @@
+use crate::structures::{
+    base_config::TMC2209_BaseConfig,
+    chopper_mode::ChopperMode,
+    config::TMC2209_Config,
+    config_builder::{
+        BlankTime, FreewheelMode, Hend, Hstrt, IholdDelay, Microsteps, PwmFreq,
+        Sgthrs, TMC2209_ConfigBuilder, Toff,
+    },
+    config_violations::ConfigViolations,
+};
+use crate::utils::calc::MotorParameters;
+
+impl Microsteps {
+    /// Value of `TMC2209_Config::microsteps` (0 means fullstep)
+    pub fn count(self) -> u32 {
+        match self {
+            Microsteps::Fullstep => 0,
+            Microsteps::M2 => 2,
+            Microsteps::M4 => 4,
+            Microsteps::M8 => 8,
+            Microsteps::M16 => 16,
+            Microsteps::M32 => 32,
+            Microsteps::M64 => 64,
+            Microsteps::M128 => 128,
+            Microsteps::M256 => 256,
+        }
+    }
+}
+
+impl BlankTime {
+    pub fn register_value(self) -> u32 {
+        match self {
+            BlankTime::Clk16 => 0,
+            BlankTime::Clk24 => 1,
+            BlankTime::Clk32 => 2,
+            BlankTime::Clk40 => 3,
+        }
+    }
+}
+
+impl PwmFreq {
+    pub fn register_value(self) -> u8 {
+        match self {
+            PwmFreq::Div1024 => 0,
+            PwmFreq::Div683 => 1,
+            PwmFreq::Div512 => 2,
+            PwmFreq::Div410 => 3,
+        }
+    }
+}
+
+impl FreewheelMode {
+    pub fn register_value(self) -> u8 {
+        match self {
+            FreewheelMode::Normal => 0,
+            FreewheelMode::Freewheeling => 1,
+            FreewheelMode::ShortViaLowSide => 2,
+            FreewheelMode::ShortViaHighSide => 3,
+        }
+    }
+}
+
+// Bounded register fields. `new()` panics on out of range value, so using
+// it in const context turns the mistake into a compile error
+macro_rules! impl_bounded_field {
+    ($name:ident, $max:expr) => {
+        impl $name {
+            pub const MAX: u8 = $max;
+
+            pub const fn new(value: u8) -> $name {
+                assert!(
+                    value as u16 <= $max as u16,
+                    concat!(stringify!($name), " is out of range")
+                );
+                $name(value)
+            }
+
+            pub const fn try_new(value: u8) -> Option<$name> {
+                if value as u16 <= $max as u16 {
+                    Some($name(value))
+                } else {
+                    None
+                }
+            }
+
+            pub const fn get(self) -> u8 {
+                self.0
+            }
+        }
+    };
+}
+
+impl_bounded_field!(Toff, 15);
+impl_bounded_field!(Hstrt, 7);
+impl_bounded_field!(Hend, 15);
+impl_bounded_field!(IholdDelay, 15);
+impl_bounded_field!(Sgthrs, 255);
+
+// Setters for fields that do not need special types
+macro_rules! setters {
+    ($($field:ident: $type:ty),* $(,)?) => {
+        $(
+            pub fn $field(mut self, $field: $type) -> Self {
+                self.config.$field = Some($field);
+                self
+            }
+        )*
+    };
+}
+
+impl TMC2209_ConfigBuilder {
+    pub fn new() -> TMC2209_ConfigBuilder {
+        TMC2209_ConfigBuilder {
+            config: TMC2209_Config::default(),
+        }
+    }
+
+    /// Continue building from existing config
+    pub fn from_config(config: TMC2209_Config) -> TMC2209_ConfigBuilder {
+        TMC2209_ConfigBuilder { config }
+    }
+
+    pub fn microsteps(mut self, microsteps: Microsteps) -> Self {
+        self.config.microsteps = Some(microsteps.count());
+        self
+    }
+
+    pub fn blank_time(mut self, blank_time: BlankTime) -> Self {
+        self.config.blank_time = Some(blank_time.register_value());
+        self
+    }
+
+    pub fn pwm_freq(mut self, pwm_freq: PwmFreq) -> Self {
+        self.config.pwm_freq = Some(pwm_freq.register_value());
+        self
+    }
+
+    pub fn freewheel(mut self, freewheel: FreewheelMode) -> Self {
+        self.config.freewheel = Some(freewheel.register_value());
+        self
+    }
+
+    /// Only selects the chopper (en_spreadcycle). For
+    /// [`ChopperMode::Hybrid`] set the switch velocity with `tpwmthrs()`, or
+    /// use [`Self::chopper_preset`]
+    pub fn chopper_mode(mut self, chopper_mode: ChopperMode) -> Self {
+        self.config.en_spreadcycle =
+            Some(chopper_mode == ChopperMode::SpreadCycle);
+        self
+    }
+
+    /// Overwrite chopper related fields with preset calculated for motor
+    /// (see [`ChopperMode::preset_config`]). Returns Err if motor
+    /// parameters are not positive
+    pub fn chopper_preset(
+        mut self,
+        chopper_mode: ChopperMode,
+        base_config: &TMC2209_BaseConfig,
+        motor: &MotorParameters,
+    ) -> Result<Self, ()> {
+        let preset = chopper_mode.preset_config(base_config, motor)?;
+        self.config.en_spreadcycle = preset.en_spreadcycle;
+        self.config.interpolation = preset.interpolation;
+        self.config.toff = preset.toff;
+        self.config.blank_time = preset.blank_time;
+        self.config.hysteresis_start = preset.hysteresis_start;
+        self.config.hysteresis_end = preset.hysteresis_end;
+        self.config.tpwmthrs = preset.tpwmthrs;
+        self.config.pwm_autoscale = preset.pwm_autoscale;
+        self.config.pwm_autograd = preset.pwm_autograd;
+        self.config.pwm_freq = preset.pwm_freq;
+        self.config.pwm_ofs = preset.pwm_ofs;
+        self.config.pwm_grad = preset.pwm_grad;
+        self.config.pwm_reg = preset.pwm_reg;
+        self.config.pwm_lim = preset.pwm_lim;
+        Ok(self)
+    }
+
+    pub fn toff(mut self, toff: Toff) -> Self {
+        self.config.toff = Some(toff.get() as u32);
+        self
+    }
+
+    pub fn hysteresis_start(mut self, hysteresis_start: Hstrt) -> Self {
+        self.config.hysteresis_start = Some(hysteresis_start.get() as u32);
+        self
+    }
+
+    pub fn hysteresis_end(mut self, hysteresis_end: Hend) -> Self {
+        self.config.hysteresis_end = Some(hysteresis_end.get() as u32);
+        self
+    }
+
+    pub fn ihold_delay(mut self, ihold_delay: IholdDelay) -> Self {
+        self.config.ihold_delay = Some(ihold_delay.get());
+        self
+    }
+
+    pub fn sgthrs(mut self, sgthrs: Sgthrs) -> Self {
+        self.config.sgthrs = Some(sgthrs.get() as u32);
+        self
+    }
+
+    setters! {
+        uart_address: u8,
+        r_sense: f32,
+        rms_current: u16,
+        ihold_multiplier: f32,
+        hold_current: u16,
+        interpolation: bool,
+        tpowerdown: u32,
+        tpwmthrs: u32,
+        tcoolthrs: u32,
+        pdn_disable: bool,
+        pwm_ofs: u8,
+        pwm_grad: u8,
+        pwm_autoscale: bool,
+        pwm_autograd: bool,
+        pwm_reg: u8,
+        pwm_lim: u8,
+        internal_rsense: bool,
+        i_scale_analog: bool,
+        mstep_reg_select: bool,
+        multistep_filt: bool,
+        index_otpw: bool,
+        index_step: bool,
+        senddelay: u8,
+        semin: u16,
+        seup: u16,
+        semax: u16,
+        sedn: u16,
+        seimin: bool,
+        vsense: bool,
+        dedge: bool,
+        diss2g: bool,
+        diss2vs: bool,
+        fclktrim: u8,
+        ottrim: u8,
+        shaft: bool,
+    }
+
+    /// All violations of the config built so far
+    pub fn validate(&self) -> ConfigViolations {
+        self.config.validate()
+    }
+
+    /// Validated config
+    // Violations are stored inline, there is no allocator in no_std
+    #[allow(clippy::result_large_err)]
+    pub fn build(self) -> Result<TMC2209_Config, ConfigViolations> {
+        let violations = self.config.validate();
+        if violations.is_empty() {
+            Ok(self.config)
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+impl Default for TMC2209_ConfigBuilder {
+    fn default() -> Self {
+        TMC2209_ConfigBuilder::new()
+    }
+}
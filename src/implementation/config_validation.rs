@@ -0,0 +1,116 @@
+use crate::structures::{
+    chip_variant::ChipVariant,
+    config::TMC2209_Config,
+    config_violations::{ConfigViolation, ConfigViolations},
+};
+use crate::utils::calc::TSTEP_MAX;
+
+impl TMC2209_Config {
+    /// Check every set field against its register range and the datasheet
+    /// constraints between fields. Returns all found violations (empty if
+    /// config is valid)
+    pub fn validate(&self) -> ConfigViolations {
+        let mut violations = ConfigViolations::new();
+        let mut range = |field, value: Option<u32>, min, max| {
+            if let Some(value) = value {
+                if value < min || value > max {
+                    violations.push(ConfigViolation::OutOfRange {
+                        field,
+                        value,
+                        min,
+                        max,
+                    });
+                }
+            }
+        };
+
+        range("uart_address", self.uart_address.map(u32::from), 0, 3);
+        range("ihold_delay", self.ihold_delay.map(u32::from), 0, 15);
+        range("blank_time", self.blank_time, 0, 3);
+        range("hysteresis_end", self.hysteresis_end, 0, 15);
+        range("hysteresis_start", self.hysteresis_start, 0, 7);
+        range("tpowerdown", self.tpowerdown, 0, 255);
+        range("tpwmthrs", self.tpwmthrs, 0, TSTEP_MAX);
+        range("sgthrs", self.sgthrs, 0, 255);
+        range("tcoolthrs", self.tcoolthrs, 0, TSTEP_MAX);
+        range("pwm_freq", self.pwm_freq.map(u32::from), 0, 3);
+        range("pwm_reg", self.pwm_reg.map(u32::from), 1, 15);
+        range("pwm_lim", self.pwm_lim.map(u32::from), 0, 15);
+        range("freewheel", self.freewheel.map(u32::from), 0, 3);
+        range("senddelay", self.senddelay.map(u32::from), 0, 15);
+        range("semin", self.semin.map(u32::from), 0, 15);
+        range("seup", self.seup.map(u32::from), 0, 3);
+        range("semax", self.semax.map(u32::from), 0, 15);
+        range("sedn", self.sedn.map(u32::from), 0, 3);
+        range("toff", self.toff, 0, 15);
+        range("fclktrim", self.fclktrim.map(u32::from), 0, 31);
+        range("ottrim", self.ottrim.map(u32::from), 0, 3);
+
+        if let Some(microsteps) = self.microsteps {
+            if microsteps != 0
+                && (microsteps == 1
+                    || microsteps > 256
+                    || !microsteps.is_power_of_two())
+            {
+                violations.push(ConfigViolation::InvalidMicrosteps(microsteps));
+            }
+        }
+
+        if let Some(r_sense) = self.r_sense {
+            if !(r_sense > 0.0 && r_sense.is_finite()) {
+                violations
+                    .push(ConfigViolation::InvalidValue { field: "r_sense" });
+            }
+        }
+
+        if let Some(ihold_multiplier) = self.ihold_multiplier {
+            if !(0.0..=1.0).contains(&ihold_multiplier) {
+                violations.push(ConfigViolation::InvalidValue {
+                    field: "ihold_multiplier",
+                });
+            }
+        }
+
+        // Effective values are HSTRT + 1 and HEND - 3, sum must be <= 16
+        if let (Some(hysteresis_start), Some(hysteresis_end)) =
+            (self.hysteresis_start, self.hysteresis_end)
+        {
+            if hysteresis_start + hysteresis_end > 18 {
+                violations.push(ConfigViolation::HysteresisTooHigh {
+                    hysteresis_start,
+                    hysteresis_end,
+                });
+            }
+        }
+
+        if let (Some(1), Some(blank_time)) = (self.toff, self.blank_time) {
+            if blank_time < 2 {
+                violations.push(ConfigViolation::ToffRequiresLongerBlankTime);
+            }
+        }
+
+        violations
+    }
+
+    /// Fields the chip does not support (see [`ChipVariant`]). Empty if the
+    /// config can be sent to the chip
+    pub fn check_chip_support(&self, chip: ChipVariant) -> ConfigViolations {
+        let mut violations = ConfigViolations::new();
+        let mut unsupported = |field, is_set: bool, supported: bool| {
+            if is_set && !supported {
+                violations
+                    .push(ConfigViolation::UnsupportedByChip { field, chip });
+            }
+        };
+
+        unsupported("sgthrs", self.sgthrs.is_some(), chip.has_stallguard());
+        unsupported("tcoolthrs", self.tcoolthrs.is_some(), chip.has_coolstep());
+        unsupported("semin", self.semin.is_some(), chip.has_coolstep());
+        unsupported("seup", self.seup.is_some(), chip.has_coolstep());
+        unsupported("semax", self.semax.is_some(), chip.has_coolstep());
+        unsupported("sedn", self.sedn.is_some(), chip.has_coolstep());
+        unsupported("seimin", self.seimin.is_some(), chip.has_coolstep());
+
+        violations
+    }
+}
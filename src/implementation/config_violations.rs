@@ -0,0 +1,38 @@
+use crate::structures::config_violations::{
+    ConfigViolation, ConfigViolations, MAX_CONFIG_VIOLATIONS,
+};
+
+impl ConfigViolations {
+    pub fn new() -> ConfigViolations {
+        ConfigViolations {
+            items: [None; MAX_CONFIG_VIOLATIONS],
+            len: 0,
+        }
+    }
+
+    /// Add violation (ignored if list is full)
+    pub fn push(&mut self, violation: ConfigViolation) {
+        if self.len < MAX_CONFIG_VIOLATIONS {
+            self.items[self.len] = Some(violation);
+            self.len += 1;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ConfigViolation> {
+        self.items[..self.len].iter().flatten()
+    }
+}
+
+impl Default for ConfigViolations {
+    fn default() -> Self {
+        ConfigViolations::new()
+    }
+}
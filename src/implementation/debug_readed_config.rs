@@ -0,0 +1,68 @@
+use crate::structures::{
+    debug_readed_config::TMC2209_DebugConfig,
+    register_view::{FieldChange, FieldChanges, FieldValue},
+};
+
+// Compare listed fields of two configs, pushing changed ones
+macro_rules! diff_fields {
+    ($changes:ident, $old:ident, $new:ident,
+     $($field:ident: $kind:ident),* $(,)?) => {
+        $(
+            let old = diff_fields!(@value $kind, $old.$field);
+            let new = diff_fields!(@value $kind, $new.$field);
+            if old != new {
+                $changes.push(FieldChange {
+                    field: stringify!($field),
+                    old,
+                    new,
+                });
+            }
+        )*
+    };
+    (@value bool, $value:expr) => {
+        FieldValue::Bool($value)
+    };
+    (@value uint, $value:expr) => {
+        FieldValue::Uint($value as u32)
+    };
+}
+
+impl TMC2209_DebugConfig {
+    /// Fields that differ in `other` (for example config read before and
+    /// after a change)
+    pub fn diff(&self, other: &TMC2209_DebugConfig) -> FieldChanges {
+        let mut changes = FieldChanges::new();
+        diff_fields!(changes, self, other,
+            microsteps: uint,
+            interpolation: bool,
+            blank_time: uint,
+            hysteresis_end: uint,
+            hysteresis_start: uint,
+            en_spreadcycle: bool,
+            pdn_disable: bool,
+            pwm_ofs: uint,
+            pwm_grad: uint,
+            pwm_freq: uint,
+            pwm_autoscale: bool,
+            pwm_autograd: bool,
+            pwm_reg: uint,
+            pwm_lim: uint,
+            freewheel: uint,
+            internal_rsense: bool,
+            i_scale_analog: bool,
+            mstep_reg_select: bool,
+            multistep_filt: bool,
+            index_otpw: bool,
+            index_step: bool,
+            toff: uint,
+            vsense: bool,
+            dedge: bool,
+            diss2g: bool,
+            diss2vs: bool,
+            fclktrim: uint,
+            ottrim: uint,
+            shaft: bool,
+        );
+        changes
+    }
+}
@@ -0,0 +1,20 @@
+use crate::structures::drv_status::{TMC2209_DrvStatus, TemperatureLevel};
+
+impl TMC2209_DrvStatus {
+    /// Highest temperature threshold exceeded
+    pub fn temperature_level(&self) -> TemperatureLevel {
+        if self.ot {
+            TemperatureLevel::Overtemperature
+        } else if self.t157 {
+            TemperatureLevel::Above157
+        } else if self.t150 {
+            TemperatureLevel::Above150
+        } else if self.t143 {
+            TemperatureLevel::Above143
+        } else if self.t120 || self.otpw {
+            TemperatureLevel::Above120
+        } else {
+            TemperatureLevel::Normal
+        }
+    }
+}
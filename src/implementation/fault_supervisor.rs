@@ -0,0 +1,272 @@
+use crate::structures::{
+    config::TMC2209_Config,
+    fault_supervisor::{
+        FaultAction, FaultClass, FaultPolicies, FaultPolicy, FaultRecord,
+        FaultStatus, FaultSupervisor, FAULT_HISTORY_LEN,
+    },
+};
+use crate::utils::timing::UartTransport;
+use crate::TMC2209UART;
+
+const FAULT_CLASSES: [FaultClass; 4] = [
+    FaultClass::ShortToGround,
+    FaultClass::ShortToSupply,
+    FaultClass::OpenLoad,
+    FaultClass::DriverError,
+];
+
+impl FaultClass {
+    fn index(self) -> usize {
+        match self {
+            FaultClass::ShortToGround => 0,
+            FaultClass::ShortToSupply => 1,
+            FaultClass::OpenLoad => 2,
+            FaultClass::DriverError => 3,
+        }
+    }
+}
+
+impl Default for FaultPolicies {
+    fn default() -> Self {
+        let retry = FaultPolicy::Retry {
+            cool_down_ms: 1000,
+            max_retries: 3,
+        };
+        FaultPolicies {
+            short_to_ground: retry,
+            short_to_supply: retry,
+            // Open load is also reported at standstill and high velocity
+            open_load: FaultPolicy::LogOnly,
+            driver_error: retry,
+        }
+    }
+}
+
+impl FaultPolicies {
+    pub fn get(&self, class: FaultClass) -> FaultPolicy {
+        match class {
+            FaultClass::ShortToGround => self.short_to_ground,
+            FaultClass::ShortToSupply => self.short_to_supply,
+            FaultClass::OpenLoad => self.open_load,
+            FaultClass::DriverError => self.driver_error,
+        }
+    }
+}
+
+impl FaultSupervisor {
+    pub fn new(policies: FaultPolicies) -> FaultSupervisor {
+        FaultSupervisor {
+            policies,
+            status: FaultStatus::Running,
+            active: [false; 4],
+            retries: [0; 4],
+            toff: 0,
+            history: [None; FAULT_HISTORY_LEN],
+            history_next: 0,
+            history_len: 0,
+        }
+    }
+
+    /// Read fault flags, record new faults and react to them. Enables
+    /// output again when retry cool-down is over. `now_ms` is any
+    /// monotonic time in ms. Output is switched off with TOFF = 0 and the
+    /// saved TOFF is restored, so saved config must be loaded first (see
+    /// `init_saved_config()`). Returns Err if saved TOFF is 0
+    pub fn poll<Uart: UartTransport>(
+        &mut self,
+        driver: &mut TMC2209UART<Uart>,
+        now_ms: u64,
+    ) -> Result<FaultStatus, ()> {
+        match self.status {
+            FaultStatus::RetryPending { retry_at_ms, .. }
+                if now_ms >= retry_at_ms =>
+            {
+                self.enable_output(driver)?;
+            }
+            // Checked before any fault is recorded, so it is handled again
+            // once saved config is loaded
+            FaultStatus::Running if driver.get_saved_config().toff == 0 => {
+                return Err(());
+            }
+            FaultStatus::Running => {}
+            // Flags of disabled power stage tell nothing
+            _ => return Ok(self.status),
+        }
+
+        let drv_status = driver.read_drv_status()?;
+        let gstat = driver.read_gstat()?;
+        let flags = [
+            drv_status.s2ga || drv_status.s2gb,
+            drv_status.s2vsa || drv_status.s2vsb,
+            drv_status.ola || drv_status.olb,
+            gstat.drv_err,
+        ];
+        if gstat.drv_err {
+            driver.clear_gstat(&gstat)?;
+        }
+
+        for class in FAULT_CLASSES.iter() {
+            let index = class.index();
+            let is_new = flags[index] && !self.active[index];
+            self.active[index] = flags[index];
+            if is_new {
+                self.handle_fault(driver, *class, now_ms)?;
+            }
+        }
+
+        Ok(self.status)
+    }
+
+    fn handle_fault<Uart: UartTransport>(
+        &mut self,
+        driver: &mut TMC2209UART<Uart>,
+        class: FaultClass,
+        now_ms: u64,
+    ) -> Result<(), ()> {
+        let action = match self.policies.get(class) {
+            FaultPolicy::LogOnly => FaultAction::Logged,
+            FaultPolicy::DisableOutput => FaultAction::OutputDisabled,
+            FaultPolicy::Latch => FaultAction::Latched,
+            FaultPolicy::Retry {
+                cool_down_ms,
+                max_retries,
+            } => {
+                let retries = &mut self.retries[class.index()];
+                if *retries < max_retries {
+                    *retries += 1;
+                    FaultAction::RetryScheduled {
+                        attempt: *retries,
+                        retry_at_ms: now_ms + cool_down_ms as u64,
+                    }
+                } else {
+                    // Retry budget is spent, do not loop forever
+                    FaultAction::Latched
+                }
+            }
+        };
+
+        tmc_warn!("fault {:?} at {} ms: {:?}", class, now_ms, action);
+        self.record(FaultRecord {
+            class,
+            timestamp_ms: now_ms,
+            action,
+        });
+
+        let status = match action {
+            FaultAction::Logged => return Ok(()),
+            FaultAction::OutputDisabled => {
+                FaultStatus::OutputDisabled { class }
+            }
+            FaultAction::RetryScheduled { retry_at_ms, .. } => {
+                FaultStatus::RetryPending { class, retry_at_ms }
+            }
+            FaultAction::Latched => FaultStatus::Latched { class },
+        };
+
+        // Several faults in one poll: keep the strongest reaction
+        if severity(&status) > severity(&self.status) {
+            if self.status == FaultStatus::Running {
+                self.disable_output(driver)?;
+            }
+            self.status = status;
+        }
+        Ok(())
+    }
+
+    // TOFF = 0 in saved config is either not loaded from the driver or the
+    // output is already off, there would be nothing to restore later
+    fn disable_output<Uart: UartTransport>(
+        &mut self,
+        driver: &mut TMC2209UART<Uart>,
+    ) -> Result<(), ()> {
+        let toff = driver.get_saved_config().toff;
+        if toff == 0 {
+            return Err(());
+        }
+        self.toff = toff;
+        let config = TMC2209_Config {
+            toff: Some(0),
+            ..Default::default()
+        };
+        driver.apply_config(&config).map_err(|_| ())
+    }
+
+    fn enable_output<Uart: UartTransport>(
+        &mut self,
+        driver: &mut TMC2209UART<Uart>,
+    ) -> Result<(), ()> {
+        if self.toff == 0 {
+            return Err(());
+        }
+        let config = TMC2209_Config {
+            toff: Some(self.toff),
+            ..Default::default()
+        };
+        driver.apply_config(&config).map_err(|_| ())?;
+        tmc_info!("fault supervisor: output enabled (toff {})", self.toff);
+        self.status = FaultStatus::Running;
+        self.active = [false; 4];
+        Ok(())
+    }
+
+    fn record(&mut self, record: FaultRecord) {
+        self.history[self.history_next] = Some(record);
+        self.history_next = (self.history_next + 1) % FAULT_HISTORY_LEN;
+        self.history_len = (self.history_len + 1).min(FAULT_HISTORY_LEN);
+    }
+
+    /// Enable output disabled by [`FaultPolicy::DisableOutput`] or pending
+    /// retry. Latched faults need [`Self::clear_latch`]
+    pub fn resume<Uart: UartTransport>(
+        &mut self,
+        driver: &mut TMC2209UART<Uart>,
+    ) -> Result<(), ()> {
+        match self.status {
+            FaultStatus::OutputDisabled { .. }
+            | FaultStatus::RetryPending { .. } => self.enable_output(driver),
+            FaultStatus::Running => Ok(()),
+            FaultStatus::Latched { .. } => Err(()),
+        }
+    }
+
+    /// Enable output after latched fault (for example after the user
+    /// checked motor wiring) and reset retry budgets
+    pub fn clear_latch<Uart: UartTransport>(
+        &mut self,
+        driver: &mut TMC2209UART<Uart>,
+    ) -> Result<(), ()> {
+        self.retries = [0; 4];
+        match self.status {
+            FaultStatus::Running => Ok(()),
+            _ => self.enable_output(driver),
+        }
+    }
+
+    pub fn status(&self) -> FaultStatus {
+        self.status
+    }
+
+    /// Recorded faults, oldest first
+    pub fn history(&self) -> impl Iterator<Item = &FaultRecord> {
+        let start = (self.history_next + FAULT_HISTORY_LEN - self.history_len)
+            % FAULT_HISTORY_LEN;
+        (0..self.history_len).filter_map(move |i| {
+            self.history[(start + i) % FAULT_HISTORY_LEN].as_ref()
+        })
+    }
+
+    pub fn clear_history(&mut self) {
+        self.history = [None; FAULT_HISTORY_LEN];
+        self.history_next = 0;
+        self.history_len = 0;
+    }
+}
+
+fn severity(status: &FaultStatus) -> u8 {
+    match status {
+        FaultStatus::Running => 0,
+        FaultStatus::RetryPending { .. } => 1,
+        FaultStatus::OutputDisabled { .. } => 2,
+        FaultStatus::Latched { .. } => 3,
+    }
+}
@@ -1,5 +1,21 @@
 pub mod base_config;
+pub mod bus_scan;
+pub mod chip_identity;
+pub mod chip_variant;
+pub mod chopper_mode;
 pub mod config;
+pub mod config_builder;
+pub mod config_validation;
+pub mod config_violations;
+pub mod debug_readed_config;
+pub mod drv_status;
+pub mod fault_supervisor;
+pub mod motion_monitor;
+pub mod otp;
+pub mod persisted_config;
+pub mod register_view;
 pub mod registers_collection;
 pub mod saved_config;
-pub mod tmc2209_uart_impl;
\ No newline at end of file
+pub mod stealthchop_autotune;
+pub mod thermal_supervisor;
+pub mod tmc2209_uart_impl;
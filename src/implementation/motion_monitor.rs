@@ -0,0 +1,131 @@
+use crate::structures::motion_monitor::{
+    Debounced, MotionEvent, MotionMonitor, MotionMonitorOptions,
+};
+use crate::utils::calc::tstep_to_rpm;
+use crate::utils::timing::UartTransport;
+use crate::TMC2209UART;
+
+impl Default for MotionMonitorOptions {
+    fn default() -> Self {
+        MotionMonitorOptions {
+            debounce: 3,
+            open_load_min_rpm: 10.0,
+            open_load_max_rpm: 300.0,
+            stall_min_rpm: 30.0,
+        }
+    }
+}
+
+impl Debounced {
+    /// Returns new state when it changed. `None` sample means the
+    /// condition can not be checked now, state is kept
+    fn update(&mut self, sample: Option<bool>, debounce: u8) -> Option<bool> {
+        match sample {
+            Some(sample) if sample != self.active => {
+                self.count = self.count.saturating_add(1);
+                if self.count >= debounce {
+                    self.active = sample;
+                    self.count = 0;
+                    return Some(sample);
+                }
+            }
+            _ => self.count = 0,
+        }
+        None
+    }
+}
+
+impl MotionMonitor {
+    pub fn new(options: MotionMonitorOptions) -> MotionMonitor {
+        MotionMonitor {
+            options,
+            disconnected: Debounced::default(),
+            stalled: Debounced::default(),
+        }
+    }
+
+    /// Read driver state and return an event if motor state changed.
+    /// Returns at most one event, if both states change the stall event
+    /// comes with the next poll
+    pub fn poll<Uart: UartTransport>(
+        &mut self,
+        driver: &mut TMC2209UART<Uart>,
+    ) -> Result<Option<MotionEvent>, ()> {
+        let event = self.detect_event(driver)?;
+        if let Some(event) = event {
+            tmc_warn!("motion monitor: {:?}", event);
+        }
+        Ok(event)
+    }
+
+    fn detect_event<Uart: UartTransport>(
+        &mut self,
+        driver: &mut TMC2209UART<Uart>,
+    ) -> Result<Option<MotionEvent>, ()> {
+        let tstep = driver.read_tstep()?;
+        let velocity_rpm = tstep_to_rpm(
+            tstep,
+            driver.base_config.full_steps_per_rotation,
+            driver.base_config.fclk,
+        );
+        let drv_status = driver.read_drv_status()?;
+
+        let open_load = drv_status.ola || drv_status.olb;
+        let open_load_valid = velocity_rpm >= self.options.open_load_min_rpm
+            && velocity_rpm <= self.options.open_load_max_rpm;
+        let sample = Some(open_load).filter(|_| open_load_valid);
+        match self.disconnected.update(sample, self.options.debounce) {
+            Some(true) => {
+                return Ok(Some(MotionEvent::Disconnected {
+                    phase_a: drv_status.ola,
+                    phase_b: drv_status.olb,
+                }))
+            }
+            Some(false) => return Ok(Some(MotionEvent::Reconnected)),
+            None => {}
+        }
+
+        // SG_RESULT of a disconnected motor means nothing
+        if !driver.base_config.chip_variant.has_stallguard()
+            || self.disconnected.active
+        {
+            return Ok(None);
+        }
+        let saved_config = driver.get_saved_config();
+        // StallGuard4 only works in stealthChop, its output is enabled
+        // between TCOOLTHRS and TPWMTHRS
+        let stall_valid = !saved_config.en_spreadcycle
+            && velocity_rpm >= self.options.stall_min_rpm
+            && (saved_config.tcoolthrs == 0 || tstep <= saved_config.tcoolthrs)
+            && tstep > saved_config.tpwmthrs;
+        let stall_level = saved_config.sgthrs * 2;
+        let sg_result = if stall_valid {
+            Some(driver.read_sg_result()?)
+        } else {
+            None
+        };
+        let sample = sg_result.map(|sg_result| sg_result as u32 <= stall_level);
+        match self.stalled.update(sample, self.options.debounce) {
+            Some(true) => Ok(Some(MotionEvent::Stalled {
+                sg_result: sg_result.unwrap_or(0),
+                velocity_rpm,
+            })),
+            Some(false) => Ok(Some(MotionEvent::StallCleared)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn is_disconnected(&self) -> bool {
+        self.disconnected.active
+    }
+
+    pub fn is_stalled(&self) -> bool {
+        self.stalled.active
+    }
+
+    /// Forget detected states, for example after the motor was stopped
+    pub fn reset(&mut self) {
+        self.disconnected = Debounced::default();
+        self.stalled = Debounced::default();
+    }
+}
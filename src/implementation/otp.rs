@@ -0,0 +1,106 @@
+use crate::structures::otp::{
+    OtpBit, OtpConfirmToken, OtpError, TMC2209_Otp, TMC2209_OtpPowerUpConfig,
+    OTP_MAGIC,
+};
+
+// Reset defaults selected by OTP fields (datasheet, OTP memory)
+const OTP_TPWMTHRS: [u32; 8] = [0, 200, 300, 400, 500, 800, 1200, 4000];
+const OTP_IHOLDDELAY: [u8; 4] = [1, 2, 4, 8];
+const OTP_IHOLD: [u8; 4] = [16, 2, 8, 24];
+const OTP_PWM_GRAD: [u8; 16] = [
+    14, 16, 18, 21, 24, 27, 31, 35, 40, 46, 52, 59, 67, 77, 88, 100,
+];
+
+impl OtpBit {
+    /// OTP0.0..4 OTP_FCLKTRIM
+    pub const FCLKTRIM_0: OtpBit = OtpBit { byte: 0, bit: 0 };
+    /// OTP0.5 OTP_OTTRIM
+    pub const OTTRIM: OtpBit = OtpBit { byte: 0, bit: 5 };
+    /// OTP0.6 OTP_internalRsense
+    pub const INTERNAL_RSENSE: OtpBit = OtpBit { byte: 0, bit: 6 };
+    /// OTP0.7 OTP_TBL
+    pub const TBL: OtpBit = OtpBit { byte: 0, bit: 7 };
+    /// OTP1.4 OTP_PWM_AUTOGRAD
+    pub const PWM_AUTOGRAD: OtpBit = OtpBit { byte: 1, bit: 4 };
+    /// OTP2.0 OTP_PWM_OFS
+    pub const PWM_OFS: OtpBit = OtpBit { byte: 2, bit: 0 };
+    /// OTP2.1 OTP_PWM_REG
+    pub const PWM_REG: OtpBit = OtpBit { byte: 2, bit: 1 };
+    /// OTP2.2 OTP_PWM_FREQ
+    pub const PWM_FREQ: OtpBit = OtpBit { byte: 2, bit: 2 };
+    /// OTP2.7 OTP_en_spreadCycle
+    pub const EN_SPREADCYCLE: OtpBit = OtpBit { byte: 2, bit: 7 };
+
+    pub fn new(byte: u8, bit: u8) -> Result<OtpBit, OtpError> {
+        if byte > 2 || bit > 7 {
+            return Err(OtpError::InvalidBit);
+        }
+        Ok(OtpBit { byte, bit })
+    }
+
+    pub fn byte(&self) -> u8 {
+        self.byte
+    }
+
+    pub fn bit(&self) -> u8 {
+        self.bit
+    }
+}
+
+impl OtpConfirmToken {
+    /// `magic` must be [`OTP_MAGIC`], so programming can not happen by
+    /// passing a default value
+    pub fn new(bit: OtpBit, magic: u8) -> Result<OtpConfirmToken, OtpError> {
+        if magic != OTP_MAGIC {
+            return Err(OtpError::InvalidMagic);
+        }
+        Ok(OtpConfirmToken { bit })
+    }
+
+    pub fn bit(&self) -> OtpBit {
+        self.bit
+    }
+}
+
+impl TMC2209_Otp {
+    pub fn from_otp_read(otp_read: u32) -> TMC2209_Otp {
+        TMC2209_Otp {
+            bytes: [
+                otp_read as u8,
+                (otp_read >> 8) as u8,
+                (otp_read >> 16) as u8,
+            ],
+        }
+    }
+
+    pub fn is_set(&self, bit: OtpBit) -> bool {
+        self.bytes[bit.byte as usize] & (1 << bit.bit) != 0
+    }
+
+    /// Contents after programming `bit`
+    pub fn with_bit(&self, bit: OtpBit) -> TMC2209_Otp {
+        let mut otp = *self;
+        otp.bytes[bit.byte as usize] |= 1 << bit.bit;
+        otp
+    }
+
+    /// Register defaults the chip loads at power-up from these contents
+    pub fn power_up_config(&self) -> TMC2209_OtpPowerUpConfig {
+        let [otp0, otp1, otp2] = self.bytes;
+        TMC2209_OtpPowerUpConfig {
+            fclktrim: otp0 & 0x1F,
+            ottrim: (otp0 >> 5) & 0x01,
+            internal_rsense: otp0 & 0x40 != 0,
+            blank_time: if otp0 & 0x80 != 0 { 1 } else { 2 },
+            pwm_grad: OTP_PWM_GRAD[(otp1 & 0x0F) as usize],
+            pwm_autograd: otp1 & 0x10 == 0,
+            tpwmthrs: OTP_TPWMTHRS[(otp1 >> 5) as usize],
+            pwm_ofs: if otp2 & 0x01 != 0 { 0 } else { 36 },
+            pwm_reg: if otp2 & 0x02 != 0 { 2 } else { 1 },
+            pwm_freq: if otp2 & 0x04 != 0 { 2 } else { 1 },
+            ihold_delay: OTP_IHOLDDELAY[((otp2 >> 3) & 0x03) as usize],
+            ihold: OTP_IHOLD[((otp2 >> 5) & 0x03) as usize],
+            en_spreadcycle: otp2 & 0x80 != 0,
+        }
+    }
+}
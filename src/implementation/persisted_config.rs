@@ -0,0 +1,496 @@
+use crate::structures::{
+    base_config::TMC2209_BaseConfig,
+    chip_variant::ChipVariant,
+    persisted_config::{PersistedConfigError, TMC2209_PersistedConfig},
+    registers_collection::TMC2209_ConfigRegistersChangesDetected,
+    saved_config::TMC2209_SavedConfig,
+};
+use crate::utils::checksum::crc32;
+use embedded_storage::nor_flash::{NorFlash, NorFlashError, ReadNorFlash};
+
+const MAGIC: [u8; 2] = *b"T9";
+const HEADER_SIZE: usize = 4;
+const CRC_SIZE: usize = 4;
+const MAX_PAYLOAD_SIZE: usize =
+    TMC2209_PersistedConfig::ENCODED_SIZE - HEADER_SIZE - CRC_SIZE;
+
+// Order of boolean fields in the flags word. Append only!
+const FLAG_INTERPOLATION: u32 = 1 << 0;
+const FLAG_EN_SPREADCYCLE: u32 = 1 << 1;
+const FLAG_PDN_DISABLE: u32 = 1 << 2;
+const FLAG_PWM_AUTOSCALE: u32 = 1 << 3;
+const FLAG_PWM_AUTOGRAD: u32 = 1 << 4;
+const FLAG_INTERNAL_RSENSE: u32 = 1 << 5;
+const FLAG_I_SCALE_ANALOG: u32 = 1 << 6;
+const FLAG_MSTEP_REG_SELECT: u32 = 1 << 7;
+const FLAG_MULTISTEP_FILT: u32 = 1 << 8;
+const FLAG_INDEX_OTPW: u32 = 1 << 9;
+const FLAG_INDEX_STEP: u32 = 1 << 10;
+const FLAG_SEIMIN: u32 = 1 << 11;
+const FLAG_VSENSE: u32 = 1 << 12;
+const FLAG_DEDGE: u32 = 1 << 13;
+const FLAG_DISS2G: u32 = 1 << 14;
+const FLAG_DISS2VS: u32 = 1 << 15;
+const FLAG_SHAFT: u32 = 1 << 16;
+
+// Order of registers in the known registers word. Append only!
+const KNOWN_GCONF: u16 = 1 << 0;
+const KNOWN_CHOPCONF: u16 = 1 << 1;
+const KNOWN_SLAVECONF: u16 = 1 << 2;
+const KNOWN_FACTORY_CONF: u16 = 1 << 3;
+const KNOWN_IHOLD_IRUN: u16 = 1 << 4;
+const KNOWN_COOLCONF: u16 = 1 << 5;
+const KNOWN_PWMCONF: u16 = 1 << 6;
+const KNOWN_TPOWERDOWN: u16 = 1 << 7;
+const KNOWN_TPWMTHRS: u16 = 1 << 8;
+const KNOWN_SGTHRS: u16 = 1 << 9;
+const KNOWN_TCOOLTHRS: u16 = 1 << 10;
+
+impl TMC2209_PersistedConfig {
+    /// Size of encoded block in bytes. It never changes between versions,
+    /// so the same flash region can be reused after firmware update
+    pub const ENCODED_SIZE: usize = 128;
+
+    /// Current encoding version.
+    ///
+    /// Fields are only ever appended to the payload. Blocks written by an
+    /// older version are migrated on load: fields missing from the stored
+    /// payload keep their default values.
+    pub const VERSION: u8 = 7;
+
+    pub fn new(
+        base_config: &TMC2209_BaseConfig,
+        saved_config: &TMC2209_SavedConfig,
+    ) -> TMC2209_PersistedConfig {
+        TMC2209_PersistedConfig {
+            base_config: base_config.clone(),
+            saved_config: saved_config.clone(),
+        }
+    }
+
+    /// Encode config into fixed-size block
+    pub fn encode(&self, buf: &mut [u8; Self::ENCODED_SIZE]) {
+        buf.fill(0xFF);
+
+        let mut writer = ByteWriter {
+            buf: &mut buf[HEADER_SIZE..HEADER_SIZE + MAX_PAYLOAD_SIZE],
+            pos: 0,
+        };
+        encode_payload(self, &mut writer);
+        let payload_len = writer.pos;
+
+        buf[0..2].copy_from_slice(&MAGIC);
+        buf[2] = Self::VERSION;
+        buf[3] = payload_len as u8;
+
+        let crc_pos = Self::ENCODED_SIZE - CRC_SIZE;
+        let crc = crc32(&buf[..crc_pos]);
+        buf[crc_pos..].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    /// Decode config from block created by [`Self::encode`] (this or any
+    /// older version)
+    // Violations of a refused restore are stored inline, there is no
+    // allocator in no_std
+    #[allow(clippy::result_large_err)]
+    pub fn decode(
+        buf: &[u8; Self::ENCODED_SIZE],
+    ) -> Result<TMC2209_PersistedConfig, PersistedConfigError> {
+        if buf[0..2] != MAGIC {
+            return Err(PersistedConfigError::BadMagic);
+        }
+
+        let crc_pos = Self::ENCODED_SIZE - CRC_SIZE;
+        let mut stored_crc = [0u8; CRC_SIZE];
+        stored_crc.copy_from_slice(&buf[crc_pos..]);
+        if crc32(&buf[..crc_pos]) != u32::from_le_bytes(stored_crc) {
+            return Err(PersistedConfigError::CrcMismatch);
+        }
+
+        let version = buf[2];
+        if version == 0 || version > Self::VERSION {
+            return Err(PersistedConfigError::UnsupportedVersion(version));
+        }
+
+        let payload_len = buf[3] as usize;
+        if payload_len > MAX_PAYLOAD_SIZE {
+            return Err(PersistedConfigError::BadLength);
+        }
+
+        let mut reader = ByteReader {
+            buf: &buf[HEADER_SIZE..HEADER_SIZE + payload_len],
+            pos: 0,
+        };
+        Ok(decode_payload(&mut reader))
+    }
+
+    /// Erase flash region starting at `offset` and write encoded config.
+    /// `offset` must be aligned to the flash erase size
+    // Violations of a refused restore are stored inline, there is no
+    // allocator in no_std
+    #[allow(clippy::result_large_err)]
+    pub fn write_to_flash<Flash: NorFlash>(
+        &self,
+        flash: &mut Flash,
+        offset: u32,
+    ) -> Result<(), PersistedConfigError> {
+        let erase_size = Flash::ERASE_SIZE as u32;
+        if !offset.is_multiple_of(erase_size)
+            || !Self::ENCODED_SIZE.is_multiple_of(Flash::WRITE_SIZE)
+        {
+            return Err(PersistedConfigError::NotAligned);
+        }
+
+        let mut buf = [0u8; Self::ENCODED_SIZE];
+        self.encode(&mut buf);
+
+        let erase_len =
+            (Self::ENCODED_SIZE as u32).div_ceil(erase_size) * erase_size;
+        flash
+            .erase(offset, offset + erase_len)
+            .map_err(|e| PersistedConfigError::Flash(e.kind()))?;
+        flash
+            .write(offset, &buf)
+            .map_err(|e| PersistedConfigError::Flash(e.kind()))
+    }
+
+    /// Read and decode config stored by [`Self::write_to_flash`]
+    // Violations of a refused restore are stored inline, there is no
+    // allocator in no_std
+    #[allow(clippy::result_large_err)]
+    pub fn read_from_flash<Flash: ReadNorFlash>(
+        flash: &mut Flash,
+        offset: u32,
+    ) -> Result<TMC2209_PersistedConfig, PersistedConfigError> {
+        let mut buf = [0u8; Self::ENCODED_SIZE];
+        flash
+            .read(offset, &mut buf)
+            .map_err(|e| PersistedConfigError::Flash(e.kind()))?;
+        Self::decode(&buf)
+    }
+}
+
+// NorFlashErrorKind has no defmt support, so the flash error is printed by
+// its Debug name
+#[cfg(feature = "defmt")]
+impl defmt::Format for PersistedConfigError {
+    fn format(&self, f: defmt::Formatter) {
+        match *self {
+            PersistedConfigError::BadMagic => defmt::write!(f, "BadMagic"),
+            PersistedConfigError::UnsupportedVersion(version) => {
+                defmt::write!(f, "UnsupportedVersion({=u8})", version)
+            }
+            PersistedConfigError::BadLength => defmt::write!(f, "BadLength"),
+            PersistedConfigError::CrcMismatch => {
+                defmt::write!(f, "CrcMismatch")
+            }
+            PersistedConfigError::NotAligned => defmt::write!(f, "NotAligned"),
+            PersistedConfigError::Flash(kind) => {
+                defmt::write!(f, "Flash({})", defmt::Debug2Format(&kind))
+            }
+            PersistedConfigError::Apply(error) => {
+                defmt::write!(f, "Apply({})", error)
+            }
+        }
+    }
+}
+
+fn encode_payload(config: &TMC2209_PersistedConfig, writer: &mut ByteWriter) {
+    let base = &config.base_config;
+    let saved = &config.saved_config;
+
+    // Version 1
+    writer.u8(base.uart_address);
+    writer.f32(base.r_sense);
+    writer.f32(base.ihold_multiplier);
+
+    writer.u16(saved.rms_current);
+    writer.u8(saved.ihold_delay);
+    writer.u16(saved.microsteps as u16);
+    writer.u8(saved.blank_time as u8);
+    writer.u8(saved.hysteresis_end as u8);
+    writer.u8(saved.hysteresis_start as u8);
+    writer.u8(saved.tpowerdown as u8);
+    writer.u32(saved.tpwmthrs);
+    writer.u8(saved.sgthrs as u8);
+    writer.u32(saved.tcoolthrs);
+    writer.u8(saved.pwm_ofs);
+    writer.u8(saved.pwm_grad);
+    writer.u8(saved.pwm_freq);
+    writer.u8(saved.pwm_reg);
+    writer.u8(saved.pwm_lim);
+    writer.u8(saved.freewheel);
+    writer.u8(saved.senddelay);
+    writer.u8(saved.semin as u8);
+    writer.u8(saved.seup as u8);
+    writer.u8(saved.semax as u8);
+    writer.u8(saved.sedn as u8);
+    writer.u8(saved.toff as u8);
+    writer.u8(saved.fclktrim);
+    writer.u8(saved.ottrim);
+    writer.u32(encode_flags(saved));
+
+    // Version 2
+    writer.f32(base.fclk);
+    writer.u16(base.full_steps_per_rotation as u16);
+
+    // Version 3
+    writer.u8(base.chip_variant.to_u8());
+
+    // Version 4
+    writer.u16(saved.hold_current);
+
+    // Version 5
+    writer.f32(base.vref);
+    writer.f32(base.rref);
+
+    // Version 6
+    writer.u32(base.baud_rate);
+
+    // Version 7
+    writer.u16(encode_known_registers(&saved.known_registers));
+}
+
+fn decode_payload(reader: &mut ByteReader) -> TMC2209_PersistedConfig {
+    let default_base = TMC2209_BaseConfig::default();
+    let mut base = TMC2209_BaseConfig::default();
+    let mut saved = TMC2209_SavedConfig::new();
+
+    // Version 1
+    base.uart_address = reader.u8().unwrap_or(default_base.uart_address);
+    base.r_sense = reader.f32().unwrap_or(default_base.r_sense);
+    base.ihold_multiplier =
+        reader.f32().unwrap_or(default_base.ihold_multiplier);
+
+    saved.rms_current = reader.u16().unwrap_or(0);
+    saved.ihold_delay = reader.u8().unwrap_or(0);
+    saved.microsteps = reader.u16().unwrap_or(0) as u32;
+    saved.blank_time = reader.u8().unwrap_or(0) as u32;
+    saved.hysteresis_end = reader.u8().unwrap_or(0) as u32;
+    saved.hysteresis_start = reader.u8().unwrap_or(0) as u32;
+    saved.tpowerdown = reader.u8().unwrap_or(0) as u32;
+    saved.tpwmthrs = reader.u32().unwrap_or(0);
+    saved.sgthrs = reader.u8().unwrap_or(0) as u32;
+    saved.tcoolthrs = reader.u32().unwrap_or(0);
+    saved.pwm_ofs = reader.u8().unwrap_or(0);
+    saved.pwm_grad = reader.u8().unwrap_or(0);
+    saved.pwm_freq = reader.u8().unwrap_or(0);
+    saved.pwm_reg = reader.u8().unwrap_or(0);
+    saved.pwm_lim = reader.u8().unwrap_or(0);
+    saved.freewheel = reader.u8().unwrap_or(0);
+    saved.senddelay = reader.u8().unwrap_or(0);
+    saved.semin = reader.u8().unwrap_or(0) as u16;
+    saved.seup = reader.u8().unwrap_or(0) as u16;
+    saved.semax = reader.u8().unwrap_or(0) as u16;
+    saved.sedn = reader.u8().unwrap_or(0) as u16;
+    saved.toff = reader.u8().unwrap_or(0) as u32;
+    saved.fclktrim = reader.u8().unwrap_or(0);
+    saved.ottrim = reader.u8().unwrap_or(0);
+    decode_flags(reader.u32().unwrap_or(0), &mut saved);
+
+    // Version 2
+    base.fclk = reader.f32().unwrap_or(default_base.fclk);
+    base.full_steps_per_rotation = reader
+        .u16()
+        .map(|steps| steps as u32)
+        .unwrap_or(default_base.full_steps_per_rotation);
+
+    // Version 3
+    base.chip_variant = reader
+        .u8()
+        .and_then(ChipVariant::from_u8)
+        .unwrap_or(default_base.chip_variant);
+
+    // Version 4
+    saved.hold_current = reader.u16().unwrap_or(0);
+
+    // Version 5
+    base.vref = reader.f32().unwrap_or(default_base.vref);
+    base.rref = reader.f32().unwrap_or(default_base.rref);
+
+    // Version 6
+    base.baud_rate = reader.u32().unwrap_or(default_base.baud_rate);
+
+    // Version 7
+    saved.known_registers = match reader.u16() {
+        Some(known) => decode_known_registers(known),
+        None => guess_known_registers(&saved),
+    };
+
+    TMC2209_PersistedConfig {
+        base_config: base,
+        saved_config: saved,
+    }
+}
+
+fn encode_flags(saved: &TMC2209_SavedConfig) -> u32 {
+    let mut flags = 0;
+    let mut set = |flag: u32, value: bool| {
+        if value {
+            flags |= flag;
+        }
+    };
+    set(FLAG_INTERPOLATION, saved.interpolation);
+    set(FLAG_EN_SPREADCYCLE, saved.en_spreadcycle);
+    set(FLAG_PDN_DISABLE, saved.pdn_disable);
+    set(FLAG_PWM_AUTOSCALE, saved.pwm_autoscale);
+    set(FLAG_PWM_AUTOGRAD, saved.pwm_autograd);
+    set(FLAG_INTERNAL_RSENSE, saved.internal_rsense);
+    set(FLAG_I_SCALE_ANALOG, saved.i_scale_analog);
+    set(FLAG_MSTEP_REG_SELECT, saved.mstep_reg_select);
+    set(FLAG_MULTISTEP_FILT, saved.multistep_filt);
+    set(FLAG_INDEX_OTPW, saved.index_otpw);
+    set(FLAG_INDEX_STEP, saved.index_step);
+    set(FLAG_SEIMIN, saved.seimin);
+    set(FLAG_VSENSE, saved.vsense);
+    set(FLAG_DEDGE, saved.dedge);
+    set(FLAG_DISS2G, saved.diss2g);
+    set(FLAG_DISS2VS, saved.diss2vs);
+    set(FLAG_SHAFT, saved.shaft);
+    flags
+}
+
+fn decode_flags(flags: u32, saved: &mut TMC2209_SavedConfig) {
+    saved.interpolation = flags & FLAG_INTERPOLATION != 0;
+    saved.en_spreadcycle = flags & FLAG_EN_SPREADCYCLE != 0;
+    saved.pdn_disable = flags & FLAG_PDN_DISABLE != 0;
+    saved.pwm_autoscale = flags & FLAG_PWM_AUTOSCALE != 0;
+    saved.pwm_autograd = flags & FLAG_PWM_AUTOGRAD != 0;
+    saved.internal_rsense = flags & FLAG_INTERNAL_RSENSE != 0;
+    saved.i_scale_analog = flags & FLAG_I_SCALE_ANALOG != 0;
+    saved.mstep_reg_select = flags & FLAG_MSTEP_REG_SELECT != 0;
+    saved.multistep_filt = flags & FLAG_MULTISTEP_FILT != 0;
+    saved.index_otpw = flags & FLAG_INDEX_OTPW != 0;
+    saved.index_step = flags & FLAG_INDEX_STEP != 0;
+    saved.seimin = flags & FLAG_SEIMIN != 0;
+    saved.vsense = flags & FLAG_VSENSE != 0;
+    saved.dedge = flags & FLAG_DEDGE != 0;
+    saved.diss2g = flags & FLAG_DISS2G != 0;
+    saved.diss2vs = flags & FLAG_DISS2VS != 0;
+    saved.shaft = flags & FLAG_SHAFT != 0;
+}
+
+fn encode_known_registers(
+    known: &TMC2209_ConfigRegistersChangesDetected,
+) -> u16 {
+    let mut flags = 0;
+    let mut set = |flag: u16, value: bool| {
+        if value {
+            flags |= flag;
+        }
+    };
+    set(KNOWN_GCONF, known.gconf);
+    set(KNOWN_CHOPCONF, known.chopconf);
+    set(KNOWN_SLAVECONF, known.slaveconf);
+    set(KNOWN_FACTORY_CONF, known.factory_conf);
+    set(KNOWN_IHOLD_IRUN, known.ihold_irun);
+    set(KNOWN_COOLCONF, known.coolconf);
+    set(KNOWN_PWMCONF, known.pwmconf);
+    set(KNOWN_TPOWERDOWN, known.tpowerdown);
+    set(KNOWN_TPWMTHRS, known.tpwmthrs);
+    set(KNOWN_SGTHRS, known.sgthrs);
+    set(KNOWN_TCOOLTHRS, known.tcoolthrs);
+    flags
+}
+
+fn decode_known_registers(
+    flags: u16,
+) -> TMC2209_ConfigRegistersChangesDetected {
+    TMC2209_ConfigRegistersChangesDetected {
+        gconf: flags & KNOWN_GCONF != 0,
+        chopconf: flags & KNOWN_CHOPCONF != 0,
+        slaveconf: flags & KNOWN_SLAVECONF != 0,
+        factory_conf: flags & KNOWN_FACTORY_CONF != 0,
+        ihold_irun: flags & KNOWN_IHOLD_IRUN != 0,
+        coolconf: flags & KNOWN_COOLCONF != 0,
+        pwmconf: flags & KNOWN_PWMCONF != 0,
+        tpowerdown: flags & KNOWN_TPOWERDOWN != 0,
+        tpwmthrs: flags & KNOWN_TPWMTHRS != 0,
+        sgthrs: flags & KNOWN_SGTHRS != 0,
+        tcoolthrs: flags & KNOWN_TCOOLTHRS != 0,
+    }
+}
+
+// Blocks before version 7 do not record known registers. Saved microsteps
+// are 0 only if CHOPCONF was never read or written, such a block holds
+// `TMC2209_SavedConfig::new()` values. FACTORY_CONF may not have been read
+// from the chip, so it is never restored from these blocks
+fn guess_known_registers(
+    saved: &TMC2209_SavedConfig,
+) -> TMC2209_ConfigRegistersChangesDetected {
+    let configured = saved.microsteps > 0;
+    TMC2209_ConfigRegistersChangesDetected {
+        gconf: configured,
+        chopconf: configured,
+        slaveconf: configured,
+        factory_conf: false,
+        ihold_irun: saved.rms_current > 0,
+        coolconf: configured,
+        pwmconf: configured,
+        tpowerdown: configured,
+        tpwmthrs: configured,
+        sgthrs: configured,
+        tcoolthrs: configured,
+    }
+}
+
+struct ByteWriter<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl ByteWriter<'_> {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.buf[self.pos..self.pos + bytes.len()].copy_from_slice(bytes);
+        self.pos += bytes.len();
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.bytes(&[value]);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.bytes(&value.to_le_bytes());
+    }
+}
+
+/// Returns None for fields that are not present in the stored payload
+/// (written by an older version)
+struct ByteReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl ByteReader<'_> {
+    fn bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let slice = self.buf.get(self.pos..self.pos + N)?;
+        self.pos += N;
+        let mut out = [0u8; N];
+        out.copy_from_slice(slice);
+        Some(out)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes::<1>().map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes().map(u32::from_le_bytes)
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        self.bytes().map(f32::from_le_bytes)
+    }
+}
@@ -0,0 +1,552 @@
+use crate::structures::register_view::{
+    FieldChange, FieldChanges, FieldValue, RegisterView, MAX_FIELD_CHANGES,
+};
+use core::fmt;
+
+/// How a field value is shown after the number
+#[derive(Clone, Copy)]
+enum Meaning {
+    None,
+    Flag,
+    /// Two's complement of `width` bits
+    Signed,
+    Mres,
+    Toff,
+    Tbl,
+    Hstrt,
+    Hend,
+    CurrentScale,
+    PwmFreq,
+    Freewheel,
+    Senddelay,
+    Seup,
+    Sedn,
+    Seimin,
+}
+
+struct BitField {
+    name: &'static str,
+    lsb: u8,
+    width: u8,
+    meaning: Meaning,
+}
+
+struct RegisterDesc {
+    address: u8,
+    name: &'static str,
+    fields: &'static [BitField],
+}
+
+const fn field(
+    name: &'static str,
+    lsb: u8,
+    width: u8,
+    meaning: Meaning,
+) -> BitField {
+    BitField {
+        name,
+        lsb,
+        width,
+        meaning,
+    }
+}
+
+const fn flag(name: &'static str, lsb: u8) -> BitField {
+    field(name, lsb, 1, Meaning::Flag)
+}
+
+const fn number(name: &'static str, lsb: u8, width: u8) -> BitField {
+    field(name, lsb, width, Meaning::None)
+}
+
+// Field layout from tmc2209 datasheet, chapter 5 (register map)
+const REGISTERS: [RegisterDesc; 23] = [
+    RegisterDesc {
+        address: 0x00,
+        name: "GCONF",
+        fields: &[
+            flag("I_SCALE_ANALOG", 0),
+            flag("INTERNAL_RSENSE", 1),
+            flag("EN_SPREADCYCLE", 2),
+            flag("SHAFT", 3),
+            flag("INDEX_OTPW", 4),
+            flag("INDEX_STEP", 5),
+            flag("PDN_DISABLE", 6),
+            flag("MSTEP_REG_SELECT", 7),
+            flag("MULTISTEP_FILT", 8),
+            flag("TEST_MODE", 9),
+        ],
+    },
+    RegisterDesc {
+        address: 0x01,
+        name: "GSTAT",
+        fields: &[flag("RESET", 0), flag("DRV_ERR", 1), flag("UV_CP", 2)],
+    },
+    RegisterDesc {
+        address: 0x02,
+        name: "IFCNT",
+        fields: &[number("IFCNT", 0, 8)],
+    },
+    RegisterDesc {
+        address: 0x03,
+        name: "SLAVECONF",
+        fields: &[field("SENDDELAY", 8, 4, Meaning::Senddelay)],
+    },
+    RegisterDesc {
+        address: 0x05,
+        name: "OTP_READ",
+        fields: &[
+            number("OTP0", 0, 8),
+            number("OTP1", 8, 8),
+            number("OTP2", 16, 8),
+        ],
+    },
+    RegisterDesc {
+        address: 0x06,
+        name: "IOIN",
+        fields: &[
+            flag("ENN", 0),
+            flag("MS1", 2),
+            flag("MS2", 3),
+            flag("DIAG", 4),
+            flag("PDN_UART", 6),
+            flag("STEP", 7),
+            flag("SPREAD_EN", 8),
+            flag("DIR", 9),
+            number("VERSION", 24, 8),
+        ],
+    },
+    RegisterDesc {
+        address: 0x07,
+        name: "FACTORY_CONF",
+        fields: &[number("FCLKTRIM", 0, 5), number("OTTRIM", 8, 2)],
+    },
+    RegisterDesc {
+        address: 0x10,
+        name: "IHOLD_IRUN",
+        fields: &[
+            field("IHOLD", 0, 5, Meaning::CurrentScale),
+            field("IRUN", 8, 5, Meaning::CurrentScale),
+            number("IHOLDDELAY", 16, 4),
+        ],
+    },
+    RegisterDesc {
+        address: 0x11,
+        name: "TPOWERDOWN",
+        fields: &[number("TPOWERDOWN", 0, 8)],
+    },
+    RegisterDesc {
+        address: 0x12,
+        name: "TSTEP",
+        fields: &[number("TSTEP", 0, 20)],
+    },
+    RegisterDesc {
+        address: 0x13,
+        name: "TPWMTHRS",
+        fields: &[number("TPWMTHRS", 0, 20)],
+    },
+    RegisterDesc {
+        address: 0x14,
+        name: "TCOOLTHRS",
+        fields: &[number("TCOOLTHRS", 0, 20)],
+    },
+    RegisterDesc {
+        address: 0x22,
+        name: "VACTUAL",
+        fields: &[field("VACTUAL", 0, 24, Meaning::Signed)],
+    },
+    RegisterDesc {
+        address: 0x40,
+        name: "SGTHRS",
+        fields: &[number("SGTHRS", 0, 8)],
+    },
+    RegisterDesc {
+        address: 0x41,
+        name: "SG_RESULT",
+        fields: &[number("SG_RESULT", 0, 10)],
+    },
+    RegisterDesc {
+        address: 0x42,
+        name: "COOLCONF",
+        fields: &[
+            number("SEMIN", 0, 4),
+            field("SEUP", 5, 2, Meaning::Seup),
+            number("SEMAX", 8, 4),
+            field("SEDN", 13, 2, Meaning::Sedn),
+            field("SEIMIN", 15, 1, Meaning::Seimin),
+        ],
+    },
+    RegisterDesc {
+        address: 0x6A,
+        name: "MSCNT",
+        fields: &[number("MSCNT", 0, 10)],
+    },
+    RegisterDesc {
+        address: 0x6B,
+        name: "MSCURACT",
+        fields: &[
+            field("CUR_A", 0, 9, Meaning::Signed),
+            field("CUR_B", 16, 9, Meaning::Signed),
+        ],
+    },
+    RegisterDesc {
+        address: 0x6C,
+        name: "CHOPCONF",
+        fields: &[
+            field("TOFF", 0, 4, Meaning::Toff),
+            field("HSTRT", 4, 3, Meaning::Hstrt),
+            field("HEND", 7, 4, Meaning::Hend),
+            field("TBL", 15, 2, Meaning::Tbl),
+            flag("VSENSE", 17),
+            field("MRES", 24, 4, Meaning::Mres),
+            flag("INTPOL", 28),
+            flag("DEDGE", 29),
+            flag("DISS2G", 30),
+            flag("DISS2VS", 31),
+        ],
+    },
+    RegisterDesc {
+        address: 0x6F,
+        name: "DRV_STATUS",
+        fields: &[
+            flag("OTPW", 0),
+            flag("OT", 1),
+            flag("S2GA", 2),
+            flag("S2GB", 3),
+            flag("S2VSA", 4),
+            flag("S2VSB", 5),
+            flag("OLA", 6),
+            flag("OLB", 7),
+            flag("T120", 8),
+            flag("T143", 9),
+            flag("T150", 10),
+            flag("T157", 11),
+            field("CS_ACTUAL", 16, 5, Meaning::CurrentScale),
+            flag("STEALTH", 30),
+            flag("STST", 31),
+        ],
+    },
+    RegisterDesc {
+        address: 0x70,
+        name: "PWMCONF",
+        fields: &[
+            number("PWM_OFS", 0, 8),
+            number("PWM_GRAD", 8, 8),
+            field("PWM_FREQ", 16, 2, Meaning::PwmFreq),
+            flag("PWM_AUTOSCALE", 18),
+            flag("PWM_AUTOGRAD", 19),
+            field("FREEWHEEL", 20, 2, Meaning::Freewheel),
+            number("PWM_REG", 24, 4),
+            number("PWM_LIM", 28, 4),
+        ],
+    },
+    RegisterDesc {
+        address: 0x71,
+        name: "PWM_SCALE",
+        fields: &[
+            number("PWM_SCALE_SUM", 0, 8),
+            field("PWM_SCALE_AUTO", 16, 9, Meaning::Signed),
+        ],
+    },
+    RegisterDesc {
+        address: 0x72,
+        name: "PWM_AUTO",
+        fields: &[number("PWM_OFS_AUTO", 0, 8), number("PWM_GRAD_AUTO", 16, 8)],
+    },
+];
+
+/// Decoded meaning of a field value, shown in parentheses
+#[derive(Clone, Copy)]
+enum Decoded {
+    None,
+    Text(&'static str),
+    Microsteps(u32),
+    Clocks(u32),
+    Offset(i32),
+    CurrentScale(u32),
+    BitTimes(u32),
+    Steps(u32),
+}
+
+impl fmt::Display for Decoded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Decoded::None => Ok(()),
+            Decoded::Text(text) => write!(f, " ({})", text),
+            Decoded::Microsteps(1) => write!(f, " (fullstep)"),
+            Decoded::Microsteps(microsteps) => {
+                write!(f, " ({} µsteps)", microsteps)
+            }
+            Decoded::Clocks(clocks) => write!(f, " ({} clocks)", clocks),
+            Decoded::Offset(offset) => write!(f, " ({:+})", offset),
+            Decoded::CurrentScale(cs) => write!(f, " ({}/32)", cs + 1),
+            Decoded::BitTimes(bit_times) => {
+                write!(f, " ({} bit times)", bit_times)
+            }
+            Decoded::Steps(steps) => write!(f, " ({})", steps),
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Decoded {
+    fn format(&self, f: defmt::Formatter) {
+        match *self {
+            Decoded::None => {}
+            Decoded::Text(text) => defmt::write!(f, " ({=str})", text),
+            Decoded::Microsteps(1) => defmt::write!(f, " (fullstep)"),
+            Decoded::Microsteps(microsteps) => {
+                defmt::write!(f, " ({=u32} µsteps)", microsteps)
+            }
+            Decoded::Clocks(clocks) => {
+                defmt::write!(f, " ({=u32} clocks)", clocks)
+            }
+            Decoded::Offset(offset) => defmt::write!(f, " ({=i32})", offset),
+            Decoded::CurrentScale(cs) => {
+                defmt::write!(f, " ({=u32}/32)", cs + 1)
+            }
+            Decoded::BitTimes(bit_times) => {
+                defmt::write!(f, " ({=u32} bit times)", bit_times)
+            }
+            Decoded::Steps(steps) => defmt::write!(f, " ({=u32})", steps),
+        }
+    }
+}
+
+impl BitField {
+    fn raw(&self, value: u32) -> u32 {
+        let mask = if self.width >= 32 {
+            u32::MAX
+        } else {
+            (1u32 << self.width) - 1
+        };
+        (value >> self.lsb) & mask
+    }
+
+    fn value(&self, value: u32) -> FieldValue {
+        let raw = self.raw(value);
+        match self.meaning {
+            Meaning::Flag => FieldValue::Bool(raw != 0),
+            Meaning::Signed => {
+                let shift = 32 - self.width as u32;
+                FieldValue::Int(((raw << shift) as i32) >> shift)
+            }
+            _ => FieldValue::Uint(raw),
+        }
+    }
+
+    fn decode(&self, value: u32) -> Decoded {
+        let raw = self.raw(value);
+        match self.meaning {
+            Meaning::None | Meaning::Flag | Meaning::Signed => Decoded::None,
+            Meaning::Mres => Decoded::Microsteps(256 >> raw.min(8)),
+            Meaning::Toff if raw == 0 => Decoded::Text("driver disabled"),
+            Meaning::Toff => Decoded::Text("chopper enabled"),
+            Meaning::Tbl => Decoded::Clocks([16, 24, 32, 40][raw as usize]),
+            Meaning::Hstrt => Decoded::Offset(raw as i32 + 1),
+            Meaning::Hend => Decoded::Offset(raw as i32 - 3),
+            Meaning::CurrentScale => Decoded::CurrentScale(raw),
+            Meaning::PwmFreq => Decoded::Text(
+                ["2/1024 fclk", "2/683 fclk", "2/512 fclk", "2/410 fclk"]
+                    [raw as usize],
+            ),
+            Meaning::Freewheel => Decoded::Text(
+                ["normal", "freewheeling", "LS short", "HS short"]
+                    [raw as usize],
+            ),
+            Meaning::Senddelay => Decoded::BitTimes((raw | 1) * 8),
+            Meaning::Seup => Decoded::Steps(1 << raw),
+            Meaning::Sedn => Decoded::Steps([32, 8, 2, 1][raw as usize]),
+            Meaning::Seimin if raw == 0 => Decoded::Text("1/2 of IRUN"),
+            Meaning::Seimin => Decoded::Text("1/4 of IRUN"),
+        }
+    }
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FieldValue::Bool(value) => write!(f, "{}", value as u8),
+            FieldValue::Uint(value) => write!(f, "{}", value),
+            FieldValue::Int(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for FieldValue {
+    fn format(&self, f: defmt::Formatter) {
+        match *self {
+            FieldValue::Bool(value) => defmt::write!(f, "{=u8}", value as u8),
+            FieldValue::Uint(value) => defmt::write!(f, "{=u32}", value),
+            FieldValue::Int(value) => defmt::write!(f, "{=i32}", value),
+        }
+    }
+}
+
+fn describe(address: u8) -> Option<&'static RegisterDesc> {
+    REGISTERS.iter().find(|desc| desc.address == address & 0x7F)
+}
+
+impl RegisterView {
+    pub fn new(address: u8, value: u32) -> RegisterView {
+        RegisterView { address, value }
+    }
+
+    /// Register name, `None` for unknown address
+    pub fn name(&self) -> Option<&'static str> {
+        describe(self.address).map(|desc| desc.name)
+    }
+
+    /// Register address by name (for example "CHOPCONF"), case insensitive
+    pub fn address_by_name(name: &str) -> Option<u8> {
+        REGISTERS
+            .iter()
+            .find(|desc| desc.name.eq_ignore_ascii_case(name))
+            .map(|desc| desc.address)
+    }
+
+    /// Value of field `name` (for example "MRES")
+    pub fn field(&self, name: &str) -> Option<FieldValue> {
+        describe(self.address)?
+            .fields
+            .iter()
+            .find(|field| field.name.eq_ignore_ascii_case(name))
+            .map(|field| field.value(self.value))
+    }
+
+    /// Fields that differ in `other` (a later value of the same register)
+    pub fn diff(&self, other: &RegisterView) -> FieldChanges {
+        let mut changes = FieldChanges::new();
+        if let Some(desc) = describe(self.address) {
+            for field in desc.fields {
+                let old = field.value(self.value);
+                let new = field.value(other.value);
+                if old != new {
+                    changes.push(FieldChange {
+                        field: field.name,
+                        old,
+                        new,
+                    });
+                }
+            }
+        }
+        changes
+    }
+}
+
+impl fmt::Display for RegisterView {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let desc = match describe(self.address) {
+            Some(desc) => desc,
+            None => {
+                return write!(
+                    f,
+                    "0x{:02X} = 0x{:08X}",
+                    self.address, self.value
+                )
+            }
+        };
+        write!(f, "{} = 0x{:08X}", desc.name, self.value)?;
+        for field in desc.fields {
+            write!(
+                f,
+                "\n  {}={}{}",
+                field.name,
+                field.value(self.value),
+                field.decode(self.value)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for RegisterView {
+    fn format(&self, f: defmt::Formatter) {
+        let desc = match describe(self.address) {
+            Some(desc) => desc,
+            None => {
+                defmt::write!(
+                    f,
+                    "{=u8:#04x} = {=u32:#010x}",
+                    self.address,
+                    self.value
+                );
+                return;
+            }
+        };
+        defmt::write!(f, "{=str} = {=u32:#010x}", desc.name, self.value);
+        for field in desc.fields {
+            defmt::write!(
+                f,
+                " {=str}={}{}",
+                field.name,
+                field.value(self.value),
+                field.decode(self.value)
+            );
+        }
+    }
+}
+
+impl FieldChanges {
+    pub fn new() -> FieldChanges {
+        FieldChanges {
+            items: [None; MAX_FIELD_CHANGES],
+            len: 0,
+        }
+    }
+
+    /// Add change (ignored if list is full)
+    pub fn push(&mut self, change: FieldChange) {
+        if self.len < MAX_FIELD_CHANGES {
+            self.items[self.len] = Some(change);
+            self.len += 1;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &FieldChange> {
+        self.items[..self.len].iter().flatten()
+    }
+}
+
+impl Default for FieldChanges {
+    fn default() -> Self {
+        FieldChanges::new()
+    }
+}
+
+impl fmt::Display for FieldChanges {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, change) in self.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}: {} -> {}", change.field, change.old, change.new)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for FieldChanges {
+    fn format(&self, f: defmt::Formatter) {
+        for change in self.iter() {
+            defmt::write!(
+                f,
+                "{=str}: {} -> {}; ",
+                change.field,
+                change.old,
+                change.new
+            );
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use crate::structures::{
-    debug_readed_config::TMC2209_DebugConfig, saved_config::TMC2209_SavedConfig,
+    debug_readed_config::TMC2209_DebugConfig,
+    registers_collection::TMC2209_ConfigRegistersChangesDetected,
+    saved_config::TMC2209_SavedConfig,
 };
 
 impl TMC2209_SavedConfig {
@@ -7,6 +9,7 @@ impl TMC2209_SavedConfig {
     pub fn new() -> TMC2209_SavedConfig {
         TMC2209_SavedConfig {
             rms_current: 0,
+            hold_current: 0,
             ihold_delay: 0,
             microsteps: 0,
             interpolation: false,
@@ -47,6 +50,7 @@ impl TMC2209_SavedConfig {
             semax: 0,
             semin: 0,
             senddelay: 0,
+            known_registers: TMC2209_ConfigRegistersChangesDetected::new(),
         }
     }
 
@@ -56,6 +60,7 @@ impl TMC2209_SavedConfig {
     ) -> TMC2209_SavedConfig {
         TMC2209_SavedConfig {
             rms_current: 0,
+            hold_current: 0,
             ihold_delay: 0,
             microsteps: debug_config.microsteps,
             interpolation: debug_config.interpolation,
@@ -96,6 +101,14 @@ impl TMC2209_SavedConfig {
             semax: 0,
             semin: 0,
             senddelay: 0,
+            // Registers read by debug_read_config_from_driver()
+            known_registers: TMC2209_ConfigRegistersChangesDetected {
+                gconf: true,
+                chopconf: true,
+                factory_conf: true,
+                pwmconf: true,
+                ..TMC2209_ConfigRegistersChangesDetected::new()
+            },
         }
     }
 }
@@ -0,0 +1,28 @@
+use crate::structures::{
+    config::TMC2209_Config,
+    stealthchop_autotune::{
+        StealthChopAutotuneOptions, StealthChopAutotuneResult,
+    },
+};
+
+impl Default for StealthChopAutotuneOptions {
+    fn default() -> Self {
+        StealthChopAutotuneOptions {
+            standstill_time_ms: 150,
+            // ~60 rpm of 200 steps motor with internal clock
+            velocity: 1000,
+            poll_interval_ms: 20,
+            stable_reads: 5,
+            scale_auto_tolerance: 1,
+            timeout_ms: 5000,
+        }
+    }
+}
+
+impl StealthChopAutotuneResult {
+    /// Store learned values as PWM_OFS/PWM_GRAD start values
+    pub fn apply_to(&self, config: &mut TMC2209_Config) {
+        config.pwm_ofs = Some(self.pwm_ofs);
+        config.pwm_grad = Some(self.pwm_grad);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::structures::{
+    config::TMC2209_Config,
+    drv_status::TemperatureLevel,
+    thermal_supervisor::{
+        ThermalEvent, ThermalSupervisor, ThermalSupervisorOptions,
+    },
+};
+use crate::utils::timing::UartTransport;
+use crate::TMC2209UART;
+
+impl Default for ThermalSupervisorOptions {
+    fn default() -> Self {
+        ThermalSupervisorOptions {
+            derate_level: TemperatureLevel::Above120,
+            restore_level: TemperatureLevel::Normal,
+            step: 100,
+            min_current: 300,
+        }
+    }
+}
+
+impl ThermalSupervisor {
+    /// Supervisor for the driver, currents set now are nominal
+    pub fn new<Uart: UartTransport>(
+        driver: &TMC2209UART<Uart>,
+        options: ThermalSupervisorOptions,
+    ) -> ThermalSupervisor {
+        let currents = driver.get_currents();
+        ThermalSupervisor {
+            options,
+            nominal_run_current: currents.run_current,
+            nominal_hold_current: currents.hold_current,
+            run_current: currents.run_current,
+            level: TemperatureLevel::Normal,
+            pending: false,
+        }
+    }
+
+    /// Change nominal currents (mA RMS), for example after the user set a
+    /// new run current. Sent to the driver by the next poll()
+    pub fn set_nominal_current(&mut self, run_current: u16, hold_current: u16) {
+        self.nominal_run_current = run_current;
+        self.nominal_hold_current = hold_current;
+        self.run_current = self.run_current.min(run_current);
+        self.pending = true;
+    }
+
+    /// Read DRV_STATUS temperature flags and step run current down or up
+    pub fn poll<Uart: UartTransport>(
+        &mut self,
+        driver: &mut TMC2209UART<Uart>,
+    ) -> Result<Option<ThermalEvent>, ()> {
+        let level = driver.read_drv_status()?.temperature_level();
+        self.level = level;
+
+        let target = if level >= self.options.derate_level {
+            let floor = self.options.min_current.min(self.nominal_run_current);
+            self.run_current
+                .saturating_sub(self.options.step)
+                .max(floor)
+        } else if level <= self.options.restore_level {
+            self.run_current
+                .saturating_add(self.options.step)
+                .min(self.nominal_run_current)
+        } else {
+            // Hysteresis, keep current
+            self.run_current
+        };
+
+        if target == self.run_current && !self.pending {
+            return Ok(None);
+        }
+
+        // Hold current must not get above reduced run current
+        let config = TMC2209_Config {
+            rms_current: Some(target),
+            hold_current: Some(self.nominal_hold_current.min(target)),
+            ..Default::default()
+        };
+        driver.apply_config(&config).map_err(|_| ())?;
+        self.pending = false;
+
+        let derated = target < self.run_current;
+        self.run_current = target;
+        let run_current = driver.get_saved_config().rms_current;
+
+        let event = if derated {
+            ThermalEvent::Derated { level, run_current }
+        } else if target < self.nominal_run_current {
+            ThermalEvent::Restoring { level, run_current }
+        } else {
+            ThermalEvent::Restored { run_current }
+        };
+        tmc_warn!("thermal supervisor: {:?}", event);
+        Ok(Some(event))
+    }
+
+    pub fn level(&self) -> TemperatureLevel {
+        self.level
+    }
+
+    /// Run current requested by supervisor, mA RMS
+    pub fn run_current(&self) -> u16 {
+        self.run_current
+    }
+
+    pub fn is_derated(&self) -> bool {
+        self.run_current < self.nominal_run_current
+    }
+}
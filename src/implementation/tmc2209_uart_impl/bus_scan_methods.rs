@@ -0,0 +1,40 @@
+use crate::structures::bus_scan::{
+    BusScan, NodeProbe, TMC2209_NodeInfo, NODE_ADDRESS_COUNT,
+};
+use crate::utils::timing::{UartTiming, UartTransport};
+use crate::utils::tmc_read_write::{read_reg_checked, ReadFailure};
+
+/// Probe one node address by reading IFCNT and IOIN
+pub fn probe_node<Uart: UartTransport>(
+    uart: &mut Uart,
+    uart_address: u8,
+    timing: &UartTiming,
+) -> NodeProbe {
+    let ifcnt =
+        read_reg_checked::<tmc2209::reg::IFCNT, _>(uart, uart_address, timing);
+    let ioin =
+        read_reg_checked::<tmc2209::reg::IOIN, _>(uart, uart_address, timing);
+    match (ifcnt, ioin) {
+        (Ok(ifcnt), Ok(ioin)) => NodeProbe::Responding(TMC2209_NodeInfo {
+            address: uart_address,
+            ifcnt: u32::from(ifcnt) as u8,
+            version: ioin.version() as u8,
+        }),
+        (Err(ReadFailure::InvalidReply), _)
+        | (_, Err(ReadFailure::InvalidReply)) => NodeProbe::Collision,
+        _ => NodeProbe::NoReply,
+    }
+}
+
+pub fn scan_bus<Uart: UartTransport>(
+    uart: &mut Uart,
+    timing: &UartTiming,
+) -> BusScan {
+    let mut scan = BusScan {
+        nodes: [NodeProbe::NoReply; NODE_ADDRESS_COUNT],
+    };
+    for (address, node) in scan.nodes.iter_mut().enumerate() {
+        *node = probe_node(uart, address as u8, timing);
+    }
+    scan
+}
@@ -4,28 +4,35 @@ use crate::structures::{
 };
 use crate::utils::{
     calc::mres_to_microsteps,
+    timing::{UartTiming, UartTransport},
     tmc_read_write::test_uart_connection,
     tmc_read_write::{read_reg_blocking, write_reg},
 };
-use embedded_io::{Read, Write};
 
-pub fn get_registers_changed_in_config<'a, Uart: Read + Write>(
+pub fn get_registers_changed_in_config<'a, Uart: UartTransport>(
     uart: &'a mut Uart,
     uart_address: u8,
     config: &TMC2209_Config,
+    timing: &UartTiming,
 ) -> Result<TMC2209_ConfigRegisters, ()> {
     let changes = config.which_registers_changed();
     let mut output = TMC2209_ConfigRegisters::new();
 
     if changes.gconf {
-        let gconf =
-            read_reg_blocking::<tmc2209::reg::GCONF, _>(uart, uart_address)?;
+        let gconf = read_reg_blocking::<tmc2209::reg::GCONF, _>(
+            uart,
+            uart_address,
+            timing,
+        )?;
         output.gconf = Some(gconf);
     }
 
     if changes.chopconf {
-        let chopconf =
-            read_reg_blocking::<tmc2209::reg::CHOPCONF, _>(uart, uart_address)?;
+        let chopconf = read_reg_blocking::<tmc2209::reg::CHOPCONF, _>(
+            uart,
+            uart_address,
+            timing,
+        )?;
         output.chopconf = Some(chopconf);
     }
 
@@ -35,8 +42,11 @@ pub fn get_registers_changed_in_config<'a, Uart: Read + Write>(
     }
 
     if changes.factory_conf {
-        let factory_conf =
-            read_reg_blocking::<tmc2209::reg::FACTORY_CONF, _>(uart, 0)?;
+        let factory_conf = read_reg_blocking::<tmc2209::reg::FACTORY_CONF, _>(
+            uart,
+            uart_address,
+            timing,
+        )?;
         output.factory_conf = Some(factory_conf);
     }
 
@@ -51,8 +61,11 @@ pub fn get_registers_changed_in_config<'a, Uart: Read + Write>(
     }
 
     if changes.pwmconf {
-        let pwmconf =
-            read_reg_blocking::<tmc2209::reg::PWMCONF, _>(uart, uart_address)?;
+        let pwmconf = read_reg_blocking::<tmc2209::reg::PWMCONF, _>(
+            uart,
+            uart_address,
+            timing,
+        )?;
         output.pwmconf = Some(pwmconf);
     }
 
@@ -78,18 +91,31 @@ pub fn get_registers_changed_in_config<'a, Uart: Read + Write>(
     Ok(output)
 }
 
-pub fn debug_read_config_from_driver<'a, Uart: Read + Write>(
+pub fn debug_read_config_from_driver<'a, Uart: UartTransport>(
     uart: &'a mut Uart,
     uart_address: u8,
+    timing: &UartTiming,
 ) -> Result<TMC2209_DebugConfig, ()> {
-    let gconf =
-        read_reg_blocking::<tmc2209::reg::GCONF, _>(uart, uart_address)?;
-    let chopconf =
-        read_reg_blocking::<tmc2209::reg::CHOPCONF, _>(uart, uart_address)?;
-    let factory_conf =
-        read_reg_blocking::<tmc2209::reg::FACTORY_CONF, _>(uart, 0)?;
-    let pwmconf =
-        read_reg_blocking::<tmc2209::reg::PWMCONF, _>(uart, uart_address)?;
+    let gconf = read_reg_blocking::<tmc2209::reg::GCONF, _>(
+        uart,
+        uart_address,
+        timing,
+    )?;
+    let chopconf = read_reg_blocking::<tmc2209::reg::CHOPCONF, _>(
+        uart,
+        uart_address,
+        timing,
+    )?;
+    let factory_conf = read_reg_blocking::<tmc2209::reg::FACTORY_CONF, _>(
+        uart,
+        uart_address,
+        timing,
+    )?;
+    let pwmconf = read_reg_blocking::<tmc2209::reg::PWMCONF, _>(
+        uart,
+        uart_address,
+        timing,
+    )?;
 
     Ok(TMC2209_DebugConfig {
         microsteps: mres_to_microsteps(chopconf.mres()),
@@ -124,83 +150,113 @@ pub fn debug_read_config_from_driver<'a, Uart: Read + Write>(
     })
 }
 
-pub fn write_registers_changed_in_config<'a, Uart: Read + Write>(
+pub fn write_registers_changed_in_config<'a, Uart: UartTransport>(
     uart: &'a mut Uart,
     uart_address: u8,
     registers: &TMC2209_ConfigRegisters,
+    timing: &UartTiming,
 ) -> Result<(), ()> {
     if let Some(gconf) = registers.gconf {
-        write_reg(uart, uart_address, gconf)?;
+        write_reg(uart, uart_address, gconf, timing)?;
     }
 
     if let Some(chopconf) = registers.chopconf {
-        write_reg(uart, uart_address, chopconf)?;
+        write_reg(uart, uart_address, chopconf, timing)?;
     }
 
     if let Some(slaveconf) = registers.slaveconf {
-        write_reg(uart, uart_address, slaveconf)?;
+        write_reg(uart, uart_address, slaveconf, timing)?;
     }
 
     if let Some(factoryconf) = registers.factory_conf {
-        write_reg(uart, 0, factoryconf)?;
+        write_reg(uart, uart_address, factoryconf, timing)?;
     }
 
     if let Some(ihold_irun) = registers.ihold_irun {
-        write_reg(uart, uart_address, ihold_irun)?;
+        write_reg(uart, uart_address, ihold_irun, timing)?;
     }
 
     if let Some(coolconf) = registers.coolconf {
-        write_reg(uart, uart_address, coolconf)?;
+        write_reg(uart, uart_address, coolconf, timing)?;
     }
 
     if let Some(pwmconf) = registers.pwmconf {
-        write_reg(uart, uart_address, pwmconf)?;
+        write_reg(uart, uart_address, pwmconf, timing)?;
     }
 
     if let Some(tpowerdown) = registers.tpowerdown {
-        write_reg(uart, uart_address, tpowerdown)?;
+        write_reg(uart, uart_address, tpowerdown, timing)?;
     }
 
     if let Some(tpwmthrs) = registers.tpwmthrs {
-        write_reg(uart, uart_address, tpwmthrs)?;
+        write_reg(uart, uart_address, tpwmthrs, timing)?;
     }
 
     if let Some(sgthrs) = registers.sgthrs {
-        write_reg(uart, uart_address, sgthrs)?;
+        write_reg(uart, uart_address, sgthrs, timing)?;
     }
 
     if let Some(tcoolthrs) = registers.tcoolthrs {
-        write_reg(uart, uart_address, tcoolthrs)?;
+        write_reg(uart, uart_address, tcoolthrs, timing)?;
     }
 
     Ok(())
 }
 
-pub fn read_sg_result<'a, Uart: Read + Write>(
+pub fn read_sg_result<'a, Uart: UartTransport>(
     uart: &'a mut Uart,
     uart_address: u8,
+    timing: &UartTiming,
 ) -> Result<u16, ()> {
     return Ok(read_reg_blocking::<tmc2209::reg::SG_RESULT, _>(
         uart,
         uart_address,
+        timing,
     )?
     .get());
 }
 
-pub fn test_connection<'a, Uart: Read + Write>(
+pub fn test_connection<'a, Uart: UartTransport>(
     uart: &'a mut Uart,
     uart_address: u8,
+    timing: &UartTiming,
 ) -> bool {
-    return test_uart_connection(uart, uart_address);
+    return test_uart_connection(uart, uart_address, timing);
 }
 
-pub fn set_vactual<Uart: Read + Write>(
+pub fn set_vactual<Uart: UartTransport>(
     uart: &mut Uart,
     uart_address: u8,
     v_actual: i32,
+    timing: &UartTiming,
 ) -> Result<(), ()> {
     let mut v_actual_reg = tmc2209::reg::VACTUAL::default();
     v_actual_reg.set(v_actual);
-    write_reg(uart, uart_address, v_actual_reg)?;
+    write_reg(uart, uart_address, v_actual_reg, timing)?;
     Ok(())
 }
+
+pub fn read_pwm_scale<Uart: UartTransport>(
+    uart: &mut Uart,
+    uart_address: u8,
+    timing: &UartTiming,
+) -> Result<tmc2209::reg::PWM_SCALE, ()> {
+    read_reg_blocking::<tmc2209::reg::PWM_SCALE, _>(uart, uart_address, timing)
+}
+
+pub fn read_pwm_auto<Uart: UartTransport>(
+    uart: &mut Uart,
+    uart_address: u8,
+    timing: &UartTiming,
+) -> Result<tmc2209::reg::PWM_AUTO, ()> {
+    read_reg_blocking::<tmc2209::reg::PWM_AUTO, _>(uart, uart_address, timing)
+}
+
+pub fn write_ihold_irun<Uart: UartTransport>(
+    uart: &mut Uart,
+    uart_address: u8,
+    ihold_irun: tmc2209::reg::IHOLD_IRUN,
+    timing: &UartTiming,
+) -> Result<(), ()> {
+    write_reg(uart, uart_address, ihold_irun, timing)
+}
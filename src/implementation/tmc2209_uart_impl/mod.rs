@@ -1,3 +1,6 @@
+pub mod bus_scan_methods;
 pub mod config_read_write_methods;
+pub mod otp_methods;
 pub mod reg_processor;
+pub mod telemetry_read_methods;
 pub mod tmc2209_uart_controll;
@@ -0,0 +1,33 @@
+use crate::structures::otp::{OtpBit, TMC2209_Otp, OTP_MAGIC};
+use crate::utils::timing::{UartTiming, UartTransport};
+use crate::utils::tmc_read_write::{read_reg_blocking, write_reg};
+
+pub fn read_otp<Uart: UartTransport>(
+    uart: &mut Uart,
+    uart_address: u8,
+    timing: &UartTiming,
+) -> Result<TMC2209_Otp, ()> {
+    let otp_read = read_reg_blocking::<tmc2209::reg::OTP_READ, _>(
+        uart,
+        uart_address,
+        timing,
+    )?;
+    Ok(TMC2209_Otp::from_otp_read(u32::from(otp_read)))
+}
+
+/// Start programming of one OTP bit. Can not be undone
+pub fn write_otp_prog<Uart: UartTransport>(
+    uart: &mut Uart,
+    uart_address: u8,
+    bit: OtpBit,
+    timing: &UartTiming,
+) -> Result<(), ()> {
+    let value =
+        (bit.bit as u32) | (bit.byte as u32) << 4 | (OTP_MAGIC as u32) << 8;
+    write_reg(
+        uart,
+        uart_address,
+        tmc2209::reg::OTP_PROG::from(value),
+        timing,
+    )
+}
@@ -5,9 +5,8 @@ use crate::structures::{
     registers_collection::TMC2209_ConfigRegisters,
 };
 use crate::utils::calc::{
-    irun_to_rms_current, microsteps_to_mres, mres_to_microsteps,
-    rms_current_to_ihold_irun_vsense,
-    RmsCurrentToIholdIrunVsenseOutput as IholdIrunVsense,
+    cs_to_rms_current, microsteps_to_mres, mres_to_microsteps,
+    rms_current_to_cs, solve_rms_current, CurrentScaling,
 };
 
 /// Reg processor read user defined TMC2209_Config and make changes in loaded registers from driver.
@@ -19,55 +18,102 @@ pub fn process_reg_config(
     save_config_to: &mut TMC2209_SavedConfig,
 ) {
     process_driver_base_config(driver_base_config, config);
-
-    if let Some(mut gconf) = mutable_previous_regs.gconf {
-        process_gconf(&mut gconf, config, save_config_to);
-    }
-
-    if let Some(mut chopconf) = mutable_previous_regs.chopconf {
-        process_chopconf(&mut chopconf, config, save_config_to);
-
-        if let Some(mut ihold_irun) = mutable_previous_regs.ihold_irun {
+    tmc_debug!(
+        "config: node {}, r_sense {}, ihold_multiplier {}",
+        driver_base_config.uart_address,
+        driver_base_config.r_sense,
+        driver_base_config.ihold_multiplier
+    );
+
+    if let Some(gconf) = mutable_previous_regs.gconf.as_mut() {
+        let before = u32::from(*gconf);
+        process_gconf(gconf, config, save_config_to);
+        trace_step("GCONF", before, u32::from(*gconf));
+    }
+
+    // Current scaling or ihold_multiplier change alone keeps saved
+    // currents. Without known run current there is nothing to keep,
+    // IRUN = IHOLD = 0 would be written. ihold_multiplier is still stored
+    // in base config and used with the next run current
+    if !is_run_current_known(config, save_config_to)
+        && !is_hold_current_changed(config)
+    {
+        mutable_previous_regs.ihold_irun = None;
+    }
+
+    if let Some(chopconf) = mutable_previous_regs.chopconf.as_mut() {
+        let before = u32::from(*chopconf);
+        process_chopconf(chopconf, config, save_config_to);
+        trace_step("CHOPCONF", before, u32::from(*chopconf));
+
+        if let Some(ihold_irun) = mutable_previous_regs.ihold_irun.as_mut() {
+            let before = u32::from(*ihold_irun);
             process_ihold_irun(
-                &mut ihold_irun,
-                &mut chopconf,
+                ihold_irun,
+                chopconf,
                 driver_base_config,
                 config,
                 save_config_to,
             );
+            trace_step("IHOLD_IRUN", before, u32::from(*ihold_irun));
         }
     }
 
-    if let Some(mut slaveconf) = mutable_previous_regs.slaveconf {
-        process_slaveconf(&mut slaveconf, config, save_config_to);
+    if let Some(slaveconf) = mutable_previous_regs.slaveconf.as_mut() {
+        let before = u32::from(*slaveconf);
+        process_slaveconf(slaveconf, config, save_config_to);
+        trace_step("SLAVECONF", before, u32::from(*slaveconf));
     }
 
-    if let Some(mut factoryconf) = mutable_previous_regs.factory_conf {
-        process_factoryconf(&mut factoryconf, config, save_config_to);
+    if let Some(factoryconf) = mutable_previous_regs.factory_conf.as_mut() {
+        let before = u32::from(*factoryconf);
+        process_factoryconf(factoryconf, config, save_config_to);
+        trace_step("FACTORY_CONF", before, u32::from(*factoryconf));
     }
 
-    if let Some(mut coolconf) = mutable_previous_regs.coolconf {
-        process_coolconf(&mut coolconf, config, save_config_to);
+    if let Some(coolconf) = mutable_previous_regs.coolconf.as_mut() {
+        let before = u32::from(*coolconf);
+        process_coolconf(coolconf, config, save_config_to);
+        trace_step("COOLCONF", before, u32::from(*coolconf));
     }
 
-    if let Some(mut pwmconf) = mutable_previous_regs.pwmconf {
-        process_pwmconf(&mut pwmconf, config, save_config_to);
+    if let Some(pwmconf) = mutable_previous_regs.pwmconf.as_mut() {
+        let before = u32::from(*pwmconf);
+        process_pwmconf(pwmconf, config, save_config_to);
+        trace_step("PWMCONF", before, u32::from(*pwmconf));
     }
 
-    if let Some(mut tpowerdown) = mutable_previous_regs.tpowerdown {
-        process_tpowerdown(&mut tpowerdown, config, save_config_to);
+    if let Some(tpowerdown) = mutable_previous_regs.tpowerdown.as_mut() {
+        let before = u32::from(*tpowerdown);
+        process_tpowerdown(tpowerdown, config, save_config_to);
+        trace_step("TPOWERDOWN", before, u32::from(*tpowerdown));
     }
 
-    if let Some(mut tpwmthrs) = mutable_previous_regs.tpwmthrs {
-        process_tpwmthrs(&mut tpwmthrs, config, save_config_to);
+    if let Some(tpwmthrs) = mutable_previous_regs.tpwmthrs.as_mut() {
+        let before = u32::from(*tpwmthrs);
+        process_tpwmthrs(tpwmthrs, config, save_config_to);
+        trace_step("TPWMTHRS", before, u32::from(*tpwmthrs));
     }
 
-    if let Some(mut sgthrs) = mutable_previous_regs.sgthrs {
-        process_sgthrs(&mut sgthrs, config, save_config_to);
+    if let Some(sgthrs) = mutable_previous_regs.sgthrs.as_mut() {
+        let before = u32::from(*sgthrs);
+        process_sgthrs(sgthrs, config, save_config_to);
+        trace_step("SGTHRS", before, u32::from(*sgthrs));
     }
 
-    if let Some(mut tcoolthrs) = mutable_previous_regs.tcoolthrs {
-        process_tcoolthrs(&mut tcoolthrs, config, save_config_to);
+    if let Some(tcoolthrs) = mutable_previous_regs.tcoolthrs.as_mut() {
+        let before = u32::from(*tcoolthrs);
+        process_tcoolthrs(tcoolthrs, config, save_config_to);
+        trace_step("TCOOLTHRS", before, u32::from(*tcoolthrs));
+    }
+}
+
+// Trace one config application step (register value before and after)
+fn trace_step(register: &str, before: u32, after: u32) {
+    if before == after {
+        tmc_debug!("config: {} = {:#x} (unchanged)", register, after);
+    } else {
+        tmc_debug!("config: {} {:#x} -> {:#x}", register, before, after);
     }
 }
 
@@ -198,6 +244,21 @@ pub fn process_chopconf(
     }
 }
 
+/// Run current is set in config or known from saved config, so IHOLD_IRUN
+/// (write only) can be rebuilt
+pub fn is_run_current_known(
+    config: &TMC2209_Config,
+    saved_config: &TMC2209_SavedConfig,
+) -> bool {
+    config.rms_current.is_some() || saved_config.rms_current > 0
+}
+
+/// Config sets hold current or IHOLDDELAY, which are only kept in
+/// IHOLD_IRUN (ihold_multiplier is kept in base config too)
+pub fn is_hold_current_changed(config: &TMC2209_Config) -> bool {
+    config.hold_current.is_some() || config.ihold_delay.is_some()
+}
+
 pub fn process_ihold_irun(
     ihold_irun: &mut tmc2209::reg::IHOLD_IRUN,
     chopconf: &mut tmc2209::reg::CHOPCONF,
@@ -205,30 +266,84 @@ pub fn process_ihold_irun(
     config: &TMC2209_Config,
     save_config_to: &mut TMC2209_SavedConfig,
 ) {
-    // Assuming that process_driver_values() runned before process_ihold_irun()
-
-    if let Some(rms_current) = config.rms_current {
-        let IholdIrunVsense {
-            ihold,
-            irun,
+    // Assuming that process_driver_values() and process_gconf() runned
+    // before process_ihold_irun()
+    // IHOLD_IRUN is write only, so every field is rebuilt, fields missing in
+    // config are taken from saved config
+    let scaling = driver_base_config.current_scaling(
+        save_config_to.i_scale_analog,
+        save_config_to.internal_rsense,
+    );
+    let CurrentScaling {
+        r_sense,
+        analog_scale,
+    } = scaling;
+    let hold_multiplier = driver_base_config.ihold_multiplier;
+
+    // New current scaling keeps saved currents, but may need other CS/vsense
+    let scaling_changed =
+        config.i_scale_analog.is_some() || config.internal_rsense.is_some();
+    let saved_run_current = Some(save_config_to.rms_current)
+        .filter(|current| scaling_changed && *current > 0);
+
+    let (irun, vsense) = if let Some(rms_current) =
+        config.rms_current.or(saved_run_current)
+    {
+        let solution = solve_rms_current(
+            rms_current.min(driver_base_config.chip_variant.max_rms_current()),
+            r_sense,
+            analog_scale,
+        );
+        (solution.cs, solution.vsense)
+    } else {
+        let vsense = chopconf.vsense();
+        let irun = rms_current_to_cs(
+            save_config_to.rms_current,
             vsense,
-        } = rms_current_to_ihold_irun_vsense(
-            rms_current,
-            driver_base_config.r_sense,
-            driver_base_config.ihold_multiplier,
+            r_sense,
+            analog_scale,
         );
-        ihold_irun.set_ihold(ihold);
-        ihold_irun.set_irun(irun);
-        chopconf.set_vsense(vsense);
-
-        save_config_to.rms_current =
-            irun_to_rms_current(irun, vsense, driver_base_config.r_sense);
-    }
-
-    if let Some(ihold_delay) = config.ihold_delay {
-        ihold_irun.set_ihold_delay(ihold_delay);
-        save_config_to.ihold_delay = ihold_delay;
-    }
+        (irun, vsense)
+    };
+
+    // Saved hold current is kept when only run current changes (for example
+    // after set_hold_current()), but never above the new run current.
+    // Zero saved hold current means it is unknown (config stored before hold
+    // current was tracked), ihold_multiplier is used then
+    let mut ihold = if config.ihold_multiplier.is_some()
+        || save_config_to.hold_current == 0
+    {
+        (irun as f32 * hold_multiplier) as u8
+    } else {
+        rms_current_to_cs(
+            save_config_to.hold_current,
+            vsense,
+            r_sense,
+            analog_scale,
+        )
+        .min(irun)
+    };
+
+    // Absolute hold current wins over ihold_multiplier
+    if let Some(hold_current) = config.hold_current {
+        ihold = rms_current_to_cs(hold_current, vsense, r_sense, analog_scale);
+    }
+
+    ihold_irun.set_ihold(ihold);
+    ihold_irun.set_irun(irun);
+    chopconf.set_vsense(vsense);
+    save_config_to.vsense = vsense;
+    if save_config_to.rms_current > 0 || config.rms_current.is_some() {
+        let current = |cs| {
+            (cs_to_rms_current(cs, vsense, r_sense, analog_scale) + 0.5) as u16
+        };
+        save_config_to.rms_current = current(irun);
+        save_config_to.hold_current = current(ihold);
+    }
+
+    let ihold_delay = config.ihold_delay.unwrap_or(save_config_to.ihold_delay);
+    ihold_irun.set_ihold_delay(ihold_delay);
+    save_config_to.ihold_delay = ihold_delay;
 }
 
 pub fn process_slaveconf(
@@ -378,3 +493,199 @@ pub fn process_tcoolthrs(
         save_config_to.tcoolthrs = toolthrs_val;
     }
 }
+
+/// Update saved config from registers written by apply_config(). Fields
+/// not set in config are saved too (kept from the driver or reset to zero
+/// in write only registers), so saved config matches the driver
+pub fn process_written_registers(
+    registers: &TMC2209_ConfigRegisters,
+    driver_base_config: &TMC2209_BaseConfig,
+    save_config_to: &mut TMC2209_SavedConfig,
+) {
+    use tmc2209::reg::Address;
+
+    let mut save = |address: Address, value: u32| {
+        process_raw_register_write(
+            address as u8,
+            value,
+            driver_base_config,
+            save_config_to,
+        )
+    };
+    if let Some(gconf) = registers.gconf {
+        save(Address::GCONF, gconf.into());
+    }
+    if let Some(chopconf) = registers.chopconf {
+        save(Address::CHOPCONF, chopconf.into());
+    }
+    if let Some(slaveconf) = registers.slaveconf {
+        save(Address::SLAVECONF, slaveconf.into());
+    }
+    if let Some(factory_conf) = registers.factory_conf {
+        save(Address::FACTORY_CONF, factory_conf.into());
+    }
+    if let Some(ihold_irun) = registers.ihold_irun {
+        save(Address::IHOLD_IRUN, ihold_irun.into());
+    }
+    if let Some(coolconf) = registers.coolconf {
+        save(Address::COOLCONF, coolconf.into());
+    }
+    if let Some(pwmconf) = registers.pwmconf {
+        save(Address::PWMCONF, pwmconf.into());
+    }
+    if let Some(tpowerdown) = registers.tpowerdown {
+        save(Address::TPOWERDOWN, tpowerdown.into());
+    }
+    if let Some(tpwmthrs) = registers.tpwmthrs {
+        save(Address::TPWMTHRS, tpwmthrs.into());
+    }
+    if let Some(sgthrs) = registers.sgthrs {
+        save(Address::SGTHRS, sgthrs.into());
+    }
+    if let Some(tcoolthrs) = registers.tcoolthrs {
+        save(Address::TCOOLTHRS, tcoolthrs.into());
+    }
+}
+
+/// Update saved config after a register was written directly (raw
+/// register API), so later apply_config() calls start from real values.
+/// The register is marked known
+pub fn process_raw_register_write(
+    reg_address: u8,
+    value: u32,
+    driver_base_config: &TMC2209_BaseConfig,
+    save_config_to: &mut TMC2209_SavedConfig,
+) {
+    match reg_address {
+        0x00 => {
+            let gconf = tmc2209::reg::GCONF::from(value);
+            save_config_to.i_scale_analog = gconf.i_scale_analog();
+            save_config_to.internal_rsense = gconf.internal_rsense();
+            save_config_to.en_spreadcycle = gconf.en_spread_cycle();
+            save_config_to.shaft = gconf.shaft();
+            save_config_to.index_otpw = gconf.index_otpw();
+            save_config_to.index_step = gconf.index_step();
+            save_config_to.pdn_disable = gconf.pdn_disable();
+            save_config_to.mstep_reg_select = gconf.mstep_reg_select();
+            save_config_to.multistep_filt = gconf.multistep_filt();
+            save_config_to.known_registers.gconf = true;
+        }
+        0x03 => {
+            let slaveconf = tmc2209::reg::SLAVECONF::from(value);
+            save_config_to.senddelay = slaveconf.get();
+            save_config_to.known_registers.slaveconf = true;
+        }
+        0x07 => {
+            let factoryconf = tmc2209::reg::FACTORY_CONF::from(value);
+            save_config_to.fclktrim = factoryconf.fclktrim();
+            save_config_to.ottrim = factoryconf.ottrim();
+            save_config_to.known_registers.factory_conf = true;
+        }
+        0x10 => {
+            let ihold_irun = tmc2209::reg::IHOLD_IRUN::from(value);
+            let CurrentScaling {
+                r_sense,
+                analog_scale,
+            } = driver_base_config.current_scaling(
+                save_config_to.i_scale_analog,
+                save_config_to.internal_rsense,
+            );
+            let vsense = save_config_to.vsense;
+            let current = |cs| {
+                (cs_to_rms_current(cs, vsense, r_sense, analog_scale) + 0.5)
+                    as u16
+            };
+            save_config_to.rms_current = current(ihold_irun.irun());
+            save_config_to.hold_current = current(ihold_irun.ihold());
+            save_config_to.ihold_delay = ihold_irun.ihold_delay();
+            save_config_to.known_registers.ihold_irun = true;
+        }
+        0x11 => {
+            save_config_to.tpowerdown = value & 0xFF;
+            save_config_to.known_registers.tpowerdown = true;
+        }
+        0x13 => {
+            save_config_to.tpwmthrs = value & 0xF_FFFF;
+            save_config_to.known_registers.tpwmthrs = true;
+        }
+        0x14 => {
+            save_config_to.tcoolthrs = value & 0xF_FFFF;
+            save_config_to.known_registers.tcoolthrs = true;
+        }
+        0x40 => {
+            save_config_to.sgthrs = value & 0xFF;
+            save_config_to.known_registers.sgthrs = true;
+        }
+        0x42 => {
+            let coolconf = tmc2209::reg::COOLCONF::from(value);
+            save_config_to.semin = coolconf.semin();
+            save_config_to.seup = coolconf.seup();
+            save_config_to.semax = coolconf.semax();
+            save_config_to.sedn = coolconf.sedn();
+            save_config_to.seimin = coolconf.seimin();
+            save_config_to.known_registers.coolconf = true;
+        }
+        0x6C => {
+            let chopconf = tmc2209::reg::CHOPCONF::from(value);
+            if chopconf.vsense() != save_config_to.vsense {
+                rescale_currents(
+                    chopconf.vsense(),
+                    driver_base_config,
+                    save_config_to,
+                );
+            }
+            save_config_to.toff = chopconf.toff();
+            save_config_to.hysteresis_start = chopconf.hstrt();
+            save_config_to.hysteresis_end = chopconf.hend();
+            save_config_to.blank_time = chopconf.tbl();
+            save_config_to.vsense = chopconf.vsense();
+            save_config_to.microsteps = mres_to_microsteps(chopconf.mres());
+            save_config_to.interpolation = chopconf.ntpol();
+            save_config_to.dedge = chopconf.dedge();
+            save_config_to.diss2g = chopconf.diss2g();
+            save_config_to.diss2vs = chopconf.diss2vs();
+            save_config_to.known_registers.chopconf = true;
+        }
+        0x70 => {
+            let pwmconf = tmc2209::reg::PWMCONF::from(value);
+            save_config_to.pwm_ofs = pwmconf.pwm_ofs();
+            save_config_to.pwm_grad = pwmconf.pwm_grad();
+            save_config_to.pwm_freq = pwmconf.pwm_freq();
+            save_config_to.pwm_autoscale = pwmconf.pwm_autoscale();
+            save_config_to.pwm_autograd = pwmconf.pwm_autograd();
+            save_config_to.freewheel = pwmconf.freewheel();
+            save_config_to.pwm_reg = pwmconf.pwm_reg();
+            save_config_to.pwm_lim = pwmconf.pwm_lim();
+            save_config_to.known_registers.pwmconf = true;
+        }
+        // Not part of saved config (VACTUAL, GSTAT, ...)
+        _ => {}
+    }
+}
+
+// IRUN and IHOLD stay when VSENSE changes, the currents they give do not.
+// Recompute saved run and hold current for `vsense`
+fn rescale_currents(
+    vsense: bool,
+    driver_base_config: &TMC2209_BaseConfig,
+    save_config_to: &mut TMC2209_SavedConfig,
+) {
+    if !save_config_to.known_registers.ihold_irun {
+        return;
+    }
+    let CurrentScaling {
+        r_sense,
+        analog_scale,
+    } = driver_base_config.current_scaling(
+        save_config_to.i_scale_analog,
+        save_config_to.internal_rsense,
+    );
+    let previous_vsense = save_config_to.vsense;
+    let rescale = |current| {
+        let cs =
+            rms_current_to_cs(current, previous_vsense, r_sense, analog_scale);
+        (cs_to_rms_current(cs, vsense, r_sense, analog_scale) + 0.5) as u16
+    };
+    save_config_to.rms_current = rescale(save_config_to.rms_current);
+    save_config_to.hold_current = rescale(save_config_to.hold_current);
+}
@@ -0,0 +1,197 @@
+use crate::structures::{
+    base_config::TMC2209_BaseConfig,
+    drv_status::TMC2209_DrvStatus,
+    gstat::TMC2209_Gstat,
+    ioin::TMC2209_IoinState,
+    telemetry::{
+        TMC2209_PhaseCurrents, TMC2209_PwmAuto, TMC2209_PwmScale,
+        TMC2209_Telemetry,
+    },
+};
+use crate::utils::{
+    calc::tstep_to_rpm,
+    timing::{UartTiming, UartTransport},
+    tmc_read_write::{read_reg_blocking, write_reg},
+};
+
+use super::config_read_write_methods::{
+    read_pwm_auto, read_pwm_scale, read_sg_result,
+};
+
+pub fn read_tstep<Uart: UartTransport>(
+    uart: &mut Uart,
+    uart_address: u8,
+    timing: &UartTiming,
+) -> Result<u32, ()> {
+    let tstep = read_reg_blocking::<tmc2209::reg::TSTEP, _>(
+        uart,
+        uart_address,
+        timing,
+    )?;
+    Ok(tstep.get() as u32)
+}
+
+pub fn read_mscnt<Uart: UartTransport>(
+    uart: &mut Uart,
+    uart_address: u8,
+    timing: &UartTiming,
+) -> Result<u16, ()> {
+    let mscnt = read_reg_blocking::<tmc2209::reg::MSCNT, _>(
+        uart,
+        uart_address,
+        timing,
+    )?;
+    Ok(mscnt.get() as u16)
+}
+
+pub fn read_phase_currents<Uart: UartTransport>(
+    uart: &mut Uart,
+    uart_address: u8,
+    timing: &UartTiming,
+) -> Result<TMC2209_PhaseCurrents, ()> {
+    let mscuract = read_reg_blocking::<tmc2209::reg::MSCURACT, _>(
+        uart,
+        uart_address,
+        timing,
+    )?;
+    // 9 bit two's complement, sign extended
+    Ok(TMC2209_PhaseCurrents {
+        cur_a: ((mscuract.cur_a() as i16) << 7) >> 7,
+        cur_b: ((mscuract.cur_b() as i16) << 7) >> 7,
+    })
+}
+
+pub fn read_pwm_scale_values<Uart: UartTransport>(
+    uart: &mut Uart,
+    uart_address: u8,
+    timing: &UartTiming,
+) -> Result<TMC2209_PwmScale, ()> {
+    let pwm_scale = read_pwm_scale(uart, uart_address, timing)?;
+    Ok(TMC2209_PwmScale {
+        sum: pwm_scale.pwm_scale_sum() as u8,
+        // 9 bit two's complement, sign extended
+        auto: ((pwm_scale.pwm_scale_auto() as i16) << 7) >> 7,
+    })
+}
+
+pub fn read_pwm_auto_values<Uart: UartTransport>(
+    uart: &mut Uart,
+    uart_address: u8,
+    timing: &UartTiming,
+) -> Result<TMC2209_PwmAuto, ()> {
+    let pwm_auto = read_pwm_auto(uart, uart_address, timing)?;
+    Ok(TMC2209_PwmAuto {
+        ofs: pwm_auto.pwm_ofs_auto() as u8,
+        grad: pwm_auto.pwm_grad_auto() as u8,
+    })
+}
+
+pub fn read_ioin<Uart: UartTransport>(
+    uart: &mut Uart,
+    uart_address: u8,
+    timing: &UartTiming,
+) -> Result<TMC2209_IoinState, ()> {
+    let ioin =
+        read_reg_blocking::<tmc2209::reg::IOIN, _>(uart, uart_address, timing)?;
+    Ok(TMC2209_IoinState {
+        enn: ioin.enn(),
+        ms1: ioin.ms1(),
+        ms2: ioin.ms2(),
+        diag: ioin.diag(),
+        pdn_uart: ioin.pdn_uart(),
+        step: ioin.step(),
+        spread_en: ioin.spread_en(),
+        dir: ioin.dir(),
+        version: ioin.version() as u8,
+    })
+}
+
+pub fn read_drv_status<Uart: UartTransport>(
+    uart: &mut Uart,
+    uart_address: u8,
+    timing: &UartTiming,
+) -> Result<TMC2209_DrvStatus, ()> {
+    let drv_status = read_reg_blocking::<tmc2209::reg::DRV_STATUS, _>(
+        uart,
+        uart_address,
+        timing,
+    )?;
+    Ok(TMC2209_DrvStatus {
+        otpw: drv_status.otpw(),
+        ot: drv_status.ot(),
+        s2ga: drv_status.s2ga(),
+        s2gb: drv_status.s2gb(),
+        s2vsa: drv_status.s2vsa(),
+        s2vsb: drv_status.s2vsb(),
+        ola: drv_status.ola(),
+        olb: drv_status.olb(),
+        t120: drv_status.t120(),
+        t143: drv_status.t143(),
+        t150: drv_status.t150(),
+        t157: drv_status.t157(),
+        cs_actual: drv_status.cs_actual() as u8,
+        stealth: drv_status.stealth(),
+        stst: drv_status.stst(),
+    })
+}
+
+pub fn read_gstat<Uart: UartTransport>(
+    uart: &mut Uart,
+    uart_address: u8,
+    timing: &UartTiming,
+) -> Result<TMC2209_Gstat, ()> {
+    let gstat = read_reg_blocking::<tmc2209::reg::GSTAT, _>(
+        uart,
+        uart_address,
+        timing,
+    )?;
+    Ok(TMC2209_Gstat {
+        reset: gstat.reset(),
+        drv_err: gstat.drv_err(),
+        uv_cp: gstat.uv_cp(),
+    })
+}
+
+/// Clear GSTAT flags set in `gstat` (write 1 to clear)
+pub fn clear_gstat<Uart: UartTransport>(
+    uart: &mut Uart,
+    uart_address: u8,
+    gstat: &TMC2209_Gstat,
+    timing: &UartTiming,
+) -> Result<(), ()> {
+    let mut reg = tmc2209::reg::GSTAT::default();
+    reg.set_reset(gstat.reset);
+    reg.set_drv_err(gstat.drv_err);
+    reg.set_uv_cp(gstat.uv_cp);
+    write_reg(uart, uart_address, reg, timing)
+}
+
+pub fn read_telemetry<Uart: UartTransport>(
+    uart: &mut Uart,
+    base_config: &TMC2209_BaseConfig,
+    timestamp: u64,
+    timing: &UartTiming,
+) -> Result<TMC2209_Telemetry, ()> {
+    let uart_address = base_config.uart_address;
+    let tstep = read_tstep(uart, uart_address, timing)?;
+
+    Ok(TMC2209_Telemetry {
+        timestamp,
+        tstep,
+        velocity_rpm: tstep_to_rpm(
+            tstep,
+            base_config.full_steps_per_rotation,
+            base_config.fclk,
+        ),
+        mscnt: read_mscnt(uart, uart_address, timing)?,
+        phase_currents: read_phase_currents(uart, uart_address, timing)?,
+        pwm_scale: read_pwm_scale_values(uart, uart_address, timing)?,
+        pwm_auto: read_pwm_auto_values(uart, uart_address, timing)?,
+        sg_result: if base_config.chip_variant.has_stallguard() {
+            Some(read_sg_result(uart, uart_address, timing)?)
+        } else {
+            None
+        },
+        ioin: read_ioin(uart, uart_address, timing)?,
+    })
+}
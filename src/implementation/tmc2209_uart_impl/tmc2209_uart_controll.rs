@@ -1,24 +1,74 @@
 use core::cell::RefCell;
 
+use super::bus_scan_methods::{probe_node, scan_bus};
 use super::config_read_write_methods::{
     debug_read_config_from_driver, get_registers_changed_in_config,
-    read_sg_result, set_vactual, test_connection,
-    write_registers_changed_in_config,
+    read_pwm_auto, read_pwm_scale, read_sg_result, set_vactual,
+    test_connection, write_ihold_irun, write_registers_changed_in_config,
+};
+use super::otp_methods::{read_otp, write_otp_prog};
+use super::reg_processor::{
+    is_hold_current_changed, is_run_current_known, process_raw_register_write,
+    process_reg_config, process_written_registers,
+};
+use super::telemetry_read_methods::{
+    clear_gstat, read_drv_status, read_gstat, read_ioin, read_mscnt,
+    read_phase_currents, read_pwm_auto_values, read_pwm_scale_values,
+    read_telemetry, read_tstep,
 };
-use super::reg_processor::process_reg_config;
 
 use crate::structures::base_config::TMC2209_BaseConfig;
 use crate::{
     structures::{
-        config::TMC2209_Config, debug_readed_config::TMC2209_DebugConfig,
+        apply_config_error::ApplyConfigError,
+        bus_scan::{BusScan, NodeProbe, TMC2209_NodeInfo},
+        chip_identity::ChipIdentity,
+        chopper_mode::ChopperMode,
+        config::TMC2209_Config,
+        config_violations::ConfigViolations,
+        debug_readed_config::TMC2209_DebugConfig,
+        drv_status::TMC2209_DrvStatus,
+        gstat::TMC2209_Gstat,
+        ioin::TMC2209_IoinState,
+        motor_current::{MotorCurrents, StandstillReduction},
+        otp::{
+            OtpBit, OtpConfirmToken, OtpError, OtpMode, OtpProgramReport,
+            TMC2209_Otp, TMC2209_OtpPowerUpConfig,
+        },
+        persisted_config::{PersistedConfigError, TMC2209_PersistedConfig},
         saved_config::TMC2209_SavedConfig,
+        stealthchop_autotune::{
+            StealthChopAutotuneError, StealthChopAutotuneOptions,
+            StealthChopAutotuneResult,
+        },
+        telemetry::{
+            TMC2209_PhaseCurrents, TMC2209_PwmAuto, TMC2209_PwmScale,
+            TMC2209_Telemetry,
+        },
+    },
+    utils::calc::{
+        ihold_delay_from_ms, ihold_delay_to_ms, rms_current_to_cs,
+        solve_rms_current, spreadcycle_chopper_settings,
+        tpowerdown_from_seconds, tpowerdown_to_seconds, tstep_to_rpm,
+        CurrentRange, CurrentScaling, CurrentSolution, MotorParameters,
+        SpreadCycleChopperSettings,
+    },
+    utils::timing::{UartTiming, UartTransport},
+    utils::tmc_read_write::{
+        read_raw_blocking, read_reg_blocking, read_reg_checked, write_raw,
     },
     TMC2209UART,
 };
+use core::f32::consts::SQRT_2;
 use critical_section::Mutex;
-use embedded_io::{Read, Write};
+use embedded_hal::delay::DelayNs;
+
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+
+// Time to burn one OTP bit
+const OTP_PROGRAM_TIME_MS: u32 = 10;
 
-impl<'a, Uart: Read + Write> TMC2209UART<'a, Uart> {
+impl<'a, Uart: UartTransport> TMC2209UART<'a, Uart> {
     /// There is a `uart: &'a Mutex<RefCell<Option<Uart>>>` parameter here.
     /// You are supposed to use a special pettern to share Uart instance
     /// between different parts of the code (usually tasks, interrupts)
@@ -40,6 +90,67 @@ impl<'a, Uart: Read + Write> TMC2209UART<'a, Uart> {
         }
     }
 
+    /// Probe all four node addresses on the shared UART. Use it to find
+    /// drivers and check MS1/MS2 wiring before creating instances.
+    /// `baud_rate` is the rate of the host UART, drivers are expected to
+    /// use the power-up SENDDELAY
+    pub fn scan_bus(
+        shared_uart: &'a Mutex<RefCell<Option<Uart>>>,
+        baud_rate: u32,
+    ) -> Result<BusScan, ()> {
+        let timing = UartTiming::new(baud_rate, 0);
+        critical_section::with(|cs| {
+            let mut uart_cell = shared_uart.borrow(cs).borrow_mut();
+            if let Some(uart) = uart_cell.as_mut() {
+                Ok(scan_bus(uart, &timing))
+            } else {
+                return Err(());
+            }
+        })
+    }
+
+    /// Probe the configured node address
+    pub fn probe(&self) -> Result<NodeProbe, ()> {
+        critical_section::with(|cs| {
+            let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+            if let Some(uart) = uart_cell.as_mut() {
+                Ok(probe_node(
+                    uart,
+                    self.base_config.uart_address,
+                    &self.uart_timing(),
+                ))
+            } else {
+                return Err(());
+            }
+        })
+    }
+
+    /// Use another node address (0..3, as set by MS1/MS2 pins). The address
+    /// is only changed if a single driver answers on it
+    pub fn set_uart_address(
+        &mut self,
+        uart_address: u8,
+    ) -> Result<TMC2209_NodeInfo, ()> {
+        if uart_address > 3 {
+            return Err(());
+        }
+        let probe = critical_section::with(|cs| {
+            let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+            if let Some(uart) = uart_cell.as_mut() {
+                Ok(probe_node(uart, uart_address, &self.uart_timing()))
+            } else {
+                return Err(());
+            }
+        })?;
+        match probe {
+            NodeProbe::Responding(info) => {
+                self.base_config.uart_address = uart_address;
+                Ok(info)
+            }
+            _ => Err(()),
+        }
+    }
+
     /// Load readable registers from driver and save it in saved_config
     pub fn init_saved_config(&mut self) -> Result<(), ()> {
         let debug_config = self.debug_read_config_from_driver()?;
@@ -48,16 +159,61 @@ impl<'a, Uart: Read + Write> TMC2209UART<'a, Uart> {
         Ok(())
     }
 
-    /// Send TMC2209_Config to driver
-    pub fn apply_config(&mut self, config: &TMC2209_Config) -> Result<(), ()> {
+    /// Fields of config the chip (`base_config.chip_variant`) does not
+    /// support. `apply_config()` refuses such configs
+    // Violations are stored inline, there is no allocator in no_std
+    #[allow(clippy::result_large_err)]
+    pub fn check_config(
+        &self,
+        config: &TMC2209_Config,
+    ) -> Result<(), ConfigViolations> {
+        let violations =
+            config.check_chip_support(self.base_config.chip_variant);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Send TMC2209_Config to driver. Invalid (see
+    /// `TMC2209_Config::validate()`) and unsupported configs are refused
+    /// before anything is sent
+    // Violations are stored inline, there is no allocator in no_std
+    #[allow(clippy::result_large_err)]
+    pub fn apply_config(
+        &mut self,
+        config: &TMC2209_Config,
+    ) -> Result<(), ApplyConfigError> {
+        // Out of range values would be truncated into other register fields
+        let violations = config.validate();
+        if !violations.is_empty() {
+            return Err(ApplyConfigError::Invalid(violations));
+        }
+        // Do not send writes the chip ignores (see check_config())
+        self.check_config(config)
+            .map_err(ApplyConfigError::Unsupported)?;
+        if is_hold_current_changed(config)
+            && !is_run_current_known(config, &self.saved_config)
+        {
+            return Err(ApplyConfigError::UnknownRunCurrent);
+        }
+
+        // config.uart_address changes base config, registers are read and
+        // written at the address (and SENDDELAY) used before
+        let uart_address = self.base_config.uart_address;
+        let timing = self.uart_timing();
+        let base_config_before = self.base_config.clone();
+
         // Read registers changed by config
         let mut ready_registers = critical_section::with(|cs| {
             let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
             if let Some(uart) = uart_cell.as_mut() {
                 return get_registers_changed_in_config(
                     uart,
-                    self.base_config.uart_address,
+                    uart_address,
                     config,
+                    &timing,
                 );
             } else {
                 return Err(());
@@ -76,23 +232,35 @@ impl<'a, Uart: Read + Write> TMC2209UART<'a, Uart> {
                 );
 
                 // Write registers to driver
-                critical_section::with(|cs| {
+                let result = critical_section::with(|cs| {
                     let mut uart_cell =
                         self.shared_uart.borrow(cs).borrow_mut();
                     if let Some(uart) = uart_cell.as_mut() {
                         return write_registers_changed_in_config(
                             uart,
-                            self.base_config.uart_address,
+                            uart_address,
                             ready_registers,
+                            &timing,
                         );
+                    } else {
+                        return Err(());
                     }
+                });
 
-                    // Config writed succesful, save it
+                // Config writed succesful, save it
+                if result.is_ok() {
+                    process_written_registers(
+                        ready_registers,
+                        &self.base_config,
+                        &mut config_for_save,
+                    );
                     self.saved_config = config_for_save;
-                    Ok(())
-                })
+                } else {
+                    self.base_config = base_config_before;
+                }
+                result.map_err(|_| ApplyConfigError::Uart)
             }
-            Err(_) => return Err(()),
+            Err(_) => return Err(ApplyConfigError::Uart),
         }
     }
 
@@ -106,6 +274,7 @@ impl<'a, Uart: Read + Write> TMC2209UART<'a, Uart> {
                 return debug_read_config_from_driver(
                     uart,
                     self.base_config.uart_address,
+                    &self.uart_timing(),
                 );
             } else {
                 return Err(());
@@ -118,7 +287,12 @@ impl<'a, Uart: Read + Write> TMC2209UART<'a, Uart> {
         critical_section::with(|cs| {
             let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
             if let Some(uart) = uart_cell.as_mut() {
-                set_vactual(uart, self.base_config.uart_address, v_actual)
+                set_vactual(
+                    uart,
+                    self.base_config.uart_address,
+                    v_actual,
+                    &self.uart_timing(),
+                )
             } else {
                 return Err(());
             }
@@ -131,7 +305,7 @@ impl<'a, Uart: Read + Write> TMC2209UART<'a, Uart> {
             shaft: Some(shaft),
             ..Default::default()
         };
-        self.apply_config(&config)
+        self.apply_config(&config).map_err(|_| ())
     }
 
     /// Invert motor direction
@@ -140,35 +314,817 @@ impl<'a, Uart: Read + Write> TMC2209UART<'a, Uart> {
             shaft: Some(!self.saved_config.shaft),
             ..Default::default()
         };
-        self.apply_config(&config)
+        self.apply_config(&config).map_err(|_| ())
+    }
+
+    /// Set run current, mA RMS. Returns current actually set
+    pub fn set_run_current(&mut self, run_current: u16) -> Result<u16, ()> {
+        let config = TMC2209_Config {
+            rms_current: Some(run_current),
+            ..Default::default()
+        };
+        self.apply_config(&config).map_err(|_| ())?;
+        Ok(self.saved_config.rms_current)
+    }
+
+    /// How run current would be set, without sending it. Use it to warn
+    /// about out of range or imprecise requests
+    pub fn solve_run_current(&self, run_current: u16) -> CurrentSolution {
+        let max_rms_current = self.base_config.chip_variant.max_rms_current();
+        let scaling = self.current_scaling();
+        let mut solution = solve_rms_current(
+            run_current.min(max_rms_current),
+            scaling.r_sense,
+            scaling.analog_scale,
+        );
+
+        // Chip rating is lower than driver current range
+        if run_current > max_rms_current {
+            solution.range = CurrentRange::AboveMaximum;
+            solution.error = solution.rms_current as i32 - run_current as i32;
+            solution.error_percent =
+                solution.error as f32 / run_current as f32 * 100.0;
+        }
+        solution
+    }
+
+    /// Set run current by peak value, mA. Returns peak current actually set
+    pub fn set_peak_current(&mut self, peak_current: u16) -> Result<u16, ()> {
+        let run_current = (peak_current as f32 / SQRT_2 + 0.5) as u16;
+        self.set_run_current(run_current)?;
+        Ok(self.get_currents().peak_current)
+    }
+
+    /// Set hold current, mA RMS (instead of ihold_multiplier). Set run
+    /// current first. Returns current actually set
+    pub fn set_hold_current(&mut self, hold_current: u16) -> Result<u16, ()> {
+        let config = TMC2209_Config {
+            hold_current: Some(hold_current),
+            ..Default::default()
+        };
+        self.apply_config(&config).map_err(|_| ())?;
+        Ok(self.saved_config.hold_current)
+    }
+
+    /// Configure current reduction at standstill. Set run current first,
+    /// ramp time depends on difference between run and hold current.
+    /// Returns values actually set
+    pub fn set_standstill_reduction(
+        &mut self,
+        reduction: &StandstillReduction,
+    ) -> Result<StandstillReduction, ()> {
+        let fclk = self.base_config.fclk;
+        let irun = self.current_to_cs(self.saved_config.rms_current);
+        let ihold = self.current_to_cs(reduction.hold_current);
+
+        let config = TMC2209_Config {
+            hold_current: Some(reduction.hold_current),
+            ihold_delay: Some(ihold_delay_from_ms(
+                reduction.ramp_time_ms,
+                irun,
+                ihold,
+                fclk,
+            )),
+            tpowerdown: Some(tpowerdown_from_seconds(
+                reduction.power_down_delay_s,
+                fclk,
+            )),
+            ..Default::default()
+        };
+        self.apply_config(&config).map_err(|_| ())?;
+
+        Ok(StandstillReduction {
+            hold_current: self.saved_config.hold_current,
+            ramp_time_ms: ihold_delay_to_ms(
+                self.saved_config.ihold_delay,
+                irun,
+                ihold,
+                fclk,
+            ),
+            power_down_delay_s: tpowerdown_to_seconds(
+                self.saved_config.tpowerdown,
+                fclk,
+            ),
+        })
+    }
+
+    fn current_scaling(&self) -> CurrentScaling {
+        self.base_config.current_scaling(
+            self.saved_config.i_scale_analog,
+            self.saved_config.internal_rsense,
+        )
+    }
+
+    // Current scale of RMS current (mA) with saved vsense
+    fn current_to_cs(&self, current: u16) -> u8 {
+        let scaling = self.current_scaling();
+        rms_current_to_cs(
+            current,
+            self.saved_config.vsense,
+            scaling.r_sense,
+            scaling.analog_scale,
+        )
+    }
+
+    /// Currents set in driver (by the last apply_config())
+    pub fn get_currents(&self) -> MotorCurrents {
+        let run_current = self.saved_config.rms_current;
+        MotorCurrents {
+            run_current,
+            peak_current: (run_current as f32 * SQRT_2 + 0.5) as u16,
+            hold_current: self.saved_config.hold_current,
+        }
+    }
+
+    /// Switch chopper mode using preset calculated for motor
+    /// (see [`ChopperMode::preset_config`]). Uses sense resistor (or
+    /// equivalent of internal ones), full steps and fclk from base config
+    pub fn set_chopper_mode(
+        &mut self,
+        chopper_mode: ChopperMode,
+        motor: &MotorParameters,
+    ) -> Result<(), ()> {
+        let base_config = TMC2209_BaseConfig {
+            r_sense: self.current_scaling().r_sense,
+            ..self.base_config.clone()
+        };
+        let config = chopper_mode.preset_config(&base_config, motor)?;
+        self.apply_config(&config).map_err(|_| ())
+    }
+
+    /// Calculate spreadCycle chopper settings for motor (see
+    /// [`spreadcycle_chopper_settings`]) and apply them.
+    /// Uses sense resistor and fclk from base config
+    pub fn apply_spreadcycle_chopper_settings(
+        &mut self,
+        motor: &MotorParameters,
+    ) -> Result<SpreadCycleChopperSettings, ()> {
+        let settings = spreadcycle_chopper_settings(
+            motor,
+            self.current_scaling().r_sense,
+            self.base_config.fclk,
+        )?;
+        let config = TMC2209_Config {
+            toff: Some(settings.toff),
+            blank_time: Some(settings.blank_time),
+            hysteresis_start: Some(settings.hysteresis_start),
+            hysteresis_end: Some(settings.hysteresis_end),
+            ..Default::default()
+        };
+        self.apply_config(&config).map_err(|_| ())?;
+        Ok(settings)
+    }
+
+    /// Run stealthChop automatic tuning (see tmc2209 datasheet, page 37):
+    /// AT#1 - standstill at run current until PWM_OFS_AUTO settles,
+    /// AT#2 - motion with `options.velocity` (VACTUAL) until PWM_GRAD_AUTO
+    /// settles and PWM_SCALE_AUTO gets close to zero.
+    /// The motor is stopped and hold current restored when tuning ends.
+    /// Driver must be configured for stealthChop with pwm_autoscale and
+    /// pwm_autograd, and run current must be set.
+    /// Store the result in config (see [`StealthChopAutotuneResult::apply_to`])
+    /// to skip tuning on the next boot
+    pub fn autotune_stealthchop<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+        options: &StealthChopAutotuneOptions,
+    ) -> Result<StealthChopAutotuneResult, StealthChopAutotuneError> {
+        if self.saved_config.en_spreadcycle
+            || !self.saved_config.pwm_autoscale
+            || !self.saved_config.pwm_autograd
+            || self.saved_config.rms_current == 0
+        {
+            return Err(StealthChopAutotuneError::NotConfigured);
+        }
+
+        // IHOLD_IRUN is write only, rebuild it from saved config
+        let irun = self.current_to_cs(self.saved_config.rms_current);
+        let ihold = if self.saved_config.hold_current > 0 {
+            self.current_to_cs(self.saved_config.hold_current)
+        } else {
+            (irun as f32 * self.base_config.ihold_multiplier) as u8
+        };
+        let mut ihold_irun = tmc2209::reg::IHOLD_IRUN::default();
+        ihold_irun.set_irun(irun);
+        ihold_irun.set_ihold_delay(self.saved_config.ihold_delay);
+
+        // AT#1 needs run current at standstill
+        ihold_irun.set_ihold(irun);
+        self.write_ihold_irun(ihold_irun)?;
+        let result = self.run_stealthchop_autotune(delay, options);
+
+        // Stop and restore hold current even if tuning failed
+        let stopped = self.vactual(0);
+        ihold_irun.set_ihold(ihold);
+        let restored = self.write_ihold_irun(ihold_irun);
+
+        let result = result?;
+        stopped.map_err(|_| StealthChopAutotuneError::Uart)?;
+        restored?;
+        Ok(result)
+    }
+
+    fn run_stealthchop_autotune<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+        options: &StealthChopAutotuneOptions,
+    ) -> Result<StealthChopAutotuneResult, StealthChopAutotuneError> {
+        // AT#1
+        self.vactual(0)
+            .map_err(|_| StealthChopAutotuneError::Uart)?;
+        delay.delay_ms(options.standstill_time_ms);
+        self.wait_for_pwm_auto(delay, options, false)
+            .ok_or(StealthChopAutotuneError::StandstillTimeout)??;
+
+        // AT#2
+        self.vactual(options.velocity)
+            .map_err(|_| StealthChopAutotuneError::Uart)?;
+        self.wait_for_pwm_auto(delay, options, true)
+            .ok_or(StealthChopAutotuneError::MotionTimeout)?
+    }
+
+    // Poll PWM_AUTO until it stays unchanged for `stable_reads` reads (and
+    // PWM_SCALE_AUTO is settled, if `motion`). None on timeout
+    fn wait_for_pwm_auto<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+        options: &StealthChopAutotuneOptions,
+        motion: bool,
+    ) -> Option<Result<StealthChopAutotuneResult, StealthChopAutotuneError>>
+    {
+        let mut previous: Option<StealthChopAutotuneResult> = None;
+        let mut stable_reads = 0;
+        let mut elapsed_ms = 0;
+
+        while elapsed_ms <= options.timeout_ms {
+            let current = match self.read_pwm_tuning() {
+                Ok(current) => current,
+                Err(_) => return Some(Err(StealthChopAutotuneError::Uart)),
+            };
+
+            let unchanged = match previous {
+                Some(previous) => {
+                    previous.pwm_ofs == current.pwm_ofs
+                        && previous.pwm_grad == current.pwm_grad
+                }
+                None => false,
+            };
+            let settled = !motion
+                || current.pwm_scale_auto.unsigned_abs()
+                    <= options.scale_auto_tolerance;
+
+            if unchanged && settled {
+                stable_reads += 1;
+                if stable_reads >= options.stable_reads {
+                    return Some(Ok(current));
+                }
+            } else {
+                stable_reads = 0;
+            }
+            previous = Some(current);
+
+            delay.delay_ms(options.poll_interval_ms);
+            elapsed_ms += options.poll_interval_ms;
+        }
+
+        None
+    }
+
+    fn read_pwm_tuning(&mut self) -> Result<StealthChopAutotuneResult, ()> {
+        critical_section::with(|cs| {
+            let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+            if let Some(uart) = uart_cell.as_mut() {
+                let address = self.base_config.uart_address;
+                let timing = self.uart_timing();
+                let pwm_scale = read_pwm_scale(uart, address, &timing)?;
+                let pwm_auto = read_pwm_auto(uart, address, &timing)?;
+                Ok(StealthChopAutotuneResult {
+                    pwm_ofs: pwm_auto.pwm_ofs_auto() as u8,
+                    pwm_grad: pwm_auto.pwm_grad_auto() as u8,
+                    pwm_scale_sum: pwm_scale.pwm_scale_sum() as u8,
+                    // 9 bit two's complement, sign extended
+                    pwm_scale_auto: ((pwm_scale.pwm_scale_auto() as i16) << 7)
+                        >> 7,
+                })
+            } else {
+                return Err(());
+            }
+        })
     }
 
-    /// Read SG_RESULT
+    fn write_ihold_irun(
+        &mut self,
+        ihold_irun: tmc2209::reg::IHOLD_IRUN,
+    ) -> Result<(), StealthChopAutotuneError> {
+        critical_section::with(|cs| {
+            let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+            if let Some(uart) = uart_cell.as_mut() {
+                write_ihold_irun(
+                    uart,
+                    self.base_config.uart_address,
+                    ihold_irun,
+                    &self.uart_timing(),
+                )
+            } else {
+                return Err(());
+            }
+        })
+        .map_err(|_| StealthChopAutotuneError::Uart)
+    }
+
+    /// Read SG_RESULT (Err for chips without StallGuard)
     pub fn read_sg_result(&mut self) -> Result<u16, ()> {
+        if !self.base_config.chip_variant.has_stallguard() {
+            return Err(());
+        }
+        critical_section::with(|cs| {
+            let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+            if let Some(uart) = uart_cell.as_mut() {
+                read_sg_result(
+                    uart,
+                    self.base_config.uart_address,
+                    &self.uart_timing(),
+                )
+            } else {
+                return Err(());
+            }
+        })
+    }
+
+    /// Read TSTEP (time between 1/256 microsteps in 1/fclk)
+    pub fn read_tstep(&mut self) -> Result<u32, ()> {
+        critical_section::with(|cs| {
+            let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+            if let Some(uart) = uart_cell.as_mut() {
+                read_tstep(
+                    uart,
+                    self.base_config.uart_address,
+                    &self.uart_timing(),
+                )
+            } else {
+                return Err(());
+            }
+        })
+    }
+
+    /// Actual velocity calculated from TSTEP, rpm (0 at standstill).
+    /// Uses full_steps_per_rotation and fclk from base config
+    pub fn read_velocity_rpm(&mut self) -> Result<f32, ()> {
+        let tstep = self.read_tstep()?;
+        Ok(tstep_to_rpm(
+            tstep,
+            self.base_config.full_steps_per_rotation,
+            self.base_config.fclk,
+        ))
+    }
+
+    /// Read MSCNT (microstep table position)
+    pub fn read_mscnt(&mut self) -> Result<u16, ()> {
+        critical_section::with(|cs| {
+            let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+            if let Some(uart) = uart_cell.as_mut() {
+                read_mscnt(
+                    uart,
+                    self.base_config.uart_address,
+                    &self.uart_timing(),
+                )
+            } else {
+                return Err(());
+            }
+        })
+    }
+
+    /// Read MSCURACT (actual phase A/B currents)
+    pub fn read_phase_currents(&mut self) -> Result<TMC2209_PhaseCurrents, ()> {
+        critical_section::with(|cs| {
+            let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+            if let Some(uart) = uart_cell.as_mut() {
+                read_phase_currents(
+                    uart,
+                    self.base_config.uart_address,
+                    &self.uart_timing(),
+                )
+            } else {
+                return Err(());
+            }
+        })
+    }
+
+    /// Read PWM_SCALE (PWM_SCALE_SUM and PWM_SCALE_AUTO)
+    pub fn read_pwm_scale(&mut self) -> Result<TMC2209_PwmScale, ()> {
+        critical_section::with(|cs| {
+            let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+            if let Some(uart) = uart_cell.as_mut() {
+                read_pwm_scale_values(
+                    uart,
+                    self.base_config.uart_address,
+                    &self.uart_timing(),
+                )
+            } else {
+                return Err(());
+            }
+        })
+    }
+
+    /// Read PWM_AUTO (PWM_OFS_AUTO and PWM_GRAD_AUTO)
+    pub fn read_pwm_auto(&mut self) -> Result<TMC2209_PwmAuto, ()> {
+        critical_section::with(|cs| {
+            let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+            if let Some(uart) = uart_cell.as_mut() {
+                read_pwm_auto_values(
+                    uart,
+                    self.base_config.uart_address,
+                    &self.uart_timing(),
+                )
+            } else {
+                return Err(());
+            }
+        })
+    }
+
+    /// Read DRV_STATUS (temperature, short and open load flags)
+    pub fn read_drv_status(&mut self) -> Result<TMC2209_DrvStatus, ()> {
+        critical_section::with(|cs| {
+            let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+            if let Some(uart) = uart_cell.as_mut() {
+                read_drv_status(
+                    uart,
+                    self.base_config.uart_address,
+                    &self.uart_timing(),
+                )
+            } else {
+                return Err(());
+            }
+        })
+    }
+
+    /// Read GSTAT (reset, driver error, charge pump undervoltage)
+    pub fn read_gstat(&mut self) -> Result<TMC2209_Gstat, ()> {
+        critical_section::with(|cs| {
+            let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+            if let Some(uart) = uart_cell.as_mut() {
+                read_gstat(
+                    uart,
+                    self.base_config.uart_address,
+                    &self.uart_timing(),
+                )
+            } else {
+                return Err(());
+            }
+        })
+    }
+
+    /// Clear GSTAT flags which are set in `gstat`
+    pub fn clear_gstat(&mut self, gstat: &TMC2209_Gstat) -> Result<(), ()> {
+        critical_section::with(|cs| {
+            let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+            if let Some(uart) = uart_cell.as_mut() {
+                clear_gstat(
+                    uart,
+                    self.base_config.uart_address,
+                    gstat,
+                    &self.uart_timing(),
+                )
+            } else {
+                return Err(());
+            }
+        })
+    }
+
+    /// Read IOIN (pin levels and silicon version)
+    pub fn read_ioin(&mut self) -> Result<TMC2209_IoinState, ()> {
+        critical_section::with(|cs| {
+            let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+            if let Some(uart) = uart_cell.as_mut() {
+                read_ioin(
+                    uart,
+                    self.base_config.uart_address,
+                    &self.uart_timing(),
+                )
+            } else {
+                return Err(());
+            }
+        })
+    }
+
+    /// Detect driver chip by IOIN. Use it at boot to catch boards with
+    /// wrong driver variant
+    pub fn identify(&mut self) -> Result<ChipIdentity, ()> {
+        let ioin = self.read_ioin()?;
+        Ok(ChipIdentity::from_ioin(&ioin))
+    }
+
+    /// Read all telemetry registers in one critical section, so values
+    /// belong to the same moment. `timestamp` is stored as is
+    pub fn read_telemetry(
+        &mut self,
+        timestamp: u64,
+    ) -> Result<TMC2209_Telemetry, ()> {
+        critical_section::with(|cs| {
+            let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+            if let Some(uart) = uart_cell.as_mut() {
+                read_telemetry(
+                    uart,
+                    &self.base_config,
+                    timestamp,
+                    &self.uart_timing(),
+                )
+            } else {
+                return Err(());
+            }
+        })
+    }
+
+    /// Read OTP memory contents
+    pub fn read_otp(&mut self) -> Result<TMC2209_Otp, ()> {
+        critical_section::with(|cs| {
+            let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+            if let Some(uart) = uart_cell.as_mut() {
+                read_otp(
+                    uart,
+                    self.base_config.uart_address,
+                    &self.uart_timing(),
+                )
+            } else {
+                return Err(());
+            }
+        })
+    }
+
+    /// Register defaults the driver loads from OTP at power-up
+    pub fn read_otp_power_up_config(
+        &mut self,
+    ) -> Result<TMC2209_OtpPowerUpConfig, ()> {
+        Ok(self.read_otp()?.power_up_config())
+    }
+
+    /// Burn one OTP bit. This is permanent: the token has to be made for
+    /// the same bit with [`OtpConfirmToken::new`]. With [`OtpMode::DryRun`]
+    /// nothing is written and the report shows the resulting contents.
+    /// Supply voltage must be stable during programming
+    pub fn program_otp_bit<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+        bit: OtpBit,
+        token: OtpConfirmToken,
+        mode: OtpMode,
+    ) -> Result<OtpProgramReport, OtpError> {
+        if token.bit != bit {
+            return Err(OtpError::WrongToken);
+        }
+        let before = self.read_otp().map_err(|_| OtpError::Uart)?;
+        let expected = before.with_bit(bit);
+        let mut report = OtpProgramReport {
+            before,
+            after: expected,
+            power_up_config: expected.power_up_config(),
+            programmed: false,
+        };
+        if mode == OtpMode::DryRun || before.is_set(bit) {
+            return Ok(report);
+        }
+
+        critical_section::with(|cs| {
+            let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+            if let Some(uart) = uart_cell.as_mut() {
+                write_otp_prog(
+                    uart,
+                    self.base_config.uart_address,
+                    bit,
+                    &self.uart_timing(),
+                )
+            } else {
+                return Err(());
+            }
+        })
+        .map_err(|_| OtpError::Uart)?;
+        report.programmed = true;
+        delay.delay_ms(OTP_PROGRAM_TIME_MS);
+
+        let after = self.read_otp().map_err(|_| OtpError::Uart)?;
+        if !after.is_set(bit) {
+            return Err(OtpError::VerifyFailed);
+        }
+        report.after = after;
+        report.power_up_config = after.power_up_config();
+        Ok(report)
+    }
+
+    /// Read any readable register of the tmc2209 crate, for registers the
+    /// config does not cover
+    pub fn read_register<Reg: tmc2209::reg::ReadableRegister>(
+        &mut self,
+    ) -> Result<Reg, ()> {
         critical_section::with(|cs| {
             let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
             if let Some(uart) = uart_cell.as_mut() {
-                read_sg_result(uart, self.base_config.uart_address)
+                read_reg_blocking::<Reg, _>(
+                    uart,
+                    self.base_config.uart_address,
+                    &self.uart_timing(),
+                )
             } else {
                 return Err(());
             }
         })
     }
 
+    /// Write any writable register of the tmc2209 crate. Saved config is
+    /// updated from the written value
+    pub fn write_register<Reg: tmc2209::reg::WritableRegister>(
+        &mut self,
+        reg: Reg,
+    ) -> Result<(), ()> {
+        self.write_raw(Reg::ADDRESS as u8, reg.into())
+    }
+
+    /// Read register by address (for tools, where the register is chosen at
+    /// runtime)
+    pub fn read_raw(&mut self, reg_address: u8) -> Result<u32, ()> {
+        critical_section::with(|cs| {
+            let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+            if let Some(uart) = uart_cell.as_mut() {
+                read_raw_blocking(
+                    uart,
+                    self.base_config.uart_address,
+                    reg_address,
+                    &self.uart_timing(),
+                )
+                .map_err(|_| ())
+            } else {
+                return Err(());
+            }
+        })
+    }
+
+    /// Write register by address. Saved config is updated from the written
+    /// value. OTP_PROG is refused, use [`Self::program_otp_bit`]
+    pub fn write_raw(&mut self, reg_address: u8, value: u32) -> Result<(), ()> {
+        if reg_address == tmc2209::reg::Address::OTP_PROG as u8 {
+            return Err(());
+        }
+        critical_section::with(|cs| {
+            let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+            if let Some(uart) = uart_cell.as_mut() {
+                write_raw(
+                    uart,
+                    self.base_config.uart_address,
+                    reg_address,
+                    value,
+                    &self.uart_timing(),
+                )
+            } else {
+                return Err(());
+            }
+        })?;
+        process_raw_register_write(
+            reg_address,
+            value,
+            &self.base_config,
+            &mut self.saved_config,
+        );
+        Ok(())
+    }
+
+    /// UART timing for base config baud rate and saved SENDDELAY
+    pub fn uart_timing(&self) -> UartTiming {
+        UartTiming::new(self.base_config.baud_rate, self.saved_config.senddelay)
+    }
+
     /// Get saved config
     pub fn get_saved_config(&self) -> &TMC2209_SavedConfig {
         &self.saved_config
     }
 
+    /// Get base config (changed by applied uart_address, r_sense, ...)
+    pub fn get_base_config(&self) -> &TMC2209_BaseConfig {
+        &self.base_config
+    }
+
     /// Test connect to TMC2209. Returns true if connection was succesful
     pub fn test_connection(&self) -> bool {
         critical_section::with(|cs| {
             let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
             if let Some(uart) = uart_cell.as_mut() {
-                test_connection(uart, self.base_config.uart_address)
+                test_connection(
+                    uart,
+                    self.base_config.uart_address,
+                    &self.uart_timing(),
+                )
             } else {
                 return false;
             }
         })
     }
+
+    /// Get base and saved config packed for storing in flash/EEPROM
+    pub fn get_persisted_config(&self) -> TMC2209_PersistedConfig {
+        TMC2209_PersistedConfig::new(&self.base_config, &self.saved_config)
+    }
+
+    /// Send persisted config (for example loaded at boot) to driver. Only
+    /// registers known when the config was stored are sent, base config is
+    /// restored too
+    #[allow(clippy::result_large_err)]
+    pub fn restore_persisted_config(
+        &mut self,
+        persisted: &TMC2209_PersistedConfig,
+    ) -> Result<(), ApplyConfigError> {
+        let config = TMC2209_Config::from_saved_config(
+            &persisted.saved_config,
+            &persisted.base_config,
+        );
+        // Node address, r_sense and ihold_multiplier are changed by config,
+        // after the registers were sent at the current address
+        let base_config_before = self.base_config.clone();
+        self.base_config = TMC2209_BaseConfig {
+            uart_address: self.base_config.uart_address,
+            r_sense: self.base_config.r_sense,
+            ihold_multiplier: self.base_config.ihold_multiplier,
+            ..persisted.base_config.clone()
+        };
+        let result = self.apply_config(&config);
+        if result.is_err() {
+            self.base_config = base_config_before;
+        }
+        result
+    }
+
+    /// Store current base and saved config in flash at `offset`
+    // Violations of a refused restore are stored inline, there is no
+    // allocator in no_std
+    #[allow(clippy::result_large_err)]
+    pub fn save_config_to_flash<Flash: NorFlash>(
+        &self,
+        flash: &mut Flash,
+        offset: u32,
+    ) -> Result<(), PersistedConfigError> {
+        self.get_persisted_config().write_to_flash(flash, offset)
+    }
+
+    /// Load config stored by [`Self::save_config_to_flash`] and send it to
+    /// driver
+    // Violations of a refused restore are stored inline, there is no
+    // allocator in no_std
+    #[allow(clippy::result_large_err)]
+    pub fn restore_config_from_flash<Flash: ReadNorFlash>(
+        &mut self,
+        flash: &mut Flash,
+        offset: u32,
+    ) -> Result<(), PersistedConfigError> {
+        let persisted =
+            TMC2209_PersistedConfig::read_from_flash(flash, offset)?;
+        self.restore_persisted_config(&persisted)
+            .map_err(PersistedConfigError::Apply)
+    }
+
+    /// Find the baud rate the driver answers at. For every rate of
+    /// `baud_rates` (for example [`crate::utils::timing::DEFAULT_BAUD_RATES`])
+    /// `reconfigure` switches the host UART, then IFCNT is read with the
+    /// reply timeout. `delay` waits for the bus reset in between, outside
+    /// the critical section. The found rate is stored in base config
+    pub fn detect_baud_rate<Delay, Reconfigure>(
+        &mut self,
+        delay: &mut Delay,
+        baud_rates: &[u32],
+        mut reconfigure: Reconfigure,
+    ) -> Result<u32, ()>
+    where
+        Delay: DelayNs,
+        Reconfigure: FnMut(&mut Uart, u32) -> Result<(), ()>,
+    {
+        for baud_rate in baud_rates.iter().copied() {
+            let timing =
+                UartTiming::new(baud_rate, self.saved_config.senddelay);
+            critical_section::with(|cs| {
+                let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+                if let Some(uart) = uart_cell.as_mut() {
+                    reconfigure(uart, baud_rate)
+                } else {
+                    return Err(());
+                }
+            })?;
+            // Let the driver drop anything received at the old rate
+            delay.delay_us(timing.bus_reset_us());
+
+            let reply = critical_section::with(|cs| {
+                let mut uart_cell = self.shared_uart.borrow(cs).borrow_mut();
+                if let Some(uart) = uart_cell.as_mut() {
+                    read_reg_checked::<tmc2209::reg::IFCNT, _>(
+                        uart,
+                        self.base_config.uart_address,
+                        &timing,
+                    )
+                    .map_err(|_| ())
+                } else {
+                    return Err(());
+                }
+            });
+            if reply.is_ok() {
+                self.base_config.baud_rate = baud_rate;
+                return Ok(baud_rate);
+            }
+        }
+        Err(())
+    }
 }
@@ -4,14 +4,28 @@
 //! based on the mitchmindtree/tmc2209 library
 //! and compatible with the stepper library
 
-#![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
 #![allow(dead_code)]
 
+#[cfg(feature = "std")]
+extern crate core;
+
+#[cfg(feature = "defmt")]
+extern crate defmt;
+#[cfg(feature = "std")]
+extern crate libc;
+#[cfg(feature = "log")]
+extern crate log;
+
 pub extern crate critical_section;
 pub extern crate embedded_hal;
 pub extern crate embedded_io;
+pub extern crate embedded_storage;
 pub extern crate tmc2209;
 
+#[macro_use]
+mod macros;
+
 pub mod implementation;
 pub mod structures;
 pub mod utils;
@@ -21,13 +35,13 @@ use crate::structures::{
 };
 use core::cell::RefCell;
 use critical_section::Mutex;
-use embedded_io::{Read, Write};
+use utils::timing::UartTransport;
 
 /// The TMC2209UART driver API
 ///
 /// Users are not expected to use this API directly, except to create an
 /// instance using [`TMC2209UART::new`].
-pub struct TMC2209UART<'a, Uart: Read + Write> {
+pub struct TMC2209UART<'a, Uart: UartTransport> {
     shared_uart: &'a Mutex<RefCell<Option<Uart>>>,
     base_config: TMC2209_BaseConfig,
     saved_config: TMC2209_SavedConfig,
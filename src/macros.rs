@@ -0,0 +1,41 @@
+// Logging through `defmt` and/or `log`, whichever of the features is
+// enabled. Format strings must stick to `{}`, `{:?}` and `{:#x}`, which are
+// understood by both crates. Without any of the features arguments are only
+// type checked, so trace-only values do not cause unused warnings
+
+macro_rules! tmc_log {
+    ($level:ident, $($arg:tt)+) => {{
+        #[cfg(feature = "defmt")]
+        ::defmt::$level!($($arg)+);
+        #[cfg(feature = "log")]
+        ::log::$level!($($arg)+);
+        #[cfg(not(any(feature = "defmt", feature = "log")))]
+        {
+            let _ = format_args!($($arg)+);
+        }
+    }};
+}
+
+macro_rules! tmc_trace {
+    ($($arg:tt)+) => {
+        tmc_log!(trace, $($arg)+)
+    };
+}
+
+macro_rules! tmc_debug {
+    ($($arg:tt)+) => {
+        tmc_log!(debug, $($arg)+)
+    };
+}
+
+macro_rules! tmc_info {
+    ($($arg:tt)+) => {
+        tmc_log!(info, $($arg)+)
+    };
+}
+
+macro_rules! tmc_warn {
+    ($($arg:tt)+) => {
+        tmc_log!(warn, $($arg)+)
+    };
+}
@@ -0,0 +1,20 @@
+use crate::structures::config_violations::ConfigViolations;
+
+/// Why `apply_config()` did not apply a config. Nothing is written to the
+/// driver, except for `Uart` errors after some registers were sent
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ApplyConfigError {
+    /// Fields out of register range or breaking datasheet constraints
+    /// (see `TMC2209_Config::validate()`)
+    Invalid(ConfigViolations),
+    /// Fields the chip (`base_config.chip_variant`) does not support
+    /// (see `check_config()`)
+    Unsupported(ConfigViolations),
+    /// Config changes hold current or IHOLDDELAY, but run current is neither
+    /// in config nor known from earlier configs. IHOLD_IRUN is write only
+    /// and would be sent with IRUN = 0
+    UnknownRunCurrent,
+    /// UART communication failed
+    Uart,
+}
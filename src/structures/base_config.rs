@@ -1,4 +1,8 @@
+use crate::structures::chip_variant::ChipVariant;
+
 #[allow(non_camel_case_types)]
+#[derive(Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 //. Some values that are not sent to the driver, but are involved in the calculations
 pub struct TMC2209_BaseConfig {
     /// You can connect multiple drivers to one uart (see tmc2209 datasheet, page 17, 18)
@@ -9,4 +13,27 @@ pub struct TMC2209_BaseConfig {
 
     /// You can decrease hold current (in comparison to run current) with this multiplier
     pub ihold_multiplier: f32,
+
+    /// Driver clock frequency in Hz, used to convert velocities into
+    /// TSTEP based thresholds (see tmc2209 datasheet, page 8)
+    pub fclk: f32,
+
+    /// Motor full steps per rotation (200 for 1.8° motors, 400 for 0.9°)
+    pub full_steps_per_rotation: u32,
+
+    /// Driver chip, gates StallGuard/CoolStep fields and limits current
+    pub chip_variant: ChipVariant,
+
+    /// Voltage on VREF pin, V. Scales current if i_scale_analog is on
+    /// (see tmc2209 datasheet, page 48)
+    pub vref: f32,
+
+    /// Reference resistor on VREF pin, Ohm. Sets current if internal_rsense
+    /// is on, r_sense is not used then (see tmc2209 datasheet, page 50)
+    pub rref: f32,
+
+    /// Baud rate of the host UART, used for reply timeouts and gaps between
+    /// datagrams. The driver detects baud rate by itself (see tmc2209
+    /// datasheet, page 18)
+    pub baud_rate: u32,
 }
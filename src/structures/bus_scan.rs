@@ -0,0 +1,31 @@
+/// Number of node addresses on one UART (set by MS1/MS2 pins)
+pub const NODE_ADDRESS_COUNT: usize = 4;
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// Driver found by [`crate::TMC2209UART::scan_bus`]
+pub struct TMC2209_NodeInfo {
+    pub address: u8,
+    /// IFCNT, number of successful writes (wraps at 256)
+    pub ifcnt: u8,
+    /// IOIN VERSION (0x21 for TMC2209)
+    pub version: u8,
+}
+
+/// Result of probing one node address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NodeProbe {
+    Responding(TMC2209_NodeInfo),
+    NoReply,
+    /// Replies had bad CRC, usually several drivers share the address
+    Collision,
+}
+
+/// Result of probing all node addresses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BusScan {
+    pub nodes: [NodeProbe; NODE_ADDRESS_COUNT],
+}
@@ -0,0 +1,16 @@
+/// Driver chip detected by IOIN register (see [`TMC2209_IoinState`])
+///
+/// [`TMC2209_IoinState`]: crate::structures::ioin::TMC2209_IoinState
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ChipIdentity {
+    /// TMC2209 or TMC2226 (same silicon in different packages, they can not
+    /// be told apart over UART)
+    Tmc2209,
+    /// TMC2208 (VERSION 0x20, SEL_A = 1)
+    Tmc2208,
+    /// TMC2225 (VERSION 0x20, SEL_A = 0)
+    Tmc2225,
+    /// Chip answered with unknown VERSION
+    Unknown { version: u8 },
+}
@@ -0,0 +1,12 @@
+/// Driver chip the board is populated with. The TMC220x family shares UART
+/// protocol and most registers, but TMC2208/TMC2225 have no StallGuard and
+/// CoolStep (SGTHRS, SG_RESULT, TCOOLTHRS, COOLCONF)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ChipVariant {
+    #[default]
+    Tmc2209,
+    Tmc2226,
+    Tmc2208,
+    Tmc2225,
+}
@@ -0,0 +1,11 @@
+/// Chopper algorithm used to drive the motor (see tmc2209 datasheet, page 35)
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ChopperMode {
+    /// Voltage-mode chopper, silent at low and medium velocities
+    StealthChop,
+    /// Classic cycle-by-cycle current chopper
+    SpreadCycle,
+    /// stealthChop up to `switch_rpm`, spreadCycle above it
+    Hybrid { switch_rpm: f32 },
+}
@@ -12,11 +12,14 @@
 ///     ..Default::default()
 /// };
 /// ```
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct TMC2209_Config {
     pub uart_address: Option<u8>,
     pub r_sense: Option<f32>,
     pub rms_current: Option<u16>,
     pub ihold_multiplier: Option<f32>,
+    /// Hold current in mA RMS. Overrides ihold_multiplier
+    pub hold_current: Option<u16>,
     pub ihold_delay: Option<u8>,
     pub microsteps: Option<u32>,
     pub interpolation: Option<bool>,
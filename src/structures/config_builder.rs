@@ -0,0 +1,96 @@
+#![allow(non_camel_case_types)]
+use crate::structures::config::TMC2209_Config;
+
+/// Typed builder for [`TMC2209_Config`]
+///
+/// Usage example:
+/// ```ignore
+/// const TOFF: Toff = Toff::new(3); // Out of range value fails to compile
+///
+/// let config = TMC2209_ConfigBuilder::new()
+///     .rms_current(700)
+///     .microsteps(Microsteps::M16)
+///     .chopper_mode(ChopperMode::StealthChop)
+///     .toff(TOFF)
+///     .build()?;
+/// ```
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TMC2209_ConfigBuilder {
+    pub(crate) config: TMC2209_Config,
+}
+
+/// Microstep resolution (CHOPCONF.MRES)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Microsteps {
+    Fullstep,
+    M2,
+    M4,
+    M8,
+    M16,
+    M32,
+    M64,
+    M128,
+    M256,
+}
+
+/// Comparator blank time (CHOPCONF.TBL) in clock cycles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BlankTime {
+    Clk16,
+    Clk24,
+    Clk32,
+    Clk40,
+}
+
+/// stealthChop PWM frequency (PWMCONF.PWM_FREQ) as part of fclk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PwmFreq {
+    /// 2/1024 fclk
+    Div1024,
+    /// 2/683 fclk
+    Div683,
+    /// 2/512 fclk
+    Div512,
+    /// 2/410 fclk
+    Div410,
+}
+
+/// Standstill option when hold current is zero (PWMCONF.FREEWHEEL)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FreewheelMode {
+    Normal,
+    Freewheeling,
+    /// Coil shorted using LS drivers
+    ShortViaLowSide,
+    /// Coil shorted using HS drivers
+    ShortViaHighSide,
+}
+
+/// Slow decay time (CHOPCONF.TOFF), 0..=15. 0 disables the driver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Toff(pub(crate) u8);
+
+/// Hysteresis start value (CHOPCONF.HSTRT), 0..=7
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Hstrt(pub(crate) u8);
+
+/// Hysteresis end value (CHOPCONF.HEND), 0..=15
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Hend(pub(crate) u8);
+
+/// Hold current reduction delay (IHOLD_IRUN.IHOLDDELAY), 0..=15
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct IholdDelay(pub(crate) u8);
+
+/// StallGuard threshold (SGTHRS), 0..=255
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Sgthrs(pub(crate) u8);
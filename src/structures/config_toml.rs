@@ -0,0 +1,12 @@
+use std::string::String;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Why TOML config can not be read
+pub enum ConfigTomlError {
+    /// Line is neither a `key = value` pair nor a comment
+    Syntax { line: usize },
+    /// Key is not a field of `TMC2209_Config`
+    UnknownKey { line: usize, key: String },
+    /// Value can not be parsed or does not fit into the field
+    InvalidValue { line: usize, key: String },
+}
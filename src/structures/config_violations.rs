@@ -0,0 +1,43 @@
+use crate::structures::chip_variant::ChipVariant;
+
+/// Biggest number of violations one config can have
+pub const MAX_CONFIG_VIOLATIONS: usize = 48;
+
+/// One problem found in `TMC2209_Config` by `validate()`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigViolation {
+    /// Value does not fit into register field
+    OutOfRange {
+        field: &'static str,
+        value: u32,
+        min: u32,
+        max: u32,
+    },
+    /// Microsteps is not a power of two up to 256 (or 0 for fullstep)
+    InvalidMicrosteps(u32),
+    /// Float value is negative, zero, NaN or out of range
+    InvalidValue { field: &'static str },
+    /// HSTRT + HEND gives more than 16 hysteresis (see tmc2209 datasheet,
+    /// page 51)
+    HysteresisTooHigh {
+        hysteresis_start: u32,
+        hysteresis_end: u32,
+    },
+    /// TOFF = 1 needs blank time of at least 32 clocks (TBL >= 2)
+    ToffRequiresLongerBlankTime,
+    /// Field controls a feature the chip does not have (for example
+    /// StallGuard on TMC2208)
+    UnsupportedByChip {
+        field: &'static str,
+        chip: ChipVariant,
+    },
+}
+
+/// All problems found in `TMC2209_Config`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ConfigViolations {
+    pub(crate) items: [Option<ConfigViolation>; MAX_CONFIG_VIOLATIONS],
+    pub(crate) len: usize,
+}
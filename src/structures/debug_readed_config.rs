@@ -1,5 +1,6 @@
 #[allow(non_camel_case_types)]
 /// Config completely readed from driver (for debug)
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct TMC2209_DebugConfig {
     pub microsteps: u32,
     pub interpolation: bool,
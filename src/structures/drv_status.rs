@@ -0,0 +1,44 @@
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// Driver status flags from DRV_STATUS (see tmc2209 datasheet, page 56)
+pub struct TMC2209_DrvStatus {
+    /// Overtemperature prewarning
+    pub otpw: bool,
+    /// Overtemperature, driver is shut down
+    pub ot: bool,
+    /// Short to ground, phase A/B
+    pub s2ga: bool,
+    pub s2gb: bool,
+    /// Low side short, phase A/B
+    pub s2vsa: bool,
+    pub s2vsb: bool,
+    /// Open load, phase A/B
+    pub ola: bool,
+    pub olb: bool,
+    /// Temperature thresholds exceeded
+    pub t120: bool,
+    pub t143: bool,
+    pub t150: bool,
+    pub t157: bool,
+    /// Actual current scale
+    pub cs_actual: u8,
+    /// Driver works in stealthChop
+    pub stealth: bool,
+    /// Standstill
+    pub stst: bool,
+}
+
+/// Chip temperature range reported by DRV_STATUS
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TemperatureLevel {
+    Normal,
+    /// t120 or overtemperature prewarning
+    Above120,
+    Above143,
+    Above150,
+    Above157,
+    /// Overtemperature shutdown
+    Overtemperature,
+}
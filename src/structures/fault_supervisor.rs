@@ -0,0 +1,93 @@
+/// Number of faults kept in [`FaultSupervisor`] history
+pub const FAULT_HISTORY_LEN: usize = 16;
+
+/// Kind of driver fault
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FaultClass {
+    /// DRV_STATUS s2ga/s2gb
+    ShortToGround,
+    /// DRV_STATUS s2vsa/s2vsb (low side short)
+    ShortToSupply,
+    /// DRV_STATUS ola/olb
+    OpenLoad,
+    /// GSTAT drv_err (driver was shut down by short or overtemperature)
+    DriverError,
+}
+
+/// Reaction to a fault class
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FaultPolicy {
+    /// Only record the fault in history
+    LogOnly,
+    /// Disable power stage (TOFF = 0) until `resume()`
+    DisableOutput,
+    /// Disable power stage and enable it again after `cool_down_ms`.
+    /// After `max_retries` the fault is latched
+    Retry { cool_down_ms: u32, max_retries: u8 },
+    /// Disable power stage until `clear_latch()`
+    Latch,
+}
+
+/// Policy for every fault class
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FaultPolicies {
+    pub short_to_ground: FaultPolicy,
+    pub short_to_supply: FaultPolicy,
+    pub open_load: FaultPolicy,
+    pub driver_error: FaultPolicy,
+}
+
+/// What supervisor did with a fault
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FaultAction {
+    Logged,
+    OutputDisabled,
+    /// Output will be enabled again at `retry_at_ms`
+    RetryScheduled {
+        attempt: u8,
+        retry_at_ms: u64,
+    },
+    Latched,
+}
+
+/// One entry of fault history
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FaultRecord {
+    pub class: FaultClass,
+    /// Caller provided time of `poll()`, ms
+    pub timestamp_ms: u64,
+    pub action: FaultAction,
+}
+
+/// Power stage state controlled by supervisor
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FaultStatus {
+    Running,
+    OutputDisabled { class: FaultClass },
+    RetryPending { class: FaultClass, retry_at_ms: u64 },
+    Latched { class: FaultClass },
+}
+
+/// Watches DRV_STATUS and GSTAT for shorts, open load and driver errors and
+/// reacts according to [`FaultPolicies`]. Call [`FaultSupervisor::poll`]
+/// periodically
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FaultSupervisor {
+    pub(crate) policies: FaultPolicies,
+    pub(crate) status: FaultStatus,
+    // Fault classes active at the previous poll, to record only new faults
+    pub(crate) active: [bool; 4],
+    pub(crate) retries: [u8; 4],
+    // TOFF to restore when output is enabled again
+    pub(crate) toff: u32,
+    pub(crate) history: [Option<FaultRecord>; FAULT_HISTORY_LEN],
+    pub(crate) history_next: usize,
+    pub(crate) history_len: usize,
+}
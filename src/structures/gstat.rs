@@ -0,0 +1,12 @@
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// Global status flags from GSTAT, cleared by writing them back
+pub struct TMC2209_Gstat {
+    /// Driver was reset since the last clear
+    pub reset: bool,
+    /// Driver was shut down by overtemperature or short circuit
+    pub drv_err: bool,
+    /// Charge pump undervoltage
+    pub uv_cp: bool,
+}
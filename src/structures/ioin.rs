@@ -0,0 +1,18 @@
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// Pin levels and silicon version from IOIN register
+pub struct TMC2209_IoinState {
+    pub enn: bool,
+    pub ms1: bool,
+    pub ms2: bool,
+    pub diag: bool,
+    pub pdn_uart: bool,
+    pub step: bool,
+    /// SPREAD pin. On TMC2208/TMC2225 this bit is SEL_A
+    /// (1 - TMC220x, 0 - TMC222x)
+    pub spread_en: bool,
+    pub dir: bool,
+    /// VERSION field, 0x21 for TMC2209, 0x20 for TMC2208
+    pub version: u8,
+}
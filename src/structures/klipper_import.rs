@@ -0,0 +1,38 @@
+use crate::structures::{
+    base_config::TMC2209_BaseConfig, config::TMC2209_Config,
+};
+use std::string::String;
+use std::vec::Vec;
+
+/// Values needed to import Klipper config which are not part of
+/// `[tmc2209 ...]` sections
+pub struct KlipperImportOptions {
+    /// Distance travelled per full rotation (same unit as velocities in the
+    /// config, usually mm). Overrides `rotation_distance` of `[stepper_...]`
+    /// sections
+    pub rotation_distance: Option<f32>,
+
+    /// Driver clock frequency in Hz
+    pub fclk: f32,
+}
+
+/// Config imported from one `[tmc2209 stepper_x]` section
+pub struct KlipperTmc2209Import {
+    /// Name of stepper (e.g. `stepper_x`)
+    pub stepper: String,
+    pub base_config: TMC2209_BaseConfig,
+    pub config: TMC2209_Config,
+    /// Keys of the section that have no equivalent in this library
+    pub unsupported_keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Why Klipper config can not be imported
+pub enum KlipperImportError {
+    /// Line is neither a section header, a `key: value` pair nor a comment
+    Syntax { line: usize },
+    /// Value can not be parsed or is out of range
+    InvalidValue { line: usize, key: String },
+    /// Velocity threshold is set, but rotation distance is unknown
+    MissingRotationDistance { stepper: String },
+}
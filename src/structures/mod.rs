@@ -1,5 +1,28 @@
+pub mod apply_config_error;
 pub mod base_config;
+pub mod bus_scan;
+pub mod chip_identity;
+pub mod chip_variant;
+pub mod chopper_mode;
 pub mod config;
+pub mod config_builder;
+#[cfg(feature = "std")]
+pub mod config_toml;
+pub mod config_violations;
 pub mod debug_readed_config;
+pub mod drv_status;
+pub mod fault_supervisor;
+pub mod gstat;
+pub mod ioin;
+#[cfg(feature = "std")]
+pub mod klipper_import;
+pub mod motion_monitor;
+pub mod motor_current;
+pub mod otp;
+pub mod persisted_config;
+pub mod register_view;
 pub mod registers_collection;
 pub mod saved_config;
+pub mod stealthchop_autotune;
+pub mod telemetry;
+pub mod thermal_supervisor;
@@ -0,0 +1,51 @@
+/// Detection thresholds of [`MotionMonitor`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MotionMonitorOptions {
+    /// Consecutive polls a condition must hold (or be gone) before event
+    pub debounce: u8,
+    /// Open load flags are only checked in this velocity window, rpm.
+    /// They are false positive at standstill and at high velocity, where
+    /// back EMF reaches supply voltage
+    pub open_load_min_rpm: f32,
+    pub open_load_max_rpm: f32,
+    /// StallGuard is ignored below this velocity, rpm
+    pub stall_min_rpm: f32,
+}
+
+/// Change of motor state reported by [`MotionMonitor::poll`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MotionEvent {
+    /// Open load on one or both phases
+    Disconnected {
+        phase_a: bool,
+        phase_b: bool,
+    },
+    Reconnected,
+    /// SG_RESULT fell to the stall level (2 * SGTHRS, same as DIAG)
+    Stalled {
+        sg_result: u16,
+        velocity_rpm: f32,
+    },
+    StallCleared,
+}
+
+/// Debounced state of one condition
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Debounced {
+    pub(crate) active: bool,
+    pub(crate) count: u8,
+}
+
+/// Detects disconnected motor and mechanical stall while the motor runs
+/// (for example under VACTUAL) from DRV_STATUS open load flags, SG_RESULT
+/// and TSTEP. Call [`MotionMonitor::poll`] periodically
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MotionMonitor {
+    pub(crate) options: MotionMonitorOptions,
+    pub(crate) disconnected: Debounced,
+    pub(crate) stalled: Debounced,
+}
@@ -0,0 +1,24 @@
+/// Motor currents set in driver, after quantisation to current scale
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MotorCurrents {
+    /// Run current, mA RMS
+    pub run_current: u16,
+    /// Run current, mA peak
+    pub peak_current: u16,
+    /// Hold current, mA RMS
+    pub hold_current: u16,
+}
+
+/// How current is reduced at standstill (see tmc2209 datasheet, page 27)
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct StandstillReduction {
+    /// Current at standstill, mA RMS
+    pub hold_current: u16,
+    /// Time of smooth current ramp down to hold current, ms (IHOLDDELAY)
+    pub ramp_time_ms: f32,
+    /// Delay after standstill detection before current reduction, s
+    /// (TPOWERDOWN, up to ~5.6s with internal clock)
+    pub power_down_delay_s: f32,
+}
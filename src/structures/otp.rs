@@ -0,0 +1,89 @@
+/// Value of OTP_PROG OTPMAGIC field, programming is ignored without it
+pub const OTP_MAGIC: u8 = 0xBD;
+
+/// One OTP bit (byte 0..2, bit 0..7). Bits can only be programmed from 0 to
+/// 1, once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OtpBit {
+    pub(crate) byte: u8,
+    pub(crate) bit: u8,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// OTP_READ contents
+pub struct TMC2209_Otp {
+    pub bytes: [u8; 3],
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// Power-up register defaults set by OTP (used without UART, or until
+/// the first config write)
+///
+/// With `en_spreadcycle` the PWM related bits of OTP1/OTP2 set CHOPCONF
+/// defaults instead (see datasheet, OTP memory), then `pwm_*` and
+/// `tpwmthrs` are not meaningful
+pub struct TMC2209_OtpPowerUpConfig {
+    pub fclktrim: u8,
+    pub ottrim: u8,
+    pub internal_rsense: bool,
+    pub blank_time: u32,
+    pub pwm_grad: u8,
+    pub pwm_autograd: bool,
+    pub tpwmthrs: u32,
+    pub pwm_ofs: u8,
+    pub pwm_reg: u8,
+    pub pwm_freq: u8,
+    pub ihold_delay: u8,
+    /// IHOLD at power-up (0..31)
+    pub ihold: u8,
+    pub en_spreadcycle: bool,
+}
+
+/// Explicit confirmation that one OTP bit is going to be burned. Created
+/// with [`OtpConfirmToken::new`] and used up by programming
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OtpConfirmToken {
+    pub(crate) bit: OtpBit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OtpMode {
+    /// Read OTP and report the result, nothing is written
+    DryRun,
+    /// Burn the bit. Can not be undone
+    Program,
+}
+
+/// Result of OTP programming (or what it would be in dry run)
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OtpProgramReport {
+    pub before: TMC2209_Otp,
+    pub after: TMC2209_Otp,
+    /// Power-up config implied by `after`
+    pub power_up_config: TMC2209_OtpPowerUpConfig,
+    /// OTP_PROG was written
+    pub programmed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OtpError {
+    /// Byte or bit number out of range
+    InvalidBit,
+    /// Confirm token was not made with OTP_MAGIC
+    InvalidMagic,
+    /// Confirm token was made for another bit
+    WrongToken,
+    /// Bit is not set after programming
+    VerifyFailed,
+    /// UART communication failed
+    Uart,
+}
@@ -0,0 +1,41 @@
+use crate::structures::{
+    apply_config_error::ApplyConfigError, base_config::TMC2209_BaseConfig,
+    saved_config::TMC2209_SavedConfig,
+};
+use embedded_storage::nor_flash::NorFlashErrorKind;
+
+#[allow(non_camel_case_types)]
+#[derive(Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// Base config and saved config packed together, so the motor tuning can be
+/// stored in MCU flash/EEPROM and restored at boot without a host
+///
+/// Encoded as a fixed-size, versioned, CRC-protected binary block
+/// (see [`TMC2209_PersistedConfig::ENCODED_SIZE`])
+pub struct TMC2209_PersistedConfig {
+    pub base_config: TMC2209_BaseConfig,
+    pub saved_config: TMC2209_SavedConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Why persisted config can not be stored or loaded
+// Violations are stored inline, there is no allocator in no_std
+#[allow(clippy::large_enum_variant)]
+pub enum PersistedConfigError {
+    /// Storage does not contain a persisted config (erased or foreign data)
+    BadMagic,
+    /// Stored block was written by a newer (or unknown) encoding version
+    UnsupportedVersion(u8),
+    /// Stored payload length does not fit into the block
+    BadLength,
+    /// Stored block is corrupted
+    CrcMismatch,
+    /// Offset is not aligned to the flash erase size, or the block size is
+    /// not a multiple of the flash write size
+    NotAligned,
+    /// Flash driver returned an error
+    Flash(NorFlashErrorKind),
+    /// Config was loaded, but the driver refused it or it could not be
+    /// sent (see `apply_config()`)
+    Apply(ApplyConfigError),
+}
@@ -0,0 +1,36 @@
+/// Biggest number of changed fields one diff can hold
+pub const MAX_FIELD_CHANGES: usize = 32;
+
+/// Register value with its address. Formats as named bitfields with
+/// decoded meaning (`core::fmt::Display`, and `defmt::Format` with the
+/// `defmt` feature)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterView {
+    pub address: u8,
+    pub value: u32,
+}
+
+/// Value of one field in a diff
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldValue {
+    Bool(bool),
+    Uint(u32),
+    Int(i32),
+}
+
+/// Field with different values in two snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub old: FieldValue,
+    pub new: FieldValue,
+}
+
+/// Changed fields of two register values or two `TMC2209_DebugConfig`s,
+/// formats as `FIELD: old -> new` lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldChanges {
+    pub(crate) items: [Option<FieldChange>; MAX_FIELD_CHANGES],
+    pub(crate) len: usize,
+}
@@ -1,5 +1,7 @@
 #![allow(non_camel_case_types)]
-/// Which registers changed by user config
+/// Which registers changed by user config (or are known in saved config)
+#[derive(Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct TMC2209_ConfigRegistersChangesDetected {
     pub gconf: bool,
     pub chopconf: bool,
@@ -1,9 +1,13 @@
+use crate::structures::registers_collection::TMC2209_ConfigRegistersChangesDetected;
+
 #[allow(non_camel_case_types)]
 #[derive(Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 /// When apply_config() called values of user config will be
 /// saved in this struct in case of succes send
 pub struct TMC2209_SavedConfig {
     pub rms_current: u16,
+    pub hold_current: u16,
     pub ihold_delay: u8,
     pub microsteps: u32,
     pub interpolation: bool,
@@ -44,4 +48,8 @@ pub struct TMC2209_SavedConfig {
     pub fclktrim: u8,
     pub ottrim: u8,
     pub shaft: bool,
+    /// Registers whose fields were read from or written to the driver.
+    /// Fields of other registers keep [`TMC2209_SavedConfig::new`] values
+    /// and are not restored by `TMC2209_Config::from_saved_config()`
+    pub known_registers: TMC2209_ConfigRegistersChangesDetected,
 }
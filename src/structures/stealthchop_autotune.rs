@@ -0,0 +1,50 @@
+/// Parameters of stealthChop automatic tuning
+/// (see tmc2209 datasheet, page 37, "Automatic Tuning")
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct StealthChopAutotuneOptions {
+    /// AT#1 minimal standstill time at run current, ms (datasheet: >130ms)
+    pub standstill_time_ms: u32,
+    /// AT#2 velocity (VACTUAL value). Should be medium velocity, in
+    /// stealthChop range and above coolStep/fullstep thresholds
+    pub velocity: i32,
+    /// Time between PWM_SCALE/PWM_AUTO reads, ms
+    pub poll_interval_ms: u32,
+    /// Number of following reads with unchanged PWM_AUTO to treat a phase
+    /// as converged
+    pub stable_reads: u8,
+    /// PWM_SCALE_AUTO deviation treated as settled in AT#2
+    pub scale_auto_tolerance: u16,
+    /// Time limit for each phase, ms
+    pub timeout_ms: u32,
+}
+
+/// Values learned by automatic tuning. `pwm_ofs` and `pwm_grad` can be
+/// stored in `TMC2209_Config`, so next boot starts from tuned values
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct StealthChopAutotuneResult {
+    /// PWM_OFS_AUTO
+    pub pwm_ofs: u8,
+    /// PWM_GRAD_AUTO
+    pub pwm_grad: u8,
+    /// PWM_SCALE_SUM at the end of AT#2
+    pub pwm_scale_sum: u8,
+    /// PWM_SCALE_AUTO at the end of AT#2
+    pub pwm_scale_auto: i16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// Why automatic tuning failed
+pub enum StealthChopAutotuneError {
+    /// Driver is not configured for stealthChop with pwm_autoscale and
+    /// pwm_autograd
+    NotConfigured,
+    /// PWM_OFS_AUTO did not settle at standstill (AT#1)
+    StandstillTimeout,
+    /// PWM_GRAD_AUTO/PWM_SCALE_AUTO did not settle during motion (AT#2)
+    MotionTimeout,
+    /// UART communication failed
+    Uart,
+}
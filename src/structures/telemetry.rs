@@ -0,0 +1,53 @@
+use crate::structures::ioin::TMC2209_IoinState;
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// Actual motor phase currents from MSCURACT, -255..255 of current scale
+pub struct TMC2209_PhaseCurrents {
+    pub cur_a: i16,
+    pub cur_b: i16,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// stealthChop PWM amplitude from PWM_SCALE
+pub struct TMC2209_PwmScale {
+    /// PWM_SCALE_SUM, actual PWM duty cycle (0..255)
+    pub sum: u8,
+    /// PWM_SCALE_AUTO, automatic amplitude regulation result (-255..255)
+    pub auto: i16,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// Automatically determined stealthChop values from PWM_AUTO
+pub struct TMC2209_PwmAuto {
+    /// PWM_OFS_AUTO
+    pub ofs: u8,
+    /// PWM_GRAD_AUTO
+    pub grad: u8,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+/// Read-only driver registers, read together (for live monitoring)
+pub struct TMC2209_Telemetry {
+    /// Caller provided time of reading (any unit)
+    pub timestamp: u64,
+    /// TSTEP, time between 1/256 microsteps in 1/fclk
+    pub tstep: u32,
+    /// Actual velocity calculated from TSTEP, rpm (0 at standstill)
+    pub velocity_rpm: f32,
+    /// MSCNT, microstep table position (0..1023)
+    pub mscnt: u16,
+    pub phase_currents: TMC2209_PhaseCurrents,
+    pub pwm_scale: TMC2209_PwmScale,
+    pub pwm_auto: TMC2209_PwmAuto,
+    /// SG_RESULT, None for chips without StallGuard
+    pub sg_result: Option<u16>,
+    pub ioin: TMC2209_IoinState,
+}
@@ -0,0 +1,49 @@
+use crate::structures::drv_status::TemperatureLevel;
+
+/// When and how much run current is reduced by [`ThermalSupervisor`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ThermalSupervisorOptions {
+    /// Run current is reduced at this level and above
+    pub derate_level: TemperatureLevel,
+    /// Run current is restored at this level and below. Must be lower than
+    /// `derate_level`, the gap is hysteresis
+    pub restore_level: TemperatureLevel,
+    /// Current change per poll, mA RMS
+    pub step: u16,
+    /// Run current is never reduced below this, mA RMS
+    pub min_current: u16,
+}
+
+/// What [`ThermalSupervisor::poll`] did
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ThermalEvent {
+    /// Run current reduced
+    Derated {
+        level: TemperatureLevel,
+        run_current: u16,
+    },
+    /// Run current increased, but still below nominal
+    Restoring {
+        level: TemperatureLevel,
+        run_current: u16,
+    },
+    /// Run current is back at nominal
+    Restored { run_current: u16 },
+}
+
+/// Reduces run current step by step while driver is hot, and restores it
+/// as the chip cools down. Call [`ThermalSupervisor::poll`] periodically
+/// (for example once a second)
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ThermalSupervisor {
+    pub(crate) options: ThermalSupervisorOptions,
+    pub(crate) nominal_run_current: u16,
+    pub(crate) nominal_hold_current: u16,
+    pub(crate) run_current: u16,
+    pub(crate) level: TemperatureLevel,
+    // Currents must be sent even if run current did not change
+    pub(crate) pending: bool,
+}
@@ -1,3 +1,5 @@
+use core::f32::consts::SQRT_2;
+
 pub fn microsteps_to_mres(microsteps: u32) -> u32 {
     match microsteps {
         256 => 0,
@@ -29,11 +31,181 @@ pub fn mres_to_microsteps(mres: u32) -> u32 {
 }
 
 pub fn irun_to_rms_current(irun: u8, vsense: bool, r_sense: f32) -> u16 {
+    (cs_to_rms_current(irun, vsense, r_sense, 1.0) + 0.5) as u16
+}
+
+/// Inverse of [`irun_to_rms_current`]. Rounds to nearest, so current read
+/// back from saved config gives the same IRUN
+pub fn rms_current_to_irun(rms_current: u16, vsense: bool, r_sense: f32) -> u8 {
+    rms_current_to_cs(rms_current, vsense, r_sense, 1.0)
+}
+
+/// RMS current (mA) of current scale `cs` (IRUN/IHOLD, 0..31).
+/// `analog_scale` is VREF scaling of full scale current (1.0 if
+/// i_scale_analog is off) (see tmc2209 datasheet, page 48)
+pub fn cs_to_rms_current(
+    cs: u8,
+    vsense: bool,
+    r_sense: f32,
+    analog_scale: f32,
+) -> f32 {
+    (cs.min(31) + 1) as f32 / 32.0
+        * full_scale_rms_current(vsense, r_sense, analog_scale)
+}
+
+/// Nearest current scale (0..31) for RMS current in mA
+pub fn rms_current_to_cs(
+    rms_current: u16,
+    vsense: bool,
+    r_sense: f32,
+    analog_scale: f32,
+) -> u8 {
+    let cs = rms_current as f32 * 32.0
+        / full_scale_rms_current(vsense, r_sense, analog_scale)
+        - 1.0;
+    (cs + 0.5).clamp(0.0, 31.0) as u8
+}
+
+/// Sense resistor and VREF scaling actually used for current calculations
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CurrentScaling {
+    /// External sense resistor, or equivalent of internal sense resistors
+    pub r_sense: f32,
+    /// Full scale current multiplier from VREF, 1.0 if i_scale_analog is off
+    pub analog_scale: f32,
+}
+
+// VREF giving full scale current with i_scale_analog
+const VREF_FULL_SCALE: f32 = 2.5;
+
+// Full scale peak current (CS = 31, vsense = 0) multiplied by RREF, with
+// internal sense resistors. Approximation of datasheet table (chapter 9.1)
+const INTERNAL_RSENSE_FULL_SCALE: f32 = 13_300.0;
+
+/// Full scale current multiplier for VREF voltage (i_scale_analog = 1).
+/// VREF above 2.5V does not increase current
+pub fn analog_current_scale(vref: f32) -> f32 {
+    if vref.is_nan() {
+        return 1.0;
+    }
+    (vref / VREF_FULL_SCALE).clamp(0.0, 1.0)
+}
+
+// RREF range the internal sense resistor table covers
+const RREF_MIN: f32 = 5_600.0;
+const RREF_MAX: f32 = 22_000.0;
+
+/// Sense resistor giving the same current as internal sense resistors
+/// (internal_rsense = 1) with `rref` Ohm reference resistor on VREF pin.
+/// None if `rref` is out of datasheet range (5.6k..22k Ohm)
+pub fn internal_rsense_equivalent(rref: f32) -> Option<f32> {
+    if !(RREF_MIN..=RREF_MAX).contains(&rref) {
+        return None;
+    }
+    Some(0.325 * rref / INTERNAL_RSENSE_FULL_SCALE - 0.02)
+}
+
+// RMS current at CS = 31, mA
+fn full_scale_rms_current(
+    vsense: bool,
+    r_sense: f32,
+    analog_scale: f32,
+) -> f32 {
     let vsense_val = if vsense { 0.180 } else { 0.325 };
-    ((irun + 1) as f32 / 32.0 * vsense_val / (r_sense + 0.02) / 1.41421
-        * 1000.0) as u16
+    vsense_val * analog_scale / (r_sense + 0.02) / SQRT_2 * 1000.0
 }
 
+/// Where requested current is in relation to what the driver can do
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CurrentRange {
+    InRange,
+    /// Less than CS = 0 with vsense = 1, lowest current is used
+    BelowMinimum,
+    /// More than CS = 31 with vsense = 0, highest current is used
+    AboveMaximum,
+}
+
+/// Current scale and vsense giving closest current to the requested one
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CurrentSolution {
+    pub cs: u8,
+    pub vsense: bool,
+    /// Current actually set, mA RMS
+    pub rms_current: u16,
+    /// `rms_current` minus requested current, mA
+    pub error: i32,
+    /// Error relative to requested current, %
+    pub error_percent: f32,
+    pub range: CurrentRange,
+}
+
+// Candidates with error difference within this part of requested current
+// are treated as equal, higher CS wins then
+const CURRENT_ERROR_TIE: f32 = 0.01;
+
+/// Find CS/vsense pair for RMS current (mA). Both vsense ranges are
+/// evaluated, the one with smaller error wins. When errors are close,
+/// higher CS is preferred for better microstep resolution. Requests out of
+/// driver range saturate (see [`CurrentSolution::range`])
+pub fn solve_rms_current(
+    rms_current: u16,
+    r_sense: f32,
+    analog_scale: f32,
+) -> CurrentSolution {
+    let requested = rms_current as f32;
+    let candidate = |vsense| {
+        let cs = rms_current_to_cs(rms_current, vsense, r_sense, analog_scale);
+        let achieved = cs_to_rms_current(cs, vsense, r_sense, analog_scale);
+        (cs, vsense, achieved, (achieved - requested).abs())
+    };
+    let high_sensitivity = candidate(true);
+    let low_sensitivity = candidate(false);
+
+    let tie = requested * CURRENT_ERROR_TIE;
+    let (cs, vsense, achieved, _) =
+        if (high_sensitivity.3 - low_sensitivity.3).abs() <= tie {
+            // Same sense voltage range gives higher CS with vsense = 1
+            if high_sensitivity.0 >= low_sensitivity.0 {
+                high_sensitivity
+            } else {
+                low_sensitivity
+            }
+        } else if high_sensitivity.3 < low_sensitivity.3 {
+            high_sensitivity
+        } else {
+            low_sensitivity
+        };
+
+    let minimum = cs_to_rms_current(0, true, r_sense, analog_scale);
+    let maximum = cs_to_rms_current(31, false, r_sense, analog_scale);
+    let range = if requested < minimum {
+        CurrentRange::BelowMinimum
+    } else if requested > maximum {
+        CurrentRange::AboveMaximum
+    } else {
+        CurrentRange::InRange
+    };
+
+    let rms_current = (achieved + 0.5) as u16;
+    let error = rms_current as i32 - requested as i32;
+    CurrentSolution {
+        cs,
+        vsense,
+        rms_current,
+        error,
+        error_percent: if requested > 0.0 {
+            error as f32 / requested * 100.0
+        } else {
+            0.0
+        },
+        range,
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct RmsCurrentToIholdIrunVsenseOutput {
     pub ihold: u8,
     pub irun: u8,
@@ -46,32 +218,246 @@ pub fn rms_current_to_ihold_irun_vsense(
     rsense: f32,
     hold_multiplier: f32,
 ) -> RmsCurrentToIholdIrunVsenseOutput {
-    let mut output = RmsCurrentToIholdIrunVsenseOutput {
-        ihold: 0,
-        irun: 0,
-        vsense: false,
-    };
+    let solution = solve_rms_current(rms_current_mA, rsense, 1.0);
+    RmsCurrentToIholdIrunVsenseOutput {
+        ihold: (solution.cs as f32 * hold_multiplier) as u8,
+        irun: solution.cs,
+        vsense: solution.vsense,
+    }
+}
 
-    let mut cs: u8 = (32.0 * 1.41421 * rms_current_mA as f32 / 1000.0
-        * (rsense + 0.02)
-        / 0.325
-        - 1.0) as u8;
-    // If Current Scale is too low, turn on high sensitivity rsense and calculate again
-    if cs < 16 {
-        output.vsense = true;
-        cs = (32.0 * 1.41421 * rms_current_mA as f32 / 1000.0 * (rsense + 0.02)
-            / 0.180
-            - 1.0) as u8;
+/// Convert velocity into TSTEP value (time between two 1/256 microsteps in
+/// 1/fclk units), as used by TPWMTHRS and TCOOLTHRS.
+/// `velocity` and `rotation_distance` must use the same length unit
+/// (e.g. mm/s and mm). Zero velocity gives the maximum TSTEP value
+pub fn velocity_to_tstep(
+    velocity: f32,
+    rotation_distance: f32,
+    full_steps_per_rotation: u32,
+    fclk: f32,
+) -> u32 {
+    rps_to_tstep(velocity / rotation_distance, full_steps_per_rotation, fclk)
+}
+
+/// Convert rotations per minute into TSTEP value
+pub fn rpm_to_tstep(rpm: f32, full_steps_per_rotation: u32, fclk: f32) -> u32 {
+    rps_to_tstep(rpm / 60.0, full_steps_per_rotation, fclk)
+}
+
+/// Convert TSTEP value into rotations per minute
+pub fn tstep_to_rpm(
+    tstep: u32,
+    full_steps_per_rotation: u32,
+    fclk: f32,
+) -> f32 {
+    if tstep == 0 || tstep >= TSTEP_MAX {
+        return 0.0;
+    }
+    fclk / (tstep as f32 * full_steps_per_rotation as f32 * 256.0) * 60.0
+}
+
+/// Biggest value of TSTEP and its thresholds (20 bit)
+pub const TSTEP_MAX: u32 = 0xFFFFF;
+
+fn rps_to_tstep(rps: f32, full_steps_per_rotation: u32, fclk: f32) -> u32 {
+    if rps <= 0.0 || rps.is_nan() {
+        return TSTEP_MAX;
+    }
+    let tstep = fclk / (rps * full_steps_per_rotation as f32 * 256.0) + 0.5;
+    if tstep >= TSTEP_MAX as f32 {
+        TSTEP_MAX
     } else {
-        // If cs >= 16, turn off high_sense_r
-        output.vsense = false;
+        tstep as u32
+    }
+}
+
+/// Motor electrical parameters (from motor datasheet) used to calculate
+/// spreadCycle chopper settings and stealthChop start values
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MotorParameters {
+    /// Motor supply voltage, V
+    pub supply_voltage: f32,
+    /// Phase inductance, mH
+    pub inductance_mh: f32,
+    /// Phase resistance, Ohm
+    pub resistance: f32,
+    /// Target RMS current, mA
+    pub rms_current: u16,
+    /// Back EMF constant, V/(rad/s). Can be estimated as holding torque
+    /// (Nm) / (2 * rated RMS current (A)). None if unknown
+    pub back_emf_constant: Option<f32>,
+}
+
+/// Recommended spreadCycle settings, register values as used in
+/// `TMC2209_Config`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SpreadCycleChopperSettings {
+    pub toff: u32,
+    pub blank_time: u32,
+    pub hysteresis_start: u32,
+    pub hysteresis_end: u32,
+    /// Estimated chopper frequency, Hz
+    pub chopper_frequency: f32,
+    /// Resulting hysteresis (HSTRT + HEND) as coil current, mA
+    pub hysteresis_current: u16,
+}
+
+// Chopper frequency aimed at, above audible range for most motors
+const TARGET_CHOPPER_FREQUENCY: f32 = 25_000.0;
+// TBL = 2, 32 clocks. Fits most motors (see datasheet, chapter 7.1)
+const BLANK_TIME: u32 = 2;
+const BLANK_TIME_CLOCKS: f32 = 32.0;
+
+/// Calculate spreadCycle TOFF, TBL, HSTRT and HEND following datasheet
+/// procedure (chapter 7.1): TOFF gives target chopper frequency, hysteresis
+/// covers current ripple during blank time and slow decay phases.
+/// Real coil current depends on current scale, so `r_sense` is needed.
+/// Returns Err if motor parameters are not positive
+pub fn spreadcycle_chopper_settings(
+    motor: &MotorParameters,
+    r_sense: f32,
+    fclk: f32,
+) -> Result<SpreadCycleChopperSettings, ()> {
+    if !(motor.supply_voltage > 0.0
+        && motor.inductance_mh > 0.0
+        && motor.resistance >= 0.0
+        && motor.rms_current > 0
+        && r_sense > 0.0
+        && fclk > 0.0)
+    {
+        return Err(());
+    }
+
+    // Slow decay takes about half of the chopper period, which has two
+    // slow decay phases: t_off = 1 / (4 * f_chop)
+    let toff_clocks = fclk / (4.0 * TARGET_CHOPPER_FREQUENCY);
+    let toff = (((toff_clocks - 24.0) / 32.0 + 0.5) as u32).clamp(2, 15);
+    let t_off = (24.0 + 32.0 * toff as f32) / fclk;
+    let t_blank = BLANK_TIME_CLOCKS / fclk;
+
+    // Peak current which driver will really use
+    let cs = rms_current_to_ihold_irun_vsense(motor.rms_current, r_sense, 1.0);
+    let peak_current = irun_to_rms_current(cs.irun, cs.vsense, r_sense) as f32
+        / 1000.0
+        * SQRT_2;
+    if peak_current <= 0.0 {
+        return Err(());
     }
 
-    if cs > 31 {
-        cs = 31;
+    let inductance = motor.inductance_mh / 1000.0;
+    let di_blank = motor.supply_voltage * t_blank / inductance;
+    let di_slow_decay =
+        motor.resistance * peak_current * 2.0 * t_off / inductance;
+
+    // Hysteresis is set in 1/248 of current peak.
+    // Effective HSTRT is 1..=8, effective HEND is -3..=12, sum up to 16
+    let hysteresis =
+        ((di_blank + di_slow_decay) / peak_current * 248.0 + 0.999) as i32;
+    let hysteresis = hysteresis.clamp(-2, 16);
+    let hstrt = hysteresis.clamp(1, 8);
+    let hend = hysteresis - hstrt;
+
+    Ok(SpreadCycleChopperSettings {
+        toff,
+        blank_time: BLANK_TIME,
+        hysteresis_start: (hstrt - 1) as u32,
+        hysteresis_end: (hend + 3) as u32,
+        chopper_frequency: 1.0 / (4.0 * t_off),
+        hysteresis_current: (hysteresis.max(0) as f32 / 248.0
+            * peak_current
+            * 1000.0
+            + 0.5) as u16,
+    })
+}
+
+/// stealthChop PWM start values for automatic tuning, register values as
+/// used in `TMC2209_Config`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct StealthChopPwmSettings {
+    pub pwm_ofs: u8,
+    pub pwm_grad: u8,
+}
+
+// Datasheet start value of PWM_GRAD, used when back EMF is unknown
+const PWM_GRAD_DEFAULT: u8 = 14;
+
+/// Calculate PWM_OFS and PWM_GRAD start values (see tmc2209 datasheet,
+/// stealthChop options): PWM_OFS = 374 * R_COIL * I_COIL / V_M,
+/// PWM_GRAD = C_BEMF * 2 * pi * fclk * 1.46 / (V_M * MSPR), where MSPR are
+/// microsteps per rotation. Without `back_emf_constant` PWM_GRAD is the
+/// datasheet default 14. Returns Err if motor parameters are not positive
+pub fn stealthchop_pwm_settings(
+    motor: &MotorParameters,
+    full_steps_per_rotation: u32,
+    fclk: f32,
+) -> Result<StealthChopPwmSettings, ()> {
+    if !(motor.supply_voltage > 0.0
+        && motor.resistance >= 0.0
+        && motor.rms_current > 0
+        && full_steps_per_rotation > 0
+        && fclk > 0.0)
+    {
+        return Err(());
     }
 
-    output.irun = cs;
-    output.ihold = (cs as f32 * hold_multiplier) as u8;
-    output
+    let coil_current = motor.rms_current as f32 / 1000.0;
+    let pwm_ofs =
+        374.0 * motor.resistance * coil_current / motor.supply_voltage;
+
+    let pwm_grad = match motor.back_emf_constant {
+        Some(back_emf) if back_emf > 0.0 => {
+            let microsteps_per_rotation =
+                full_steps_per_rotation as f32 * 256.0;
+            (back_emf * 2.0 * core::f32::consts::PI * fclk * 1.46
+                / (motor.supply_voltage * microsteps_per_rotation)
+                + 0.5)
+                .clamp(0.0, 255.0) as u8
+        }
+        Some(_) => return Err(()),
+        None => PWM_GRAD_DEFAULT,
+    };
+
+    Ok(StealthChopPwmSettings {
+        pwm_ofs: (pwm_ofs + 0.5).clamp(0.0, 255.0) as u8,
+        pwm_grad,
+    })
+}
+
+// IHOLDDELAY and TPOWERDOWN count in 2^18 clocks
+const POWER_DOWN_CLOCKS: f32 = 262_144.0;
+
+/// IHOLDDELAY for ramping current down from IRUN to IHOLD in
+/// `ramp_time_ms` (IHOLDDELAY is the delay of each of `irun - ihold` steps)
+pub fn ihold_delay_from_ms(
+    ramp_time_ms: f32,
+    irun: u8,
+    ihold: u8,
+    fclk: f32,
+) -> u8 {
+    let steps = irun.saturating_sub(ihold).max(1) as f32;
+    let delay = ramp_time_ms / 1000.0 / steps * fclk / POWER_DOWN_CLOCKS;
+    (delay + 0.5).clamp(0.0, 15.0) as u8
+}
+
+/// Time of current ramp down from IRUN to IHOLD, ms
+pub fn ihold_delay_to_ms(
+    ihold_delay: u8,
+    irun: u8,
+    ihold: u8,
+    fclk: f32,
+) -> f32 {
+    let steps = irun.saturating_sub(ihold) as f32;
+    ihold_delay as f32 * steps * POWER_DOWN_CLOCKS / fclk * 1000.0
+}
+
+/// TPOWERDOWN for delay between standstill detection and current reduction
+pub fn tpowerdown_from_seconds(seconds: f32, fclk: f32) -> u32 {
+    (seconds * fclk / POWER_DOWN_CLOCKS + 0.5).clamp(0.0, 255.0) as u32
+}
+
+/// Delay between standstill detection and current reduction, s
+pub fn tpowerdown_to_seconds(tpowerdown: u32, fclk: f32) -> f32 {
+    tpowerdown as f32 * POWER_DOWN_CLOCKS / fclk
 }
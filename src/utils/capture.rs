@@ -0,0 +1,221 @@
+//! UART wrapper that records every sent and received byte with timestamp
+//! into a ring buffer, for debugging field failures. Captured bytes are
+//! printed as text log (`Display`, one byte per line), decoded into
+//! datagrams with [`crate::utils::datagram`] and fed back to the driver
+//! with [`crate::utils::replay::ReplayUart`]
+
+use crate::utils::timing::UartTransport;
+use core::fmt;
+use embedded_io::{ErrorType, Read, ReadReady, Write};
+
+/// Ring buffer length of [`CaptureUart::new`], enough for about 20 read
+/// transactions with single wire echo
+pub const DEFAULT_CAPTURE_LEN: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Direction {
+    /// Sent by the MCU
+    Tx,
+    /// Received by the MCU (replies and single wire echo)
+    Rx,
+}
+
+/// One byte of UART traffic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CapturedByte {
+    /// Time of the read or write call, in µs of the capture clock
+    pub timestamp_us: u64,
+    pub direction: Direction,
+    pub byte: u8,
+}
+
+impl CapturedByte {
+    /// Parse one line of text log (`<timestamp_us> TX|RX <hex byte>`, as
+    /// written by `Display`)
+    pub fn parse_line(line: &str) -> Option<CapturedByte> {
+        let mut parts = line.split_whitespace();
+        let timestamp_us = parts.next()?.parse().ok()?;
+        let direction = match parts.next()? {
+            "TX" => Direction::Tx,
+            "RX" => Direction::Rx,
+            _ => return None,
+        };
+        let byte = u8::from_str_radix(parts.next()?, 16).ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(CapturedByte {
+            timestamp_us,
+            direction,
+            byte,
+        })
+    }
+}
+
+impl fmt::Display for CapturedByte {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let direction = match self.direction {
+            Direction::Tx => "TX",
+            Direction::Rx => "RX",
+        };
+        write!(f, "{} {} {:02X}", self.timestamp_us, direction, self.byte)
+    }
+}
+
+/// Parse text log, skipping empty lines and `#` comments. Error is the
+/// number (starting at 1) of the first line that could not be parsed
+#[cfg(feature = "std")]
+pub fn parse_capture_log(
+    text: &str,
+) -> Result<std::vec::Vec<CapturedByte>, usize> {
+    let mut bytes = std::vec::Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        bytes.push(CapturedByte::parse_line(line).ok_or(index + 1)?);
+    }
+    Ok(bytes)
+}
+
+/// Put around the real UART (into the shared UART of
+/// [`crate::TMC2209UART`]) to record the traffic. Keeps the last `N` bytes
+pub struct CaptureUart<Uart, const N: usize = DEFAULT_CAPTURE_LEN> {
+    uart: Uart,
+    now_us: fn() -> u64,
+    bytes: [CapturedByte; N],
+    start: usize,
+    len: usize,
+    dropped: usize,
+}
+
+impl<Uart> CaptureUart<Uart, DEFAULT_CAPTURE_LEN> {
+    /// Capture with [`DEFAULT_CAPTURE_LEN`] bytes. `now_us` is any
+    /// monotonic µs clock
+    pub fn new(uart: Uart, now_us: fn() -> u64) -> Self {
+        CaptureUart::with_len(uart, now_us)
+    }
+}
+
+impl<Uart, const N: usize> CaptureUart<Uart, N> {
+    /// Capture keeping `N` bytes
+    /// (`let uart: CaptureUart<_, 1024> = CaptureUart::with_len(..)`)
+    pub fn with_len(uart: Uart, now_us: fn() -> u64) -> Self {
+        CaptureUart {
+            uart,
+            now_us,
+            bytes: [CapturedByte {
+                timestamp_us: 0,
+                direction: Direction::Tx,
+                byte: 0,
+            }; N],
+            start: 0,
+            len: 0,
+            dropped: 0,
+        }
+    }
+
+    pub fn inner(&self) -> &Uart {
+        &self.uart
+    }
+
+    pub fn inner_mut(&mut self) -> &mut Uart {
+        &mut self.uart
+    }
+
+    pub fn into_inner(self) -> Uart {
+        self.uart
+    }
+
+    /// Captured bytes, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &CapturedByte> {
+        (0..self.len).map(move |i| &self.bytes[(self.start + i) % N])
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Bytes overwritten because the buffer was full
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.len = 0;
+        self.dropped = 0;
+    }
+
+    fn record(&mut self, direction: Direction, bytes: &[u8]) {
+        if N == 0 {
+            self.dropped += bytes.len();
+            return;
+        }
+        let timestamp_us = (self.now_us)();
+        for byte in bytes {
+            let captured = CapturedByte {
+                timestamp_us,
+                direction,
+                byte: *byte,
+            };
+            if self.len < N {
+                self.bytes[(self.start + self.len) % N] = captured;
+                self.len += 1;
+            } else {
+                self.bytes[self.start] = captured;
+                self.start = (self.start + 1) % N;
+                self.dropped += 1;
+            }
+        }
+    }
+}
+
+impl<Uart: ErrorType, const N: usize> ErrorType for CaptureUart<Uart, N> {
+    type Error = Uart::Error;
+}
+
+impl<Uart: Read, const N: usize> Read for CaptureUart<Uart, N> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let count = self.uart.read(buf)?;
+        self.record(Direction::Rx, &buf[..count]);
+        Ok(count)
+    }
+}
+
+impl<Uart: ReadReady, const N: usize> ReadReady for CaptureUart<Uart, N> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        self.uart.read_ready()
+    }
+}
+
+impl<Uart: Write, const N: usize> Write for CaptureUart<Uart, N> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let count = self.uart.write(buf)?;
+        self.record(Direction::Tx, &buf[..count]);
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.uart.flush()
+    }
+}
+
+impl<Uart: UartTransport, const N: usize> UartTransport
+    for CaptureUart<Uart, N>
+{
+    fn delay_us(&mut self, us: u32) {
+        self.uart.delay_us(us);
+    }
+
+    fn reply_latency_us(&self) -> u32 {
+        self.uart.reply_latency_us()
+    }
+}
@@ -0,0 +1,30 @@
+/// CRC-32 (IEEE 802.3, reflected, init and xorout 0xFFFFFFFF)
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// CRC-8 of TMC UART datagrams (polynomial x^8 + x^2 + x + 1, bytes are
+/// processed LSB first)
+pub fn crc8_tmc(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for byte in data {
+        let mut byte = *byte;
+        for _ in 0..8 {
+            if ((crc >> 7) ^ (byte & 0x01)) != 0 {
+                crc = (crc << 1) ^ 0x07;
+            } else {
+                crc <<= 1;
+            }
+            byte >>= 1;
+        }
+    }
+    crc
+}
@@ -0,0 +1,201 @@
+//! `TMC2209_Config` as flat TOML (`field = value` per set field), for host
+//! tools
+//!
+//! ```toml
+//! rms_current = 800
+//! microsteps = 16
+//! en_spreadcycle = false
+//! r_sense = 0.11
+//! ```
+
+use crate::structures::{config::TMC2209_Config, config_toml::ConfigTomlError};
+use std::fmt::Write;
+use std::string::{String, ToString};
+
+/// Field value as written in TOML
+trait TomlValue: Sized {
+    fn parse(value: &str) -> Option<Self>;
+    fn write(&self, output: &mut String);
+}
+
+macro_rules! impl_toml_int {
+    ($($type:ty),*) => {
+        $(
+            impl TomlValue for $type {
+                fn parse(value: &str) -> Option<Self> {
+                    let value = value.replace('_', "");
+                    match value.strip_prefix("0x") {
+                        Some(hex) => <$type>::from_str_radix(hex, 16).ok(),
+                        None => value.parse().ok(),
+                    }
+                }
+
+                fn write(&self, output: &mut String) {
+                    let _ = write!(output, "{}", self);
+                }
+            }
+        )*
+    };
+}
+
+impl_toml_int!(u8, u16, u32);
+
+impl TomlValue for f32 {
+    fn parse(value: &str) -> Option<Self> {
+        value
+            .replace('_', "")
+            .parse()
+            .ok()
+            .filter(|value: &f32| value.is_finite())
+    }
+
+    // Debug keeps the decimal point (`1.0`), so the value stays a float
+    fn write(&self, output: &mut String) {
+        let _ = write!(output, "{:?}", self);
+    }
+}
+
+impl TomlValue for bool {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        }
+    }
+
+    fn write(&self, output: &mut String) {
+        let _ = write!(output, "{}", self);
+    }
+}
+
+macro_rules! toml_fields {
+    ($($field:ident: $type:ty),* $(,)?) => {
+        // Returns false for unknown key
+        fn set_field(
+            config: &mut TMC2209_Config,
+            key: &str,
+            value: &str,
+        ) -> Result<bool, ()> {
+            match key {
+                $(
+                    stringify!($field) => {
+                        config.$field = Some(<$type>::parse(value).ok_or(())?)
+                    }
+                )*
+                _ => return Ok(false),
+            }
+            Ok(true)
+        }
+
+        fn write_fields(config: &TMC2209_Config, output: &mut String) {
+            $(
+                if let Some(value) = config.$field {
+                    output.push_str(concat!(stringify!($field), " = "));
+                    value.write(output);
+                    output.push('\n');
+                }
+            )*
+        }
+    };
+}
+
+// Same order as TMC2209_Config
+toml_fields! {
+    uart_address: u8,
+    r_sense: f32,
+    rms_current: u16,
+    ihold_multiplier: f32,
+    hold_current: u16,
+    ihold_delay: u8,
+    microsteps: u32,
+    interpolation: bool,
+    blank_time: u32,
+    hysteresis_end: u32,
+    hysteresis_start: u32,
+    tpowerdown: u32,
+    tpwmthrs: u32,
+    sgthrs: u32,
+    tcoolthrs: u32,
+    en_spreadcycle: bool,
+    pdn_disable: bool,
+    pwm_ofs: u8,
+    pwm_grad: u8,
+    pwm_freq: u8,
+    pwm_autoscale: bool,
+    pwm_autograd: bool,
+    pwm_reg: u8,
+    pwm_lim: u8,
+    freewheel: u8,
+    internal_rsense: bool,
+    i_scale_analog: bool,
+    mstep_reg_select: bool,
+    multistep_filt: bool,
+    index_otpw: bool,
+    index_step: bool,
+    senddelay: u8,
+    semin: u16,
+    seup: u16,
+    semax: u16,
+    sedn: u16,
+    seimin: bool,
+    toff: u32,
+    vsense: bool,
+    dedge: bool,
+    diss2g: bool,
+    diss2vs: bool,
+    fclktrim: u8,
+    ottrim: u8,
+    shaft: bool,
+}
+
+/// Read config written by [`config_to_toml`] (or by hand). Fields missing
+/// in the text stay None. Values are not validated, use
+/// [`TMC2209_Config::validate`]
+pub fn config_from_toml(text: &str) -> Result<TMC2209_Config, ConfigTomlError> {
+    let mut config = TMC2209_Config::default();
+
+    for (index, raw_line) in text.lines().enumerate() {
+        let line = index + 1;
+        let content = match raw_line.find('#') {
+            Some(position) => &raw_line[..position],
+            None => raw_line,
+        }
+        .trim();
+        if content.is_empty() {
+            continue;
+        }
+
+        let separator =
+            content.find('=').ok_or(ConfigTomlError::Syntax { line })?;
+        let key = content[..separator].trim();
+        let value = content[separator + 1..].trim();
+        if key.is_empty() || value.is_empty() {
+            return Err(ConfigTomlError::Syntax { line });
+        }
+        match set_field(&mut config, key, value) {
+            Ok(true) => {}
+            Ok(false) => {
+                return Err(ConfigTomlError::UnknownKey {
+                    line,
+                    key: key.to_string(),
+                })
+            }
+            Err(()) => {
+                return Err(ConfigTomlError::InvalidValue {
+                    line,
+                    key: key.to_string(),
+                })
+            }
+        }
+    }
+
+    Ok(config)
+}
+
+/// Set fields of config, one `field = value` line each
+pub fn config_to_toml(config: &TMC2209_Config) -> String {
+    let mut output = String::new();
+    write_fields(config, &mut output);
+    output
+}
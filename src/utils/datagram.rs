@@ -0,0 +1,196 @@
+//! Decoder of TMC2209 UART datagrams (see tmc2209 datasheet, page 18, 19),
+//! for captured traffic
+
+use crate::utils::capture::{CapturedByte, Direction};
+use crate::utils::checksum::crc8_tmc;
+
+const MASTER_ADDRESS: u8 = 0xFF;
+const READ_REQUEST_LEN: usize = 4;
+const DATAGRAM_LEN: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DatagramKind {
+    ReadRequest,
+    WriteRequest,
+    /// Reply of the driver to read request
+    Reply,
+}
+
+/// One decoded datagram
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Datagram {
+    /// Timestamp of the first byte
+    pub timestamp_us: u64,
+    pub direction: Direction,
+    pub kind: DatagramKind,
+    /// Node address of requests, 0xFF (master) for replies
+    pub address: u8,
+    /// Register address (without write bit)
+    pub register: u8,
+    /// Data of write requests and replies
+    pub value: Option<u32>,
+}
+
+/// Splits a byte stream of one direction into datagrams. Bytes before a
+/// sync nibble are skipped. On CRC mismatch the first byte is skipped and
+/// the search for the next sync starts again from the byte after it, so
+/// a stray sync byte does not swallow the following datagram
+#[derive(Debug, Clone)]
+pub struct DatagramDecoder {
+    direction: Direction,
+    bytes: [u8; DATAGRAM_LEN],
+    timestamps_us: [u64; DATAGRAM_LEN],
+    len: usize,
+    skipped: usize,
+}
+
+impl DatagramDecoder {
+    pub fn new(direction: Direction) -> DatagramDecoder {
+        DatagramDecoder {
+            direction,
+            bytes: [0; DATAGRAM_LEN],
+            timestamps_us: [0; DATAGRAM_LEN],
+            len: 0,
+            skipped: 0,
+        }
+    }
+
+    /// Feed one byte, returns datagram completed by it
+    pub fn push(&mut self, timestamp_us: u64, byte: u8) -> Option<Datagram> {
+        if self.len == 0 && !is_sync(byte) {
+            self.skipped += 1;
+            return None;
+        }
+        self.bytes[self.len] = byte;
+        self.timestamps_us[self.len] = timestamp_us;
+        self.len += 1;
+
+        loop {
+            let len = self.datagram_len()?;
+            let bytes = &self.bytes[..len];
+            if crc8_tmc(&bytes[..len - 1]) == bytes[len - 1] {
+                let datagram = self.datagram(len);
+                self.drop_front(len);
+                return Some(datagram);
+            }
+            // Not a datagram, resync on the next sync byte
+            self.drop_front(1);
+            self.skipped += 1;
+            while self.len > 0 && !is_sync(self.bytes[0]) {
+                self.drop_front(1);
+                self.skipped += 1;
+            }
+        }
+    }
+
+    /// Bytes that were not part of any datagram
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    // Length of the buffered datagram, None until it is complete
+    fn datagram_len(&self) -> Option<usize> {
+        if self.len < READ_REQUEST_LEN {
+            return None;
+        }
+        let len = if self.is_reply() || self.is_write() {
+            DATAGRAM_LEN
+        } else {
+            READ_REQUEST_LEN
+        };
+        if self.len < len {
+            None
+        } else {
+            Some(len)
+        }
+    }
+
+    fn is_reply(&self) -> bool {
+        self.bytes[1] == MASTER_ADDRESS
+    }
+
+    fn is_write(&self) -> bool {
+        self.bytes[2] & 0x80 != 0
+    }
+
+    fn datagram(&self, len: usize) -> Datagram {
+        let bytes = &self.bytes[..len];
+        let value = if len == DATAGRAM_LEN {
+            Some(u32::from_be_bytes([bytes[3], bytes[4], bytes[5], bytes[6]]))
+        } else {
+            None
+        };
+        Datagram {
+            timestamp_us: self.timestamps_us[0],
+            direction: self.direction,
+            kind: if self.is_reply() {
+                DatagramKind::Reply
+            } else if self.is_write() {
+                DatagramKind::WriteRequest
+            } else {
+                DatagramKind::ReadRequest
+            },
+            address: bytes[1],
+            register: bytes[2] & 0x7F,
+            value,
+        }
+    }
+
+    fn drop_front(&mut self, count: usize) {
+        self.bytes.copy_within(count..self.len, 0);
+        self.timestamps_us.copy_within(count..self.len, 0);
+        self.len -= count;
+    }
+}
+
+// Upper sync bits are don't care for the driver
+fn is_sync(byte: u8) -> bool {
+    byte & 0x0F == 0x05
+}
+
+/// Iterator over datagrams of captured traffic, see [`decode_capture`]
+pub struct DecodeCapture<I> {
+    bytes: I,
+    tx: DatagramDecoder,
+    rx: DatagramDecoder,
+}
+
+impl<I> DecodeCapture<I> {
+    /// Bytes that were not part of any datagram (noise, cut datagrams)
+    pub fn skipped(&self) -> usize {
+        self.tx.skipped() + self.rx.skipped()
+    }
+}
+
+impl<'a, I: Iterator<Item = &'a CapturedByte>> Iterator for DecodeCapture<I> {
+    type Item = Datagram;
+
+    fn next(&mut self) -> Option<Datagram> {
+        for captured in self.bytes.by_ref() {
+            let decoder = match captured.direction {
+                Direction::Tx => &mut self.tx,
+                Direction::Rx => &mut self.rx,
+            };
+            if let Some(datagram) =
+                decoder.push(captured.timestamp_us, captured.byte)
+            {
+                return Some(datagram);
+            }
+        }
+        None
+    }
+}
+
+/// Decode captured bytes, sent and received bytes separately. Received
+/// datagrams include single wire echo of the requests
+pub fn decode_capture<'a, I: IntoIterator<Item = &'a CapturedByte>>(
+    bytes: I,
+) -> DecodeCapture<I::IntoIter> {
+    DecodeCapture {
+        bytes: bytes.into_iter(),
+        tx: DatagramDecoder::new(Direction::Tx),
+        rx: DatagramDecoder::new(Direction::Rx),
+    }
+}
@@ -0,0 +1,185 @@
+//! `embedded_io` UART over a file descriptor (USB-UART dongle `/dev/ttyUSB0`,
+//! pseudo-terminal), for host tools
+
+use crate::utils::timing::UartTransport;
+use embedded_io::{ErrorKind, ErrorType, Read, ReadReady, Write};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::time::Duration;
+
+/// Read gives up when no byte comes for this long (termios VTIME, 1/10 s)
+const READ_TIMEOUT_DECISECONDS: u8 = 1;
+
+#[derive(Debug)]
+pub struct FdUartError(pub io::Error);
+
+impl embedded_io::Error for FdUartError {
+    fn kind(&self) -> ErrorKind {
+        match self.0.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => {
+                ErrorKind::TimedOut
+            }
+            io::ErrorKind::Interrupted => ErrorKind::Interrupted,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// Serial port in raw 8N1 mode. Read returns `TimedOut` error when no byte
+/// comes in time, like the drivers of the library expect
+pub struct FdUart {
+    file: File,
+    echo: bool,
+}
+
+impl FdUart {
+    /// Open serial device and set baud rate (one of standard rates up to
+    /// 460800)
+    pub fn open<P: AsRef<Path>>(path: P, baud_rate: u32) -> io::Result<FdUart> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(path)?;
+        FdUart::from_file(file, baud_rate)
+    }
+
+    /// Use already opened device (for example pseudo-terminal)
+    pub fn from_file(file: File, baud_rate: u32) -> io::Result<FdUart> {
+        set_raw_mode(&file, Some(baud_rate))?;
+        Ok(FdUart { file, echo: false })
+    }
+
+    /// Single wire adapters (TX and RX joined with a resistor) receive
+    /// every sent byte back. With echo the sent bytes are read back and
+    /// dropped after each write
+    pub fn set_echo(&mut self, echo: bool) {
+        self.echo = echo;
+    }
+
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    fn drop_echo(&mut self, mut count: usize) -> io::Result<()> {
+        let mut buf = [0u8; 64];
+        while count > 0 {
+            let chunk = count.min(buf.len());
+            let read = io::Read::read(&mut self.file, &mut buf[..chunk])?;
+            if read == 0 {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            count -= read;
+        }
+        Ok(())
+    }
+}
+
+/// Raw mode, 8N1, no flow control, reads time out. Without baud rate the
+/// current one is kept (pseudo-terminals ignore it anyway)
+pub fn set_raw_mode(file: &File, baud_rate: Option<u32>) -> io::Result<()> {
+    let fd = file.as_raw_fd();
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &mut termios) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        libc::cfmakeraw(&mut termios);
+        termios.c_cflag |= libc::CLOCAL | libc::CREAD;
+        termios.c_cflag &= !(libc::CSTOPB | libc::PARENB | libc::CRTSCTS);
+        termios.c_cc[libc::VMIN] = 0;
+        termios.c_cc[libc::VTIME] = READ_TIMEOUT_DECISECONDS;
+        if let Some(baud_rate) = baud_rate {
+            let speed = baud_rate_constant(baud_rate).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "unsupported baud rate",
+                )
+            })?;
+            libc::cfsetispeed(&mut termios, speed);
+            libc::cfsetospeed(&mut termios, speed);
+        }
+        if libc::tcsetattr(fd, libc::TCSANOW, &termios) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+fn baud_rate_constant(baud_rate: u32) -> Option<libc::speed_t> {
+    Some(match baud_rate {
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        230400 => libc::B230400,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        460800 => libc::B460800,
+        _ => return None,
+    })
+}
+
+impl ErrorType for FdUart {
+    type Error = FdUartError;
+}
+
+impl Read for FdUart {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        match io::Read::read(&mut self.file, buf) {
+            // VTIME expired
+            Ok(0) => Err(FdUartError(io::ErrorKind::TimedOut.into())),
+            Ok(count) => Ok(count),
+            Err(error) => Err(FdUartError(error)),
+        }
+    }
+}
+
+impl ReadReady for FdUart {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        let mut poll_fd = libc::pollfd {
+            fd: self.file.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        match unsafe { libc::poll(&mut poll_fd, 1, 0) } {
+            -1 => Err(FdUartError(io::Error::last_os_error())),
+            ready => Ok(ready > 0 && poll_fd.revents & libc::POLLIN != 0),
+        }
+    }
+}
+
+impl Write for FdUart {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let count =
+            io::Write::write(&mut self.file, buf).map_err(FdUartError)?;
+        if self.echo {
+            self.drop_echo(count).map_err(FdUartError)?;
+        }
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        if unsafe { libc::tcdrain(self.file.as_raw_fd()) } != 0 {
+            return Err(FdUartError(io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+}
+
+impl UartTransport for FdUart {
+    fn delay_us(&mut self, us: u32) {
+        std::thread::sleep(Duration::from_micros(us as u64));
+    }
+
+    // USB-UART adapters pass received bytes on in packets
+    fn reply_latency_us(&self) -> u32 {
+        READ_TIMEOUT_DECISECONDS as u32 * 100_000
+    }
+}
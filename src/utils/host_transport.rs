@@ -0,0 +1,93 @@
+//! Serial port options and setup shared by the host tools (`tmc2209-cli`,
+//! `tmc2209-tui`)
+
+use crate::structures::base_config::TMC2209_BaseConfig;
+use crate::utils::fd_uart::FdUart;
+use core::cell::RefCell;
+use critical_section::Mutex;
+use std::convert::TryFrom;
+
+/// Serial port shared with the driver
+pub type SharedUart = Mutex<RefCell<Option<FdUart>>>;
+
+/// Port options of the host tools (`--port`, `--baud`, `--node`, `--echo`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortOptions {
+    pub port: String,
+    pub baud_rate: u32,
+    /// Node address 0..3
+    pub node: u8,
+    /// Single wire adapter, drop echo of sent bytes
+    pub echo: bool,
+}
+
+impl Default for PortOptions {
+    /// `$TMC2209_PORT` or `/dev/ttyUSB0`, 115200 baud, node 0
+    fn default() -> PortOptions {
+        PortOptions {
+            port: std::env::var("TMC2209_PORT")
+                .unwrap_or_else(|_| "/dev/ttyUSB0".to_string()),
+            baud_rate: 115_200,
+            node: 0,
+            echo: false,
+        }
+    }
+}
+
+impl PortOptions {
+    /// Take `arg` if it is a port option, `value` gives the value of the
+    /// option. Returns false for other arguments
+    pub fn parse_arg<Value>(
+        &mut self,
+        arg: &str,
+        mut value: Value,
+    ) -> Result<bool, String>
+    where
+        Value: FnMut(&str) -> Result<String, String>,
+    {
+        match arg {
+            "--port" => self.port = value("--port")?,
+            "--baud" => self.baud_rate = parse_number(&value("--baud")?)?,
+            "--node" => {
+                self.node = parse_number(&value("--node")?)?;
+                if self.node > 3 {
+                    return Err("node address must be 0..3".to_string());
+                }
+            }
+            "--echo" => self.echo = true,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Open the serial port
+    pub fn open(&self) -> Result<SharedUart, String> {
+        let mut uart =
+            FdUart::open(&self.port, self.baud_rate).map_err(|error| {
+                format!("can not open {}: {}", self.port, error)
+            })?;
+        uart.set_echo(self.echo);
+        Ok(Mutex::new(RefCell::new(Some(uart))))
+    }
+
+    /// Base config with node address and baud rate of the options
+    pub fn base_config(&self) -> TMC2209_BaseConfig {
+        TMC2209_BaseConfig {
+            uart_address: self.node,
+            baud_rate: self.baud_rate,
+            ..Default::default()
+        }
+    }
+}
+
+/// Decimal or 0x hex number
+pub fn parse_number<T: TryFrom<u64>>(text: &str) -> Result<T, String> {
+    let value = match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    value
+        .ok()
+        .and_then(|value| T::try_from(value).ok())
+        .ok_or_else(|| format!("invalid number `{}`", text))
+}
@@ -0,0 +1,363 @@
+//! Import of Klipper `[tmc2209 stepper_x]` config sections
+//!
+//! Values not present in the section get Klipper defaults, so imported
+//! config programs the driver the same way Klipper did.
+
+use crate::structures::{
+    base_config::TMC2209_BaseConfig,
+    config::TMC2209_Config,
+    klipper_import::{
+        KlipperImportError, KlipperImportOptions, KlipperTmc2209Import,
+    },
+};
+use crate::utils::calc::{velocity_to_tstep, TSTEP_MAX};
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+const TMC2209_SECTION_PREFIX: &str = "tmc2209 ";
+
+// Wiring keys, they have nothing to do with the driver config
+const WIRING_KEYS: [&str; 4] =
+    ["uart_pin", "tx_pin", "select_pins", "diag_pin"];
+
+impl Default for KlipperImportOptions {
+    fn default() -> Self {
+        KlipperImportOptions {
+            rotation_distance: None,
+            fclk: TMC2209_BaseConfig::default().fclk,
+        }
+    }
+}
+
+struct Entry<'a> {
+    line: usize,
+    key: String,
+    value: &'a str,
+}
+
+struct Section<'a> {
+    name: &'a str,
+    entries: Vec<Entry<'a>>,
+}
+
+impl Section<'_> {
+    fn get(&self, key: &str) -> Option<&Entry<'_>> {
+        self.entries.iter().find(|entry| entry.key == key)
+    }
+}
+
+/// Motion values from `[stepper_x]` section
+struct StepperParams {
+    microsteps: Option<u32>,
+    full_steps_per_rotation: u32,
+    rotation_distance: Option<f32>,
+}
+
+/// Read all `[tmc2209 ...]` sections of Klipper config (printer.cfg)
+pub fn import_klipper_config(
+    text: &str,
+    options: &KlipperImportOptions,
+) -> Result<Vec<KlipperTmc2209Import>, KlipperImportError> {
+    let sections = parse_sections(text)?;
+    let mut output = Vec::new();
+
+    for section in sections.iter() {
+        if !section.name.starts_with(TMC2209_SECTION_PREFIX) {
+            continue;
+        }
+        let stepper = section.name[TMC2209_SECTION_PREFIX.len()..].trim();
+        let stepper_params = read_stepper_params(
+            sections.iter().find(|section| section.name == stepper),
+        )?;
+        output.push(import_tmc2209_section(
+            stepper,
+            section,
+            &stepper_params,
+            options,
+        )?);
+    }
+
+    Ok(output)
+}
+
+fn parse_sections(text: &str) -> Result<Vec<Section<'_>>, KlipperImportError> {
+    let mut sections: Vec<Section> = Vec::new();
+
+    for (index, raw_line) in text.lines().enumerate() {
+        let line = index + 1;
+        let content = strip_comment(raw_line);
+        if content.trim().is_empty() {
+            continue;
+        }
+
+        // Indented lines continue multi-line value of previous key (gcode)
+        if raw_line.starts_with(' ') || raw_line.starts_with('\t') {
+            continue;
+        }
+
+        let content = content.trim();
+        if content.starts_with('[') {
+            if !content.ends_with(']') {
+                return Err(KlipperImportError::Syntax { line });
+            }
+            sections.push(Section {
+                name: content[1..content.len() - 1].trim(),
+                entries: Vec::new(),
+            });
+            continue;
+        }
+
+        let separator = content
+            .find([':', '='])
+            .ok_or(KlipperImportError::Syntax { line })?;
+        let section = sections
+            .last_mut()
+            .ok_or(KlipperImportError::Syntax { line })?;
+        section.entries.push(Entry {
+            line,
+            // Klipper keys are case insensitive
+            key: content[..separator].trim().to_ascii_lowercase(),
+            value: content[separator + 1..].trim(),
+        });
+    }
+
+    Ok(sections)
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find(['#', ';']) {
+        Some(position) => &line[..position],
+        None => line,
+    }
+}
+
+fn read_stepper_params(
+    section: Option<&Section>,
+) -> Result<StepperParams, KlipperImportError> {
+    let mut params = StepperParams {
+        microsteps: None,
+        full_steps_per_rotation: 200,
+        rotation_distance: None,
+    };
+
+    if let Some(section) = section {
+        if let Some(entry) = section.get("microsteps") {
+            params.microsteps = Some(parse_int(entry, 256)?);
+        }
+        if let Some(entry) = section.get("full_steps_per_rotation") {
+            params.full_steps_per_rotation = parse_int(entry, u16::MAX as u32)?;
+        }
+        if let Some(entry) = section.get("rotation_distance") {
+            params.rotation_distance = Some(parse_float(entry)?);
+        }
+    }
+
+    Ok(params)
+}
+
+fn import_tmc2209_section(
+    stepper: &str,
+    section: &Section,
+    stepper_params: &StepperParams,
+    options: &KlipperImportOptions,
+) -> Result<KlipperTmc2209Import, KlipperImportError> {
+    let mut base_config = TMC2209_BaseConfig {
+        fclk: options.fclk,
+        full_steps_per_rotation: stepper_params.full_steps_per_rotation,
+        ..Default::default()
+    };
+    let mut config = klipper_default_config();
+    config.microsteps = stepper_params.microsteps;
+
+    let mut unsupported_keys = Vec::new();
+    let mut run_current = None;
+    let mut hold_current = None;
+    let mut stealthchop_threshold = None;
+    let mut coolstep_threshold = None;
+
+    for entry in section.entries.iter() {
+        match entry.key.as_str() {
+            "uart_address" => {
+                let uart_address = parse_int(entry, 3)? as u8;
+                base_config.uart_address = uart_address;
+                config.uart_address = Some(uart_address);
+            }
+            "run_current" => run_current = Some(parse_float(entry)?),
+            "hold_current" => hold_current = Some(parse_float(entry)?),
+            "sense_resistor" => {
+                let r_sense = parse_float(entry)?;
+                base_config.r_sense = r_sense;
+                config.r_sense = Some(r_sense);
+            }
+            "interpolate" => config.interpolation = Some(parse_bool(entry)?),
+            "stealthchop_threshold" => {
+                stealthchop_threshold = Some(parse_float(entry)?)
+            }
+            "coolstep_threshold" => {
+                coolstep_threshold = Some(parse_float(entry)?)
+            }
+            "driver_multistep_filt" => {
+                config.multistep_filt = Some(parse_bool(entry)?)
+            }
+            "driver_iholddelay" => {
+                config.ihold_delay = Some(parse_int(entry, 15)? as u8)
+            }
+            "driver_tpowerdown" => {
+                config.tpowerdown = Some(parse_int(entry, 255)?)
+            }
+            "driver_tbl" => config.blank_time = Some(parse_int(entry, 3)?),
+            "driver_toff" => config.toff = Some(parse_int(entry, 15)?),
+            "driver_hend" => {
+                config.hysteresis_end = Some(parse_int(entry, 15)?)
+            }
+            "driver_hstrt" => {
+                config.hysteresis_start = Some(parse_int(entry, 7)?)
+            }
+            "driver_pwm_autograd" => {
+                config.pwm_autograd = Some(parse_bool(entry)?)
+            }
+            "driver_pwm_autoscale" => {
+                config.pwm_autoscale = Some(parse_bool(entry)?)
+            }
+            "driver_pwm_lim" => {
+                config.pwm_lim = Some(parse_int(entry, 15)? as u8)
+            }
+            "driver_pwm_reg" => {
+                config.pwm_reg = Some(parse_int(entry, 15)? as u8)
+            }
+            "driver_pwm_freq" => {
+                config.pwm_freq = Some(parse_int(entry, 3)? as u8)
+            }
+            "driver_pwm_grad" => {
+                config.pwm_grad = Some(parse_int(entry, 255)? as u8)
+            }
+            "driver_pwm_ofs" => {
+                config.pwm_ofs = Some(parse_int(entry, 255)? as u8)
+            }
+            "driver_sgthrs" => config.sgthrs = Some(parse_int(entry, 255)?),
+            "driver_semin" => config.semin = Some(parse_int(entry, 15)? as u16),
+            "driver_seup" => config.seup = Some(parse_int(entry, 3)? as u16),
+            "driver_semax" => config.semax = Some(parse_int(entry, 15)? as u16),
+            "driver_sedn" => config.sedn = Some(parse_int(entry, 3)? as u16),
+            "driver_seimin" => config.seimin = Some(parse_bool(entry)?),
+            key if WIRING_KEYS.contains(&key) => {}
+            key => unsupported_keys.push(key.to_string()),
+        }
+    }
+
+    if let Some(run_current) = run_current {
+        config.rms_current = Some((run_current * 1000.0 + 0.5) as u16);
+
+        // Klipper does not reduce current at standstill by default
+        let hold_current = hold_current.unwrap_or(run_current);
+        let ihold_multiplier = if run_current > 0.0 {
+            (hold_current / run_current).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        base_config.ihold_multiplier = ihold_multiplier;
+        config.ihold_multiplier = Some(ihold_multiplier);
+        config.hold_current = Some((hold_current * 1000.0 + 0.5) as u16);
+    }
+
+    let rotation_distance = options
+        .rotation_distance
+        .or(stepper_params.rotation_distance);
+    let threshold_to_tstep = |velocity: f32| match rotation_distance {
+        Some(rotation_distance) => Ok(velocity_to_tstep(
+            velocity,
+            rotation_distance,
+            base_config.full_steps_per_rotation,
+            base_config.fclk,
+        )),
+        None => Err(KlipperImportError::MissingRotationDistance {
+            stepper: stepper.to_string(),
+        }),
+    };
+
+    // Same as Klipper: stealthChop is only enabled if threshold is set
+    // above 0, 0 (the Klipper default) keeps spreadCycle only
+    match stealthchop_threshold {
+        Some(velocity) if velocity > 0.0 => {
+            config.en_spreadcycle = Some(false);
+            config.tpwmthrs = Some(threshold_to_tstep(velocity)?);
+        }
+        _ => {
+            config.en_spreadcycle = Some(true);
+            config.tpwmthrs = Some(TSTEP_MAX);
+        }
+    }
+
+    match coolstep_threshold {
+        Some(velocity) if velocity > 0.0 => {
+            config.tcoolthrs = Some(threshold_to_tstep(velocity)?);
+        }
+        _ => config.tcoolthrs = Some(0),
+    }
+
+    Ok(KlipperTmc2209Import {
+        stepper: stepper.to_string(),
+        base_config,
+        config,
+        unsupported_keys,
+    })
+}
+
+/// Driver config Klipper sends when section does not override it
+fn klipper_default_config() -> TMC2209_Config {
+    TMC2209_Config {
+        interpolation: Some(true),
+        multistep_filt: Some(true),
+        pdn_disable: Some(true),
+        mstep_reg_select: Some(true),
+        ihold_delay: Some(8),
+        tpowerdown: Some(20),
+        blank_time: Some(2),
+        toff: Some(3),
+        hysteresis_end: Some(0),
+        hysteresis_start: Some(5),
+        pwm_autograd: Some(true),
+        pwm_autoscale: Some(true),
+        pwm_lim: Some(12),
+        pwm_reg: Some(8),
+        pwm_freq: Some(1),
+        pwm_grad: Some(14),
+        pwm_ofs: Some(36),
+        sgthrs: Some(0),
+        semin: Some(0),
+        seup: Some(0),
+        semax: Some(0),
+        sedn: Some(0),
+        seimin: Some(false),
+        ..Default::default()
+    }
+}
+
+fn invalid_value(entry: &Entry) -> KlipperImportError {
+    KlipperImportError::InvalidValue {
+        line: entry.line,
+        key: entry.key.clone(),
+    }
+}
+
+fn parse_int(entry: &Entry, max: u32) -> Result<u32, KlipperImportError> {
+    match entry.value.parse::<u32>() {
+        Ok(value) if value <= max => Ok(value),
+        _ => Err(invalid_value(entry)),
+    }
+}
+
+fn parse_float(entry: &Entry) -> Result<f32, KlipperImportError> {
+    match entry.value.parse::<f32>() {
+        Ok(value) if value.is_finite() && value >= 0.0 => Ok(value),
+        _ => Err(invalid_value(entry)),
+    }
+}
+
+fn parse_bool(entry: &Entry) -> Result<bool, KlipperImportError> {
+    match entry.value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => Err(invalid_value(entry)),
+    }
+}
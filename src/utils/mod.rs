@@ -1,2 +1,17 @@
 pub mod calc;
+pub mod capture;
+pub mod checksum;
+#[cfg(feature = "std")]
+pub mod config_toml;
+pub mod datagram;
+#[cfg(all(feature = "std", unix))]
+pub mod fd_uart;
+#[cfg(all(feature = "std", unix))]
+pub mod host_transport;
+#[cfg(feature = "std")]
+pub mod klipper_import;
+pub mod replay;
+pub mod simulated_device;
+pub mod timing;
 pub mod tmc_read_write;
+pub mod trace;
@@ -0,0 +1,147 @@
+//! UART that plays back captured traffic (see [`crate::utils::capture`]),
+//! to reproduce field failures in regression tests. Received bytes of the
+//! capture are returned by reads, writes are checked against sent bytes
+
+use crate::utils::capture::{CapturedByte, Direction};
+use crate::utils::timing::UartTransport;
+use embedded_io::{ErrorKind, ErrorType, Read, ReadReady, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReplayError {
+    /// Next captured byte was sent by the MCU (or capture is over), there
+    /// is nothing to read
+    NoData,
+    /// Written byte differs from the capture, see
+    /// [`ReplayUart::mismatch`]
+    Diverged,
+}
+
+impl embedded_io::Error for ReplayError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ReplayError::NoData => ErrorKind::TimedOut,
+            ReplayError::Diverged => ErrorKind::Other,
+        }
+    }
+}
+
+/// First written byte that differs from the capture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ReplayMismatch {
+    /// Index of the expected byte in the capture
+    pub index: usize,
+    /// None if the capture has no more sent bytes
+    pub expected: Option<u8>,
+    pub actual: u8,
+}
+
+/// Put into the shared UART of [`crate::TMC2209UART`] instead of the real
+/// UART. Received bytes the driver did not read before the next write are
+/// skipped
+#[derive(Debug, Clone)]
+pub struct ReplayUart<'a> {
+    capture: &'a [CapturedByte],
+    position: usize,
+    skipped: usize,
+    mismatch: Option<ReplayMismatch>,
+}
+
+impl<'a> ReplayUart<'a> {
+    pub fn new(capture: &'a [CapturedByte]) -> ReplayUart<'a> {
+        ReplayUart {
+            capture,
+            position: 0,
+            skipped: 0,
+            mismatch: None,
+        }
+    }
+
+    /// Index of the next captured byte
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// True when the whole capture was played back
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.capture.len()
+    }
+
+    /// Received bytes skipped because they were not read
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    pub fn mismatch(&self) -> Option<ReplayMismatch> {
+        self.mismatch
+    }
+
+    fn next_is(&self, direction: Direction) -> bool {
+        self.capture
+            .get(self.position)
+            .is_some_and(|captured| captured.direction == direction)
+    }
+}
+
+impl ErrorType for ReplayUart<'_> {
+    type Error = ReplayError;
+}
+
+impl Read for ReplayUart<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if !self.next_is(Direction::Rx) {
+            return Err(ReplayError::NoData);
+        }
+        let mut count = 0;
+        while count < buf.len() && self.next_is(Direction::Rx) {
+            buf[count] = self.capture[self.position].byte;
+            self.position += 1;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+impl ReadReady for ReplayUart<'_> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.next_is(Direction::Rx))
+    }
+}
+
+impl Write for ReplayUart<'_> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if self.mismatch.is_some() {
+            return Err(ReplayError::Diverged);
+        }
+        for byte in buf {
+            while self.next_is(Direction::Rx) {
+                self.position += 1;
+                self.skipped += 1;
+            }
+            let expected = self.capture.get(self.position).map(|c| c.byte);
+            if expected != Some(*byte) {
+                self.mismatch = Some(ReplayMismatch {
+                    index: self.position,
+                    expected,
+                    actual: *byte,
+                });
+                return Err(ReplayError::Diverged);
+            }
+            self.position += 1;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+// Captured replies are there at once, there is nothing to wait for
+impl UartTransport for ReplayUart<'_> {
+    fn delay_us(&mut self, _us: u32) {}
+}
@@ -0,0 +1,343 @@
+//! TMC2209 simulated on the UART level. Implements
+//! [`crate::utils::timing::UartTransport`], so it can be put into the shared
+//! UART instead of real hardware for dry runs and tests
+
+use crate::utils::checksum::crc8_tmc;
+use crate::utils::timing::UartTransport;
+use embedded_io::{ErrorKind, ErrorType, Read, ReadReady, Write};
+
+const SYNC: u8 = 0x05;
+const MASTER_ADDRESS: u8 = 0xFF;
+const OTP_MAGIC: u8 = 0xBD;
+const GSTAT: u8 = 0x01;
+const IFCNT: u8 = 0x02;
+const OTP_PROG: u8 = 0x04;
+const OTP_READ: u8 = 0x05;
+const IOIN: u8 = 0x06;
+const FACTORY_CONF: u8 = 0x07;
+const CHOPCONF: u8 = 0x6C;
+const PWMCONF: u8 = 0x70;
+
+/// Registers answered on read. Write-only registers are still stored, so
+/// tests can check what was written with [`SimulatedTmc2209::register`]
+const READABLE: [u8; 15] = [
+    0x00, 0x01, 0x02, 0x05, 0x06, 0x07, 0x12, 0x41, 0x6A, 0x6B, 0x6C, 0x6F,
+    0x70, 0x71, 0x72,
+];
+
+/// Error of [`SimulatedTmc2209`] read when there is no reply to read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SimulatedDeviceError;
+
+impl embedded_io::Error for SimulatedDeviceError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::TimedOut
+    }
+}
+
+/// One TMC2209 on a simulated single wire bus
+#[derive(Debug, Clone)]
+pub struct SimulatedTmc2209 {
+    address: u8,
+    registers: [u32; 128],
+    otp: [u8; 3],
+    echo: bool,
+    // Datagram being received
+    rx: [u8; 8],
+    rx_len: usize,
+    // Bytes waiting to be read by the master
+    tx: [u8; 64],
+    tx_start: usize,
+    tx_len: usize,
+}
+
+impl SimulatedTmc2209 {
+    /// Chip with `address` (0..3, MS1/MS2 pins), blank OTP and power-up
+    /// register values
+    pub fn new(address: u8) -> SimulatedTmc2209 {
+        let mut device = SimulatedTmc2209 {
+            address,
+            registers: [0; 128],
+            otp: [0; 3],
+            echo: false,
+            rx: [0; 8],
+            rx_len: 0,
+            tx: [0; 64],
+            tx_start: 0,
+            tx_len: 0,
+        };
+        device.power_up();
+        device
+    }
+
+    /// Reset registers to the power-up values (GSTAT reset flag set,
+    /// FACTORY_CONF loaded from OTP)
+    pub fn power_up(&mut self) {
+        self.registers = [0; 128];
+        self.registers[GSTAT as usize] = 0x01;
+        self.registers[IOIN as usize] = 0x21 << 24;
+        self.registers[FACTORY_CONF as usize] = self.otp_factory_conf();
+        // TOFF=3, HSTRT=5, TBL=2, MRES=0 (256 microsteps)
+        self.registers[CHOPCONF as usize] = 0x1001_0053;
+        self.registers[PWMCONF as usize] = 0xC10D_0024;
+        self.rx_len = 0;
+    }
+
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// Single wire UART echoes every byte sent by the master
+    pub fn set_echo(&mut self, echo: bool) {
+        self.echo = echo;
+    }
+
+    /// Current register value (including write-only registers)
+    pub fn register(&self, address: u8) -> u32 {
+        self.registers[(address & 0x7F) as usize]
+    }
+
+    /// Set register value as the chip would, for example DRV_STATUS flags
+    pub fn set_register(&mut self, address: u8, value: u32) {
+        self.registers[(address & 0x7F) as usize] = value;
+    }
+
+    /// OTP bytes 0..2
+    pub fn otp(&self) -> [u8; 3] {
+        self.otp
+    }
+
+    pub fn set_otp(&mut self, otp: [u8; 3]) {
+        self.otp = otp;
+        self.registers[OTP_READ as usize] = self.otp_read();
+    }
+
+    fn otp_read(&self) -> u32 {
+        self.otp[0] as u32
+            | (self.otp[1] as u32) << 8
+            | (self.otp[2] as u32) << 16
+    }
+
+    fn otp_factory_conf(&self) -> u32 {
+        let fclktrim = (self.otp[0] & 0x1F) as u32;
+        let ottrim = ((self.otp[0] >> 5) & 0x01) as u32;
+        fclktrim | ottrim << 8
+    }
+
+    fn receive(&mut self, byte: u8) {
+        if self.rx_len == 0 && byte != SYNC {
+            return;
+        }
+        self.rx[self.rx_len] = byte;
+        self.rx_len += 1;
+
+        let is_write = self.rx_len >= 3 && self.rx[2] & 0x80 != 0;
+        let datagram_len = if is_write { 8 } else { 4 };
+        if self.rx_len < datagram_len {
+            return;
+        }
+        self.rx_len = 0;
+
+        let datagram = self.rx;
+        let crc_index = datagram_len - 1;
+        if crc8_tmc(&datagram[..crc_index]) != datagram[crc_index]
+            || datagram[1] != self.address
+        {
+            return;
+        }
+
+        let reg = datagram[2] & 0x7F;
+        if is_write {
+            let value = u32::from_be_bytes([
+                datagram[3],
+                datagram[4],
+                datagram[5],
+                datagram[6],
+            ]);
+            self.write_register(reg, value);
+        } else if READABLE.contains(&reg) {
+            let value = self.registers[reg as usize];
+            let mut reply = [SYNC, MASTER_ADDRESS, reg, 0, 0, 0, 0, 0];
+            reply[3..7].copy_from_slice(&value.to_be_bytes());
+            reply[7] = crc8_tmc(&reply[..7]);
+            self.transmit(&reply);
+        }
+    }
+
+    fn write_register(&mut self, reg: u8, value: u32) {
+        match reg {
+            // Write 1 to clear
+            GSTAT => self.registers[GSTAT as usize] &= !value,
+            OTP_PROG => {
+                if (value >> 8) as u8 == OTP_MAGIC {
+                    let bit = value & 0x07;
+                    let byte = ((value >> 4) & 0x03) as usize;
+                    if byte < self.otp.len() {
+                        self.otp[byte] |= 1 << bit;
+                        self.registers[OTP_READ as usize] = self.otp_read();
+                    }
+                }
+            }
+            IFCNT | OTP_READ | IOIN => {}
+            _ => self.registers[reg as usize] = value,
+        }
+        self.registers[IFCNT as usize] =
+            (self.registers[IFCNT as usize] + 1) & 0xFF;
+    }
+
+    fn transmit(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            if self.tx_len == self.tx.len() {
+                return;
+            }
+            let index = (self.tx_start + self.tx_len) % self.tx.len();
+            self.tx[index] = *byte;
+            self.tx_len += 1;
+        }
+    }
+}
+
+impl ErrorType for SimulatedTmc2209 {
+    type Error = SimulatedDeviceError;
+}
+
+impl Read for SimulatedTmc2209 {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.tx_len == 0 {
+            return Err(SimulatedDeviceError);
+        }
+        let mut count = 0;
+        while count < buf.len() && self.tx_len > 0 {
+            buf[count] = self.tx[self.tx_start];
+            self.tx_start = (self.tx_start + 1) % self.tx.len();
+            self.tx_len -= 1;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+impl ReadReady for SimulatedTmc2209 {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.tx_len > 0)
+    }
+}
+
+impl Write for SimulatedTmc2209 {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        for byte in buf {
+            if self.echo {
+                self.transmit(&[*byte]);
+            }
+            self.receive(*byte);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+// Replies are ready at once, there is nothing to wait for
+impl UartTransport for SimulatedTmc2209 {
+    fn delay_us(&mut self, _us: u32) {}
+}
+
+/// Several simulated drivers on one single wire UART. Replies sent at the
+/// same time (drivers with the same address) are combined like on the
+/// wire: a low bit of any driver wins. Equal replies stay valid, so a
+/// collision shows up only when register contents differ
+#[derive(Debug, Clone)]
+pub struct SimulatedBus {
+    devices: [Option<SimulatedTmc2209>; 4],
+}
+
+impl SimulatedBus {
+    pub fn new() -> SimulatedBus {
+        SimulatedBus {
+            devices: [None, None, None, None],
+        }
+    }
+
+    /// Connect a driver to free slot `slot` (0..3). Slot is not the node
+    /// address, two slots may hold drivers with the same address
+    pub fn connect(&mut self, slot: usize, device: SimulatedTmc2209) {
+        self.devices[slot] = Some(device);
+    }
+
+    pub fn device(&self, slot: usize) -> Option<&SimulatedTmc2209> {
+        self.devices.get(slot).and_then(|device| device.as_ref())
+    }
+
+    pub fn device_mut(&mut self, slot: usize) -> Option<&mut SimulatedTmc2209> {
+        self.devices
+            .get_mut(slot)
+            .and_then(|device| device.as_mut())
+    }
+}
+
+impl Default for SimulatedBus {
+    fn default() -> Self {
+        SimulatedBus::new()
+    }
+}
+
+impl ErrorType for SimulatedBus {
+    type Error = SimulatedDeviceError;
+}
+
+impl Read for SimulatedBus {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let mut count = 0;
+        while count < buf.len() {
+            let mut line: Option<u8> = None;
+            for device in self.devices.iter_mut().flatten() {
+                let mut byte = [0u8; 1];
+                if device.read(&mut byte).is_ok() {
+                    line = Some(line.unwrap_or(0xFF) & byte[0]);
+                }
+            }
+            match line {
+                Some(byte) => buf[count] = byte,
+                None => break,
+            }
+            count += 1;
+        }
+        if count == 0 && !buf.is_empty() {
+            return Err(SimulatedDeviceError);
+        }
+        Ok(count)
+    }
+}
+
+impl ReadReady for SimulatedBus {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(self
+            .devices
+            .iter()
+            .flatten()
+            .any(|device| device.tx_len > 0))
+    }
+}
+
+impl Write for SimulatedBus {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        for device in self.devices.iter_mut().flatten() {
+            device.write(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl UartTransport for SimulatedBus {
+    fn delay_us(&mut self, _us: u32) {}
+}
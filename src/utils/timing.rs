@@ -0,0 +1,167 @@
+//! UART timing of the TMC2209 single wire interface
+//! (see tmc2209 datasheet, page 18-20)
+
+use embedded_hal::delay::DelayNs;
+use embedded_io::{ErrorType, Read, ReadReady, Write};
+
+/// Bits per UART byte: start bit, 8 data bits, stop bit
+pub const BITS_PER_BYTE: u32 = 10;
+/// Read request datagram length, bytes
+pub const READ_REQUEST_LEN: u32 = 4;
+/// Write request and read reply datagram length, bytes
+pub const DATAGRAM_LEN: u32 = 8;
+/// Idle time after which the driver resets its receiver, bit times
+pub const BUS_RESET_BIT_TIMES: u32 = 63;
+/// Idle between datagrams, bit times. The driver needs some idle to
+/// detect the next sync nibble for its baud rate detection
+pub const INTER_FRAME_GAP_BIT_TIMES: u32 = 12;
+
+/// Baud rates tried by default when detecting the working one
+pub const DEFAULT_BAUD_RATES: [u32; 6] =
+    [115_200, 230_400, 57_600, 250_000, 500_000, 9_600];
+
+/// Bit times between read request and reply for SLAVECONF SENDDELAY
+/// (0..15). 0 and 1 mean 8 bit times, n means (n | 1) * 8
+pub fn senddelay_bit_times(senddelay: u8) -> u32 {
+    ((senddelay & 0x0F) | 1) as u32 * 8
+}
+
+/// Timing of one node for the used baud rate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct UartTiming {
+    pub baud_rate: u32,
+    /// SLAVECONF SENDDELAY
+    pub senddelay: u8,
+}
+
+impl UartTiming {
+    pub fn new(baud_rate: u32, senddelay: u8) -> UartTiming {
+        UartTiming {
+            baud_rate,
+            senddelay,
+        }
+    }
+
+    /// Duration of `bit_times` bits, µs (rounded up)
+    pub fn bit_times_us(&self, bit_times: u32) -> u32 {
+        let baud_rate = self.baud_rate.max(1) as u64;
+        (bit_times as u64 * 1_000_000).div_ceil(baud_rate) as u32
+    }
+
+    pub fn byte_time_us(&self) -> u32 {
+        self.bit_times_us(BITS_PER_BYTE)
+    }
+
+    /// Time to send a write datagram, µs
+    pub fn write_time_us(&self) -> u32 {
+        self.bit_times_us(DATAGRAM_LEN * BITS_PER_BYTE)
+    }
+
+    /// Time from the start of a read request to the end of the reply, µs
+    pub fn read_time_us(&self) -> u32 {
+        self.bit_times_us(
+            (READ_REQUEST_LEN + DATAGRAM_LEN) * BITS_PER_BYTE
+                + senddelay_bit_times(self.senddelay),
+        )
+    }
+
+    /// How long to wait for a reply after the request was sent, µs.
+    /// Includes SENDDELAY, the reply and one extra datagram of margin
+    pub fn reply_timeout_us(&self) -> u32 {
+        self.bit_times_us(
+            senddelay_bit_times(self.senddelay)
+                + 2 * DATAGRAM_LEN * BITS_PER_BYTE,
+        )
+    }
+
+    /// Minimal idle between two datagrams, µs
+    pub fn inter_frame_gap_us(&self) -> u32 {
+        self.bit_times_us(INTER_FRAME_GAP_BIT_TIMES)
+    }
+
+    /// Idle that resets the driver receiver, for example after a baud
+    /// rate change or a broken datagram, µs
+    pub fn bus_reset_us(&self) -> u32 {
+        self.bit_times_us(BUS_RESET_BIT_TIMES)
+    }
+}
+
+/// UART the driver talks through. Besides reads and writes the transport
+/// times the bus: reads poll [`ReadReady`] and give up after the reply
+/// timeout, every datagram is followed by the inter-frame gap. The driver
+/// waits with the shared UART borrowed (inside a critical section), so
+/// `delay_us()` must not depend on interrupts: count cycles or poll a
+/// hardware timer. [`TimedUart`] makes one from any UART and delay
+pub trait UartTransport: Read + ReadReady + Write {
+    fn delay_us(&mut self, us: u32);
+
+    /// Time replies may come later than
+    /// [`UartTiming::reply_timeout_us`], for example USB-UART adapters
+    /// passing bytes in packets, µs
+    fn reply_latency_us(&self) -> u32 {
+        0
+    }
+}
+
+/// [`UartTransport`] of an `embedded_io` UART and an `embedded_hal` delay
+pub struct TimedUart<Uart, Delay> {
+    uart: Uart,
+    delay: Delay,
+}
+
+impl<Uart, Delay> TimedUart<Uart, Delay> {
+    pub fn new(uart: Uart, delay: Delay) -> Self {
+        TimedUart { uart, delay }
+    }
+
+    pub fn inner(&self) -> &Uart {
+        &self.uart
+    }
+
+    pub fn inner_mut(&mut self) -> &mut Uart {
+        &mut self.uart
+    }
+
+    pub fn delay(&self) -> &Delay {
+        &self.delay
+    }
+
+    pub fn into_inner(self) -> (Uart, Delay) {
+        (self.uart, self.delay)
+    }
+}
+
+impl<Uart: ErrorType, Delay> ErrorType for TimedUart<Uart, Delay> {
+    type Error = Uart::Error;
+}
+
+impl<Uart: Read, Delay> Read for TimedUart<Uart, Delay> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.uart.read(buf)
+    }
+}
+
+impl<Uart: ReadReady, Delay> ReadReady for TimedUart<Uart, Delay> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        self.uart.read_ready()
+    }
+}
+
+impl<Uart: Write, Delay> Write for TimedUart<Uart, Delay> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.uart.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.uart.flush()
+    }
+}
+
+impl<Uart: Read + ReadReady + Write, Delay: DelayNs> UartTransport
+    for TimedUart<Uart, Delay>
+{
+    fn delay_us(&mut self, us: u32) {
+        self.delay.delay_us(us);
+    }
+}
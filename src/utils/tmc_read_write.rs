@@ -1,42 +1,160 @@
-use embedded_io::{Read, Write};
+use crate::utils::checksum::crc8_tmc;
+use crate::utils::timing::{UartTiming, UartTransport};
+use crate::utils::trace;
 
-// Read register (wait in while loop until response is received)
+/// Why a register read failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReadFailure {
+    /// Request could not be sent or no reply came
+    NoReply,
+    /// Reply with bad CRC or unknown register (for example two drivers
+    /// with the same address answering at once)
+    InvalidReply,
+}
+
+// Read register (wait until response is received or the reply timeout
+// of `timing` passed)
 pub fn read_reg_blocking<
     Reg: tmc2209::reg::ReadableRegister,
-    Uart: Read + Write,
+    Uart: UartTransport,
 >(
     uart: &mut Uart,
     uart_address: u8,
+    timing: &UartTiming,
 ) -> Result<Reg, ()> {
+    read_reg_checked(uart, uart_address, timing).map_err(|_| ())
+}
+
+// Same as read_reg_blocking(), but tells why the read failed
+pub fn read_reg_checked<
+    Reg: tmc2209::reg::ReadableRegister,
+    Uart: UartTransport,
+>(
+    uart: &mut Uart,
+    uart_address: u8,
+    timing: &UartTiming,
+) -> Result<Reg, ReadFailure> {
+    let start_us = trace::now_us();
+    let result = read_response::<Reg, _>(uart, uart_address, timing);
+    trace_read(uart_address, Reg::ADDRESS as u8, &result, start_us);
+    wait_inter_frame_gap(uart, timing);
+    result.map(|response| response.register::<Reg>().unwrap())
+}
+
+fn read_response<Reg: tmc2209::reg::ReadableRegister, Uart: UartTransport>(
+    uart: &mut Uart,
+    uart_address: u8,
+    timing: &UartTiming,
+) -> Result<tmc2209::ReadResponse, ReadFailure> {
     let result = tmc2209::send_read_request::<Reg, _>(uart_address, uart);
     if result.is_err() {
-        return Err(());
+        return Err(ReadFailure::NoReply);
     }
 
     // Wait for response
     let mut reader = tmc2209::Reader::default();
-    let mut buff = [0u8; 1];
-    while uart.read(&mut buff).is_ok() {
-        if let (_, Some(response)) = reader.read_response(&buff) {
-            if !response.crc_is_valid() {
-                return Err(());
-            }
+    let mut waited_us = 0;
+    while let Some(byte) = read_reply_byte(uart, timing, &mut waited_us) {
+        if let (_, Some(response)) = reader.read_response(&[byte]) {
+            return check_response(uart_address, response);
+        }
+    }
+    Err(ReadFailure::NoReply)
+}
 
-            match response.reg_addr() {
-                Ok(_) => {
-                    let reg = response.register::<Reg>().unwrap();
-                    return Ok(reg);
-                }
-                _ => return Err(()),
-            }
+// Next received byte. Polls every byte time with the transport delay and
+// gives up (None) once `waited_us` passed the reply timeout
+fn read_reply_byte<Uart: UartTransport>(
+    uart: &mut Uart,
+    timing: &UartTiming,
+    waited_us: &mut u32,
+) -> Option<u8> {
+    let timeout_us = timing.reply_timeout_us() + uart.reply_latency_us();
+    let poll_us = timing.byte_time_us();
+    while !uart.read_ready().ok()? {
+        if *waited_us > timeout_us {
+            return None;
         }
+        uart.delay_us(poll_us);
+        *waited_us += poll_us;
+    }
+    let mut buff = [0u8; 1];
+    uart.read(&mut buff).ok()?;
+    Some(buff[0])
+}
+
+fn check_response(
+    uart_address: u8,
+    response: tmc2209::ReadResponse,
+) -> Result<tmc2209::ReadResponse, ReadFailure> {
+    if !response.crc_is_valid() {
+        tmc_warn!("uart node {}: reply CRC mismatch", uart_address);
+        return Err(ReadFailure::InvalidReply);
+    }
+
+    match response.reg_addr() {
+        Ok(_) => Ok(response),
+        _ => Err(ReadFailure::InvalidReply),
+    }
+}
+
+fn trace_read(
+    uart_address: u8,
+    reg_address: u8,
+    result: &Result<tmc2209::ReadResponse, ReadFailure>,
+    start_us: u64,
+) {
+    trace_read_value(
+        uart_address,
+        reg_address,
+        result
+            .as_ref()
+            .map(|response| response.data_u32())
+            .map_err(|e| *e),
+        start_us,
+    )
+}
+
+fn trace_read_value(
+    uart_address: u8,
+    reg_address: u8,
+    result: Result<u32, ReadFailure>,
+    start_us: u64,
+) {
+    let latency_us = trace::elapsed_us(start_us);
+    match result {
+        Ok(value) => tmc_trace!(
+            "uart read node {} reg {:#x} = {:#x}, crc ok, {} us",
+            uart_address,
+            reg_address,
+            value,
+            latency_us
+        ),
+        Err(failure) => tmc_debug!(
+            "uart read node {} reg {:#x} failed: {:?}, {} us",
+            uart_address,
+            reg_address,
+            failure,
+            latency_us
+        ),
     }
-    return Err(());
 }
 
-pub fn test_uart_connection<Uart: Read + Write>(
+pub fn test_uart_connection<Uart: UartTransport>(
     uart: &mut Uart,
     uart_address: u8,
+    timing: &UartTiming,
+) -> bool {
+    let connected = drv_status_replies(uart, uart_address, timing);
+    wait_inter_frame_gap(uart, timing);
+    connected
+}
+
+fn drv_status_replies<Uart: UartTransport>(
+    uart: &mut Uart,
+    uart_address: u8,
+    timing: &UartTiming,
 ) -> bool {
     let result = tmc2209::send_read_request::<tmc2209::reg::DRV_STATUS, _>(
         uart_address,
@@ -48,9 +166,9 @@ pub fn test_uart_connection<Uart: Read + Write>(
 
     // Wait for response
     let mut reader = tmc2209::Reader::default();
-    let mut buff = [0u8; 1];
-    while uart.read(&mut buff).is_ok() {
-        if let (_, Some(response)) = reader.read_response(&buff) {
+    let mut waited_us = 0;
+    while let Some(byte) = read_reply_byte(uart, timing, &mut waited_us) {
+        if let (_, Some(response)) = reader.read_response(&[byte]) {
             if response.crc_is_valid() {
                 if let Ok(addr) = response.reg_addr() {
                     if addr == tmc2209::reg::Address::DRV_STATUS {
@@ -64,14 +182,123 @@ pub fn test_uart_connection<Uart: Read + Write>(
 }
 
 // Write register to tmc2209 driver
-pub fn write_reg<Reg: tmc2209::reg::WritableRegister, Uart: Read + Write>(
+pub fn write_reg<Reg: tmc2209::reg::WritableRegister, Uart: UartTransport>(
     uart: &mut Uart,
     uart_address: u8,
     reg: Reg,
+    timing: &UartTiming,
 ) -> Result<(), ()> {
-    let result = tmc2209::send_write_request(uart_address, reg, uart);
-    if result.is_err() {
-        return Err(());
+    let start_us = trace::now_us();
+    let value: u32 = reg.into();
+    let result =
+        tmc2209::send_write_request(uart_address, Reg::from(value), uart)
+            .map_err(|_| ());
+    trace_write(uart_address, Reg::ADDRESS as u8, value, &result, start_us);
+    wait_inter_frame_gap(uart, timing);
+    result
+}
+
+const SYNC: u8 = 0x05;
+const MASTER_ADDRESS: u8 = 0xFF;
+
+// Read register by its address, for registers chosen at runtime
+pub fn read_raw_blocking<Uart: UartTransport>(
+    uart: &mut Uart,
+    uart_address: u8,
+    reg_address: u8,
+    timing: &UartTiming,
+) -> Result<u32, ReadFailure> {
+    let start_us = trace::now_us();
+    let result = read_raw_value(uart, uart_address, reg_address, timing);
+    trace_read_value(uart_address, reg_address & 0x7F, result, start_us);
+    wait_inter_frame_gap(uart, timing);
+    result
+}
+
+fn read_raw_value<Uart: UartTransport>(
+    uart: &mut Uart,
+    uart_address: u8,
+    reg_address: u8,
+    timing: &UartTiming,
+) -> Result<u32, ReadFailure> {
+    let mut request = [SYNC, uart_address, reg_address & 0x7F, 0];
+    request[3] = crc8_tmc(&request[..3]);
+    if uart.write_all(&request).is_err() {
+        return Err(ReadFailure::NoReply);
     }
-    Ok(())
+
+    // Wait for response: sync, master address, register, data, CRC
+    let mut reply = [0u8; 8];
+    let mut len = 0;
+    let mut waited_us = 0;
+    while let Some(byte) = read_reply_byte(uart, timing, &mut waited_us) {
+        if (len == 0 && byte != SYNC) || (len == 1 && byte != MASTER_ADDRESS) {
+            len = 0;
+            continue;
+        }
+        reply[len] = byte;
+        len += 1;
+        if len == reply.len() {
+            if crc8_tmc(&reply[..7]) != reply[7] {
+                tmc_warn!("uart node {}: reply CRC mismatch", uart_address);
+                return Err(ReadFailure::InvalidReply);
+            }
+            if reply[2] != reg_address & 0x7F {
+                return Err(ReadFailure::InvalidReply);
+            }
+            return Ok(u32::from_be_bytes([
+                reply[3], reply[4], reply[5], reply[6],
+            ]));
+        }
+    }
+    Err(ReadFailure::NoReply)
+}
+
+// Write register by its address
+pub fn write_raw<Uart: UartTransport>(
+    uart: &mut Uart,
+    uart_address: u8,
+    reg_address: u8,
+    value: u32,
+    timing: &UartTiming,
+) -> Result<(), ()> {
+    let start_us = trace::now_us();
+    let mut request = [0u8; 8];
+    request[0] = SYNC;
+    request[1] = uart_address;
+    request[2] = reg_address | 0x80;
+    request[3..7].copy_from_slice(&value.to_be_bytes());
+    request[7] = crc8_tmc(&request[..7]);
+    let result = uart.write_all(&request).map_err(|_| ());
+    trace_write(uart_address, reg_address & 0x7F, value, &result, start_us);
+    wait_inter_frame_gap(uart, timing);
+    result
+}
+
+fn trace_write(
+    uart_address: u8,
+    reg_address: u8,
+    value: u32,
+    result: &Result<(), ()>,
+    start_us: u64,
+) {
+    tmc_trace!(
+        "uart write node {} reg {:#x} = {:#x}, {}, {} us",
+        uart_address,
+        reg_address,
+        value,
+        if result.is_ok() { "sent" } else { "failed" },
+        trace::elapsed_us(start_us)
+    );
+}
+
+// Let the datagram leave the wire, then keep the bus idle for the
+// inter-frame gap. write() may return before the bytes are sent, flush()
+// waits for them
+fn wait_inter_frame_gap<Uart: UartTransport>(
+    uart: &mut Uart,
+    timing: &UartTiming,
+) {
+    let _ = uart.flush();
+    uart.delay_us(timing.inter_frame_gap_us());
 }
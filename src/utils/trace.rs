@@ -0,0 +1,27 @@
+//! Clock for the optional `defmt` / `log` traces
+use core::cell::Cell;
+use critical_section::Mutex;
+
+type ClockFn = fn() -> u64;
+
+static CLOCK: Mutex<Cell<Option<ClockFn>>> = Mutex::new(Cell::new(None));
+
+/// Set microsecond clock used to report latency of UART transactions in
+/// traces. Without clock latency is traced as 0
+pub fn set_clock(now_us: fn() -> u64) {
+    critical_section::with(|cs| CLOCK.borrow(cs).set(Some(now_us)));
+}
+
+// Current time, 0 without clock (or when tracing is disabled)
+pub(crate) fn now_us() -> u64 {
+    if cfg!(any(feature = "defmt", feature = "log")) {
+        critical_section::with(|cs| CLOCK.borrow(cs).get())
+            .map_or(0, |now_us| now_us())
+    } else {
+        0
+    }
+}
+
+pub(crate) fn elapsed_us(start_us: u64) -> u64 {
+    now_us().wrapping_sub(start_us)
+}
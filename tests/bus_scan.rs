@@ -0,0 +1,153 @@
+extern crate critical_section;
+extern crate tmc2209uart;
+
+use critical_section::Mutex;
+use std::cell::RefCell;
+use tmc2209uart::structures::{
+    base_config::TMC2209_BaseConfig,
+    bus_scan::{NodeProbe, TMC2209_NodeInfo},
+    config::TMC2209_Config,
+};
+use tmc2209uart::utils::simulated_device::{SimulatedBus, SimulatedTmc2209};
+use tmc2209uart::TMC2209UART;
+
+const IFCNT: u8 = 0x02;
+const CHOPCONF: u8 = 0x6C;
+
+type SharedBus = Mutex<RefCell<Option<SimulatedBus>>>;
+
+/// Nodes 0 and 1 wired correctly, two drivers on node 2 and none on node 3
+fn connect(bus: &SharedBus) {
+    let mut simulated = SimulatedBus::new();
+    simulated.connect(0, SimulatedTmc2209::new(0));
+    let mut node_1 = SimulatedTmc2209::new(1);
+    node_1.set_register(IFCNT, 7);
+    simulated.connect(1, node_1);
+    simulated.connect(2, SimulatedTmc2209::new(2));
+    let mut node_2 = SimulatedTmc2209::new(2);
+    // Replies of both drivers differ, so they garble each other
+    node_2.set_register(IFCNT, 3);
+    simulated.connect(3, node_2);
+    critical_section::with(|cs| {
+        bus.borrow_ref_mut(cs).replace(simulated);
+    });
+}
+
+fn chopconf(bus: &SharedBus, slot: usize) -> u32 {
+    critical_section::with(|cs| {
+        let bus = bus.borrow_ref(cs);
+        bus.as_ref()
+            .unwrap()
+            .device(slot)
+            .unwrap()
+            .register(CHOPCONF)
+    })
+}
+
+static SCAN_BUS: SharedBus = Mutex::new(RefCell::new(None));
+
+#[test]
+fn scan_finds_drivers_and_collisions() {
+    connect(&SCAN_BUS);
+
+    let scan = TMC2209UART::scan_bus(&SCAN_BUS, 115_200).unwrap();
+    assert_eq!(
+        scan.nodes,
+        [
+            NodeProbe::Responding(TMC2209_NodeInfo {
+                address: 0,
+                ifcnt: 0,
+                version: 0x21,
+            }),
+            NodeProbe::Responding(TMC2209_NodeInfo {
+                address: 1,
+                ifcnt: 7,
+                version: 0x21,
+            }),
+            NodeProbe::Collision,
+            NodeProbe::NoReply,
+        ]
+    );
+    let responding: Vec<u8> =
+        scan.responding().map(|node| node.address).collect();
+    assert_eq!(responding, [0, 1]);
+    assert_eq!(scan.collisions().collect::<Vec<_>>(), [2]);
+    assert!(scan.has_collision());
+    assert_eq!(scan.probe(3), Some(NodeProbe::NoReply));
+    assert_eq!(scan.probe(4), None);
+
+    // Scanning only reads, IFCNT of the drivers is not changed
+    let again = TMC2209UART::scan_bus(&SCAN_BUS, 115_200).unwrap();
+    assert_eq!(again, scan);
+
+    static NO_BUS: SharedBus = Mutex::new(RefCell::new(None));
+    assert_eq!(TMC2209UART::scan_bus(&NO_BUS, 115_200), Err(()));
+}
+
+static ADDRESS_BUS: SharedBus = Mutex::new(RefCell::new(None));
+
+#[test]
+fn node_address_changes_only_to_single_responding_driver() {
+    connect(&ADDRESS_BUS);
+    let mut driver =
+        TMC2209UART::new(&ADDRESS_BUS, TMC2209_BaseConfig::default());
+    assert!(matches!(driver.probe(), Ok(NodeProbe::Responding(_))));
+
+    // Out of range, no driver and several drivers keep node 0
+    assert_eq!(driver.set_uart_address(4), Err(()));
+    assert_eq!(driver.set_uart_address(3), Err(()));
+    assert_eq!(driver.set_uart_address(2), Err(()));
+    assert_eq!(driver.get_base_config().uart_address, 0);
+
+    let info = driver.set_uart_address(1).unwrap();
+    assert_eq!(info.address, 1);
+    assert_eq!(info.ifcnt, 7);
+    assert_eq!(driver.get_base_config().uart_address, 1);
+    assert_eq!(
+        driver.probe(),
+        Ok(NodeProbe::Responding(TMC2209_NodeInfo {
+            address: 1,
+            ifcnt: 7,
+            version: 0x21,
+        }))
+    );
+}
+
+static CONFIG_BUS: SharedBus = Mutex::new(RefCell::new(None));
+
+#[test]
+fn config_address_change_writes_to_previous_address() {
+    connect(&CONFIG_BUS);
+    let mut driver =
+        TMC2209UART::new(&CONFIG_BUS, TMC2209_BaseConfig::default());
+    let node_1_chopconf = chopconf(&CONFIG_BUS, 1);
+
+    let config = TMC2209_Config {
+        uart_address: Some(1),
+        toff: Some(5),
+        ..Default::default()
+    };
+    driver.apply_config(&config).unwrap();
+    assert_eq!(chopconf(&CONFIG_BUS, 0) & 0x0F, 5);
+    assert_eq!(chopconf(&CONFIG_BUS, 1), node_1_chopconf);
+    assert_eq!(driver.get_base_config().uart_address, 1);
+
+    // Next config goes to the new address
+    let config = TMC2209_Config {
+        toff: Some(4),
+        ..Default::default()
+    };
+    driver.apply_config(&config).unwrap();
+    assert_eq!(chopconf(&CONFIG_BUS, 0) & 0x0F, 5);
+    assert_eq!(chopconf(&CONFIG_BUS, 1) & 0x0F, 4);
+
+    // Config not applied, the address is kept
+    critical_section::with(|cs| CONFIG_BUS.borrow_ref_mut(cs).take());
+    let config = TMC2209_Config {
+        uart_address: Some(0),
+        toff: Some(3),
+        ..Default::default()
+    };
+    assert!(driver.apply_config(&config).is_err());
+    assert_eq!(driver.get_base_config().uart_address, 1);
+}
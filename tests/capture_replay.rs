@@ -0,0 +1,129 @@
+extern crate critical_section;
+extern crate tmc2209uart;
+
+use critical_section::Mutex;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tmc2209uart::structures::{
+    base_config::TMC2209_BaseConfig, config::TMC2209_Config,
+};
+use tmc2209uart::utils::capture::{CaptureUart, CapturedByte, Direction};
+use tmc2209uart::utils::datagram::{decode_capture, DatagramKind};
+use tmc2209uart::utils::replay::ReplayUart;
+use tmc2209uart::utils::simulated_device::SimulatedTmc2209;
+use tmc2209uart::utils::timing::UartTransport;
+use tmc2209uart::TMC2209UART;
+
+const CHOPCONF: u8 = 0x6C;
+const DRV_STATUS: u8 = 0x6F;
+
+static CLOCK_US: AtomicU64 = AtomicU64::new(0);
+
+fn now_us() -> u64 {
+    CLOCK_US.fetch_add(100, Ordering::Relaxed)
+}
+
+// Open load on phase A, as reported from the field
+fn field_device() -> SimulatedTmc2209 {
+    let mut device = SimulatedTmc2209::new(0);
+    device.set_register(DRV_STATUS, 1 << 6);
+    device
+}
+
+fn session<Uart: UartTransport>(
+    driver: &mut TMC2209UART<Uart>,
+    toff: u32,
+) -> Result<bool, ()> {
+    driver
+        .apply_config(&TMC2209_Config {
+            toff: Some(toff),
+            ..Default::default()
+        })
+        .map_err(|_| ())?;
+    Ok(driver.read_drv_status()?.ola)
+}
+
+fn capture_session(
+    uart: &'static Mutex<RefCell<Option<CaptureUart<SimulatedTmc2209>>>>,
+) -> Vec<CapturedByte> {
+    critical_section::with(|cs| {
+        uart.borrow_ref_mut(cs)
+            .replace(CaptureUart::new(field_device(), now_us));
+    });
+    let mut driver = TMC2209UART::new(uart, TMC2209_BaseConfig::default());
+    assert_eq!(session(&mut driver, 5), Ok(true));
+    critical_section::with(|cs| {
+        uart.borrow_ref(cs)
+            .as_ref()
+            .unwrap()
+            .iter()
+            .copied()
+            .collect()
+    })
+}
+
+static DECODE_UART: Mutex<RefCell<Option<CaptureUart<SimulatedTmc2209>>>> =
+    Mutex::new(RefCell::new(None));
+
+#[test]
+fn captured_traffic_decodes_into_datagrams() {
+    let capture = capture_session(&DECODE_UART);
+
+    // Text log round trip
+    for captured in capture.iter() {
+        let line = captured.to_string();
+        assert_eq!(CapturedByte::parse_line(&line), Some(*captured));
+    }
+
+    let mut datagrams = decode_capture(&capture);
+    let sent: Vec<_> = datagrams
+        .by_ref()
+        .filter(|d| d.direction == Direction::Tx)
+        .collect();
+    assert_eq!(datagrams.skipped(), 0);
+    assert!(sent.iter().all(|d| d.address == 0));
+    assert_eq!(sent[0].kind, DatagramKind::ReadRequest);
+    assert_eq!(sent[0].register, CHOPCONF);
+    let write = sent
+        .iter()
+        .find(|d| d.kind == DatagramKind::WriteRequest)
+        .unwrap();
+    assert_eq!(write.register, CHOPCONF);
+    assert_eq!(write.value.unwrap() & 0x0F, 5);
+
+    let replies: Vec<_> = decode_capture(&capture)
+        .filter(|d| d.kind == DatagramKind::Reply)
+        .collect();
+    assert_eq!(replies.len(), 2);
+    assert_eq!(replies[0].register, CHOPCONF);
+    assert_eq!(replies[0].value, Some(0x1001_0053));
+    assert_eq!(replies[1].register, DRV_STATUS);
+}
+
+static REPLAY_CAPTURE: Mutex<RefCell<Option<CaptureUart<SimulatedTmc2209>>>> =
+    Mutex::new(RefCell::new(None));
+
+#[test]
+fn replay_reproduces_captured_session() {
+    let capture = capture_session(&REPLAY_CAPTURE);
+
+    let uart = Mutex::new(RefCell::new(Some(ReplayUart::new(&capture))));
+    let mut driver = TMC2209UART::new(&uart, TMC2209_BaseConfig::default());
+    assert_eq!(session(&mut driver, 5), Ok(true));
+    critical_section::with(|cs| {
+        let replay = uart.borrow_ref(cs);
+        let replay = replay.as_ref().unwrap();
+        assert!(replay.is_finished());
+        assert_eq!(replay.mismatch(), None);
+    });
+
+    // Different config sends different CHOPCONF
+    let uart = Mutex::new(RefCell::new(Some(ReplayUart::new(&capture))));
+    let mut driver = TMC2209UART::new(&uart, TMC2209_BaseConfig::default());
+    assert_eq!(session(&mut driver, 6), Err(()));
+    critical_section::with(|cs| {
+        let replay = uart.borrow_ref(cs);
+        let mismatch = replay.as_ref().unwrap().mismatch().unwrap();
+        assert_eq!(capture[mismatch.index].direction, Direction::Tx);
+    });
+}
@@ -0,0 +1,68 @@
+extern crate critical_section;
+extern crate tmc2209uart;
+
+use critical_section::Mutex;
+use std::cell::RefCell;
+use tmc2209uart::structures::{
+    base_config::TMC2209_BaseConfig, chip_identity::ChipIdentity,
+    chip_variant::ChipVariant,
+};
+use tmc2209uart::utils::simulated_device::SimulatedTmc2209;
+use tmc2209uart::TMC2209UART;
+
+const IOIN: u8 = 0x06;
+
+static DEVICE: Mutex<RefCell<Option<SimulatedTmc2209>>> =
+    Mutex::new(RefCell::new(None));
+
+fn set_ioin(value: u32) {
+    critical_section::with(|cs| {
+        let mut device = DEVICE.borrow_ref_mut(cs);
+        device.as_mut().unwrap().set_register(IOIN, value);
+    });
+}
+
+#[test]
+fn ioin_pins_and_chip_identity() {
+    critical_section::with(|cs| {
+        DEVICE.borrow_ref_mut(cs).replace(SimulatedTmc2209::new(0));
+    });
+    let mut driver = TMC2209UART::new(&DEVICE, TMC2209_BaseConfig::default());
+
+    // Power-up IOIN of the simulated TMC2209
+    assert_eq!(driver.identify(), Ok(ChipIdentity::Tmc2209));
+
+    // ENN, MS2, PDN_UART and DIR high
+    set_ioin(0x21 << 24 | 1 << 9 | 1 << 6 | 1 << 3 | 1);
+    let ioin = driver.read_ioin().unwrap();
+    assert!(ioin.enn && ioin.ms2 && ioin.pdn_uart && ioin.dir);
+    assert!(!ioin.ms1 && !ioin.diag && !ioin.step && !ioin.spread_en);
+    assert_eq!(ioin.version, 0x21);
+
+    // VERSION 0x20, bit 8 is SEL_A
+    set_ioin(0x20 << 24 | 1 << 8);
+    assert_eq!(driver.identify(), Ok(ChipIdentity::Tmc2208));
+    set_ioin(0x20 << 24);
+    assert_eq!(driver.identify(), Ok(ChipIdentity::Tmc2225));
+    set_ioin(0x30 << 24);
+    assert_eq!(
+        driver.identify(),
+        Ok(ChipIdentity::Unknown { version: 0x30 })
+    );
+}
+
+#[test]
+fn identity_matches_variant() {
+    // TMC2226 is a TMC2209 in another package
+    assert!(ChipIdentity::Tmc2209.matches(ChipVariant::Tmc2209));
+    assert!(ChipIdentity::Tmc2209.matches(ChipVariant::Tmc2226));
+    assert!(ChipIdentity::Tmc2208.matches(ChipVariant::Tmc2208));
+    assert!(ChipIdentity::Tmc2225.matches(ChipVariant::Tmc2225));
+    assert!(!ChipIdentity::Tmc2208.matches(ChipVariant::Tmc2209));
+    assert!(!ChipIdentity::Tmc2209.matches(ChipVariant::Tmc2208));
+    assert!(
+        !ChipIdentity::Unknown { version: 0x30 }.matches(ChipVariant::Tmc2209)
+    );
+    assert!(ChipIdentity::Tmc2209.is_tmc2209());
+    assert!(!ChipIdentity::Tmc2225.is_tmc2209());
+}
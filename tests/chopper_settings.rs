@@ -0,0 +1,94 @@
+extern crate tmc2209uart;
+
+use tmc2209uart::utils::calc::{
+    spreadcycle_chopper_settings, MotorParameters, SpreadCycleChopperSettings,
+};
+
+const FCLK: f32 = 12_000_000.0;
+const R_SENSE: f32 = 0.11;
+
+fn motor(inductance_mh: f32, resistance: f32) -> MotorParameters {
+    MotorParameters {
+        supply_voltage: 24.0,
+        inductance_mh,
+        resistance,
+        rms_current: 1000,
+        back_emf_constant: None,
+    }
+}
+
+// Worked through the datasheet procedure (chapter 7.1) by hand. 1000 mA
+// with 0.11 Ohm is IRUN = 17, vsense = 0: 994 mA RMS, 1.406 A peak.
+// TOFF = 3 gives t_off = (24 + 32 * 3) / 12 MHz = 10 us, f_chop = 25 kHz,
+// TBL = 2 gives t_blank = 32 / 12 MHz = 2.67 us
+#[test]
+fn typical_motor() {
+    // dI_blank = 24 V * 2.67 us / 4 mH = 16 mA,
+    // dI_sd = 2 Ohm * 1.406 A * 2 * 10 us / 4 mH = 14 mA,
+    // 30 mA / 1.406 A * 248 = 5.3, rounded up to 6 = HSTRT 6 + HEND 0
+    let settings =
+        spreadcycle_chopper_settings(&motor(4.0, 2.0), R_SENSE, FCLK).unwrap();
+    assert_eq!(
+        settings,
+        SpreadCycleChopperSettings {
+            toff: 3,
+            blank_time: 2,
+            hysteresis_start: 5, // HSTRT 6
+            hysteresis_end: 3,   // HEND 0
+            chopper_frequency: 25_000.0,
+            hysteresis_current: 34,
+        }
+    );
+}
+
+#[test]
+fn low_inductance_motor_saturates_hysteresis() {
+    // dI_blank = 64 mA, dI_sd = 28 mA: 16.2 * 1/248 of peak is more than
+    // HSTRT + HEND can do (16)
+    let settings =
+        spreadcycle_chopper_settings(&motor(1.0, 1.0), R_SENSE, FCLK).unwrap();
+    assert_eq!(settings.hysteresis_start, 7); // HSTRT 8
+    assert_eq!(settings.hysteresis_end, 11); // HEND 8
+    assert_eq!(settings.hysteresis_current, 91);
+}
+
+#[test]
+fn high_inductance_motor_gets_minimal_hysteresis() {
+    // dI_blank = 1.3 mA, no slow decay ripple without coil resistance
+    let settings =
+        spreadcycle_chopper_settings(&motor(50.0, 0.0), R_SENSE, FCLK).unwrap();
+    assert_eq!(settings.hysteresis_start, 0); // HSTRT 1
+    assert_eq!(settings.hysteresis_end, 3); // HEND 0
+}
+
+#[test]
+fn toff_follows_clock() {
+    // (16 MHz / 100 kHz - 24) / 32 = 4.25
+    let settings =
+        spreadcycle_chopper_settings(&motor(4.0, 2.0), R_SENSE, 16e6).unwrap();
+    assert_eq!(settings.toff, 4);
+    assert_eq!(settings.blank_time, 2);
+    // Slow clock would need TOFF below 2, which is not allowed with TBL = 2
+    let settings =
+        spreadcycle_chopper_settings(&motor(4.0, 2.0), R_SENSE, 4e6).unwrap();
+    assert_eq!(settings.toff, 2);
+}
+
+#[test]
+fn invalid_parameters_are_rejected() {
+    assert!(
+        spreadcycle_chopper_settings(&motor(0.0, 2.0), R_SENSE, FCLK).is_err()
+    );
+    assert!(
+        spreadcycle_chopper_settings(&motor(4.0, -1.0), R_SENSE, FCLK).is_err()
+    );
+    assert!(spreadcycle_chopper_settings(&motor(4.0, 2.0), 0.0, FCLK).is_err());
+    assert!(
+        spreadcycle_chopper_settings(&motor(4.0, 2.0), R_SENSE, 0.0).is_err()
+    );
+    let no_current = MotorParameters {
+        rms_current: 0,
+        ..motor(4.0, 2.0)
+    };
+    assert!(spreadcycle_chopper_settings(&no_current, R_SENSE, FCLK).is_err());
+}
@@ -0,0 +1,136 @@
+// Binaries exist only with `host-tools`, serial ports only on unix
+#![cfg(all(feature = "host-tools", unix))]
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+
+/// Simulator process, killed when the test ends
+struct Simulator {
+    child: Child,
+    port: String,
+}
+
+impl Simulator {
+    fn start(args: &[&str]) -> Simulator {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_tmc2209-sim"))
+            .args(args)
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("simulator starts");
+        let mut port = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut port)
+            .expect("simulator prints terminal path");
+        Simulator {
+            child,
+            port: port.trim().to_string(),
+        }
+    }
+
+    fn cli(&self, args: &[&str]) -> (bool, String) {
+        let output = Command::new(env!("CARGO_BIN_EXE_tmc2209-cli"))
+            .arg("--port")
+            .arg(&self.port)
+            .args(args)
+            .output()
+            .expect("cli runs");
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        (output.status.success(), text)
+    }
+}
+
+impl Drop for Simulator {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn commission_driver_over_pty() {
+    let sim = Simulator::start(&["--nodes", "0,2", "--set", "0x41=0x80"]);
+
+    let (ok, scan) = sim.cli(&["scan"]);
+    assert!(ok, "{}", scan);
+    assert!(scan.contains("node 0: version 0x21"), "{}", scan);
+    assert!(scan.contains("node 1: no reply"), "{}", scan);
+    assert!(scan.contains("node 2: version 0x21"), "{}", scan);
+
+    let (ok, read) = sim.cli(&["read", "chopconf"]);
+    assert!(ok, "{}", read);
+    assert!(read.contains("CHOPCONF"), "{}", read);
+    assert!(read.contains("0x10010053"), "{}", read);
+
+    let (ok, write) = sim.cli(&["--node", "2", "write", "TPWMTHRS", "500"]);
+    assert!(ok, "{}", write);
+
+    let (ok, status) = sim.cli(&["status"]);
+    assert!(ok, "{}", status);
+    assert!(status.contains("Tmc2209"), "{}", status);
+    assert!(status.contains("sg_result:   128"), "{}", status);
+
+    let (ok, monitor) = sim.cli(&["sg-monitor", "--count", "2"]);
+    assert!(ok, "{}", monitor);
+    assert_eq!(monitor.lines().count(), 3, "{}", monitor);
+
+    let (ok, _) = sim.cli(&["vactual", "2000"]);
+    assert!(ok);
+
+    let (ok, dump) = sim.cli(&["dump"]);
+    assert!(ok, "{}", dump);
+    assert!(dump.contains("PWMCONF"), "{}", dump);
+    assert!(!dump.contains("no reply"), "{}", dump);
+}
+
+#[test]
+fn apply_toml_config_over_pty() {
+    let sim = Simulator::start(&[]);
+    let path = std::env::temp_dir()
+        .join(format!("tmc2209-cli-{}.toml", std::process::id()));
+    std::fs::write(&path, "# bench motor\ntoff = 4\nmicrosteps = 16\n")
+        .unwrap();
+
+    let (ok, applied) = sim.cli(&["apply", path.to_str().unwrap()]);
+    let _ = std::fs::remove_file(&path);
+    assert!(ok, "{}", applied);
+    assert!(applied.contains("toff: 3 -> 4"), "{}", applied);
+    assert!(applied.contains("microsteps: 256 -> 16"), "{}", applied);
+
+    let (ok, read) = sim.cli(&["read", "CHOPCONF"]);
+    assert!(ok, "{}", read);
+    assert!(read.contains("0x14010054"), "{}", read);
+
+    let (ok, broken) = sim.cli(&["apply", "/nonexistent/config.toml"]);
+    assert!(!ok);
+    assert!(broken.contains("can not read"), "{}", broken);
+}
+
+#[test]
+fn apply_current_config_over_pty() {
+    let sim = Simulator::start(&[]);
+    let path = std::env::temp_dir()
+        .join(format!("tmc2209-cli-current-{}.toml", std::process::id()));
+
+    // A new process does not know the run current of the driver
+    std::fs::write(&path, "hold_current = 300\n").unwrap();
+    let (ok, refused) = sim.cli(&["apply", path.to_str().unwrap()]);
+    assert!(!ok, "{}", refused);
+    assert!(refused.contains("need rms_current"), "{}", refused);
+
+    std::fs::write(&path, "rms_current = 800\nhold_current = 400\n").unwrap();
+    let (ok, applied) = sim.cli(&["apply", path.to_str().unwrap()]);
+    let _ = std::fs::remove_file(&path);
+    assert!(ok, "{}", applied);
+    let currents = applied
+        .lines()
+        .find(|line| line.starts_with("rms_current:"))
+        .expect(&applied);
+    let milliamps: Vec<u16> = currents
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|number| number.parse().ok())
+        .collect();
+    assert_eq!(milliamps.len(), 2, "{}", currents);
+    assert!(milliamps[0].abs_diff(800) < 60, "{}", currents);
+    assert!(milliamps[1].abs_diff(400) < 60, "{}", currents);
+}
@@ -0,0 +1,233 @@
+extern crate critical_section;
+extern crate tmc2209uart;
+
+use critical_section::Mutex;
+use std::cell::RefCell;
+use tmc2209uart::structures::{
+    apply_config_error::ApplyConfigError,
+    base_config::TMC2209_BaseConfig,
+    chip_variant::ChipVariant,
+    chopper_mode::ChopperMode,
+    config::TMC2209_Config,
+    config_builder::{
+        BlankTime, Hend, Hstrt, Microsteps, Sgthrs, TMC2209_ConfigBuilder, Toff,
+    },
+    config_violations::ConfigViolation,
+};
+use tmc2209uart::utils::calc::MotorParameters;
+use tmc2209uart::utils::simulated_device::SimulatedTmc2209;
+use tmc2209uart::TMC2209UART;
+
+const CHOPCONF: u8 = 0x6C;
+const SGTHRS: u8 = 0x40;
+
+#[test]
+fn builder_sets_register_values() {
+    const TOFF: Toff = Toff::new(4);
+    let config = TMC2209_ConfigBuilder::new()
+        .rms_current(700)
+        .microsteps(Microsteps::M32)
+        .blank_time(BlankTime::Clk24)
+        .toff(TOFF)
+        .hysteresis_start(Hstrt::new(3))
+        .hysteresis_end(Hend::new(2))
+        .sgthrs(Sgthrs::new(120))
+        .chopper_mode(ChopperMode::SpreadCycle)
+        .build()
+        .unwrap();
+    assert_eq!(config.rms_current, Some(700));
+    assert_eq!(config.microsteps, Some(32));
+    assert_eq!(config.blank_time, Some(1));
+    assert_eq!(config.toff, Some(4));
+    assert_eq!(config.hysteresis_start, Some(3));
+    assert_eq!(config.hysteresis_end, Some(2));
+    assert_eq!(config.sgthrs, Some(120));
+    assert_eq!(config.en_spreadcycle, Some(true));
+    // Not set fields stay untouched
+    assert_eq!(config.tpwmthrs, None);
+
+    assert_eq!(Toff::try_new(15).map(Toff::get), Some(15));
+    assert!(Toff::try_new(16).is_none());
+    assert!(Hstrt::try_new(Hstrt::MAX + 1).is_none());
+}
+
+#[test]
+fn builder_refuses_invalid_config() {
+    let violations = TMC2209_ConfigBuilder::new()
+        .pwm_reg(0)
+        .r_sense(0.0)
+        .build()
+        .err()
+        .unwrap();
+    assert_eq!(violations.len(), 2);
+    assert_eq!(
+        violations.iter().next(),
+        Some(&ConfigViolation::OutOfRange {
+            field: "pwm_reg",
+            value: 0,
+            min: 1,
+            max: 15,
+        })
+    );
+
+    let builder = TMC2209_ConfigBuilder::new()
+        .toff(Toff::new(1))
+        .blank_time(BlankTime::Clk16);
+    let violations = builder.validate();
+    assert_eq!(violations.len(), 1);
+    assert_eq!(
+        violations.iter().next(),
+        Some(&ConfigViolation::ToffRequiresLongerBlankTime)
+    );
+}
+
+#[test]
+fn validate_reports_every_violation() {
+    assert!(TMC2209_Config::default().validate().is_empty());
+
+    let config = TMC2209_Config {
+        uart_address: Some(4),
+        toff: Some(16),
+        tpowerdown: Some(256),
+        microsteps: Some(3),
+        ihold_multiplier: Some(1.5),
+        r_sense: Some(f32::NAN),
+        hysteresis_start: Some(7),
+        hysteresis_end: Some(12),
+        ..Default::default()
+    };
+    let violations: Vec<ConfigViolation> =
+        config.validate().iter().copied().collect();
+    assert_eq!(
+        violations,
+        vec![
+            ConfigViolation::OutOfRange {
+                field: "uart_address",
+                value: 4,
+                min: 0,
+                max: 3,
+            },
+            ConfigViolation::OutOfRange {
+                field: "tpowerdown",
+                value: 256,
+                min: 0,
+                max: 255,
+            },
+            ConfigViolation::OutOfRange {
+                field: "toff",
+                value: 16,
+                min: 0,
+                max: 15,
+            },
+            ConfigViolation::InvalidMicrosteps(3),
+            ConfigViolation::InvalidValue { field: "r_sense" },
+            ConfigViolation::InvalidValue {
+                field: "ihold_multiplier"
+            },
+            ConfigViolation::HysteresisTooHigh {
+                hysteresis_start: 7,
+                hysteresis_end: 12,
+            },
+        ]
+    );
+
+    for microsteps in [0, 2, 16, 256] {
+        let config = TMC2209_Config {
+            microsteps: Some(microsteps),
+            ..Default::default()
+        };
+        assert!(config.validate().is_empty(), "{}", microsteps);
+    }
+    for microsteps in [1, 24, 512] {
+        let config = TMC2209_Config {
+            microsteps: Some(microsteps),
+            ..Default::default()
+        };
+        assert!(!config.validate().is_empty(), "{}", microsteps);
+    }
+}
+
+#[test]
+fn chopper_preset_is_valid() {
+    let motor = MotorParameters {
+        supply_voltage: 24.0,
+        inductance_mh: 4.0,
+        resistance: 2.0,
+        rms_current: 1000,
+        back_emf_constant: None,
+    };
+    let base_config = TMC2209_BaseConfig::default();
+    for mode in [
+        ChopperMode::SpreadCycle,
+        ChopperMode::StealthChop,
+        ChopperMode::Hybrid { switch_rpm: 120.0 },
+    ] {
+        let config = TMC2209_ConfigBuilder::new()
+            .chopper_preset(mode, &base_config, &motor)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(config.toff, Some(3));
+        assert_eq!(
+            config.en_spreadcycle,
+            Some(mode == ChopperMode::SpreadCycle)
+        );
+    }
+
+    let no_inductance = MotorParameters {
+        inductance_mh: 0.0,
+        ..motor
+    };
+    assert!(TMC2209_ConfigBuilder::new()
+        .chopper_preset(ChopperMode::SpreadCycle, &base_config, &no_inductance)
+        .is_err());
+}
+
+type SharedDevice = Mutex<RefCell<Option<SimulatedTmc2209>>>;
+
+fn register(device: &SharedDevice, address: u8) -> u32 {
+    critical_section::with(|cs| {
+        device.borrow_ref(cs).as_ref().unwrap().register(address)
+    })
+}
+
+static DEVICE: SharedDevice = Mutex::new(RefCell::new(None));
+
+#[test]
+fn apply_config_sends_nothing_for_refused_config() {
+    critical_section::with(|cs| {
+        DEVICE.borrow_ref_mut(cs).replace(SimulatedTmc2209::new(0));
+    });
+    let chopconf = register(&DEVICE, CHOPCONF);
+
+    let mut driver = TMC2209UART::new(&DEVICE, TMC2209_BaseConfig::default());
+    let invalid = TMC2209_Config {
+        microsteps: Some(16),
+        toff: Some(16),
+        ..Default::default()
+    };
+    match driver.apply_config(&invalid) {
+        Err(ApplyConfigError::Invalid(violations)) => {
+            assert_eq!(violations.len(), 1)
+        }
+        result => panic!("{:?}", result),
+    }
+    assert_eq!(register(&DEVICE, CHOPCONF), chopconf);
+
+    let base_config = TMC2209_BaseConfig {
+        chip_variant: ChipVariant::Tmc2208,
+        ..Default::default()
+    };
+    let mut driver = TMC2209UART::new(&DEVICE, base_config);
+    let stallguard = TMC2209_Config {
+        sgthrs: Some(100),
+        ..Default::default()
+    };
+    match driver.apply_config(&stallguard) {
+        Err(ApplyConfigError::Unsupported(violations)) => {
+            assert_eq!(violations.len(), 1)
+        }
+        result => panic!("{:?}", result),
+    }
+    assert_eq!(register(&DEVICE, SGTHRS), 0);
+}
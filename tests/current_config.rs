@@ -0,0 +1,184 @@
+extern crate critical_section;
+extern crate tmc2209uart;
+
+use critical_section::Mutex;
+use std::cell::RefCell;
+use tmc2209uart::structures::{
+    apply_config_error::ApplyConfigError, base_config::TMC2209_BaseConfig,
+    config::TMC2209_Config,
+};
+use tmc2209uart::utils::calc::{cs_to_rms_current, CurrentScaling};
+use tmc2209uart::utils::simulated_device::SimulatedTmc2209;
+use tmc2209uart::TMC2209UART;
+
+const GCONF: u8 = 0x00;
+const IHOLD_IRUN: u8 = 0x10;
+const CHOPCONF: u8 = 0x6C;
+const VSENSE: u32 = 1 << 17;
+// Not written by the driver yet, any write changes it
+const IHOLD_IRUN_UNTOUCHED: u32 = 0x0005_1F1F;
+
+type SharedDevice = Mutex<RefCell<Option<SimulatedTmc2209>>>;
+
+fn connect(device: &SharedDevice) {
+    let mut chip = SimulatedTmc2209::new(0);
+    chip.set_register(IHOLD_IRUN, IHOLD_IRUN_UNTOUCHED);
+    critical_section::with(|cs| {
+        device.borrow_ref_mut(cs).replace(chip);
+    });
+}
+
+fn register(device: &SharedDevice, address: u8) -> u32 {
+    critical_section::with(|cs| {
+        device.borrow_ref(cs).as_ref().unwrap().register(address)
+    })
+}
+
+static SCALING_DEVICE: SharedDevice = Mutex::new(RefCell::new(None));
+
+#[test]
+fn scaling_change_keeps_unknown_run_current() {
+    connect(&SCALING_DEVICE);
+    let mut driver =
+        TMC2209UART::new(&SCALING_DEVICE, TMC2209_BaseConfig::default());
+
+    let scaling = TMC2209_Config {
+        i_scale_analog: Some(true),
+        ..Default::default()
+    };
+    driver.apply_config(&scaling).unwrap();
+    assert_eq!(register(&SCALING_DEVICE, GCONF) & 0x01, 0x01);
+    // Run current was never set, IRUN = IHOLD = 0 must not be written
+    assert_eq!(register(&SCALING_DEVICE, IHOLD_IRUN), IHOLD_IRUN_UNTOUCHED);
+    assert_eq!(driver.get_saved_config().rms_current, 0);
+
+    let run_current = TMC2209_Config {
+        rms_current: Some(800),
+        ..Default::default()
+    };
+    driver.apply_config(&run_current).unwrap();
+    let irun_analog = (register(&SCALING_DEVICE, IHOLD_IRUN) >> 8) & 0x1F;
+    assert!(irun_analog > 0);
+
+    // Known run current is kept with new scaling, so IRUN changes
+    let scaling = TMC2209_Config {
+        i_scale_analog: Some(false),
+        ..Default::default()
+    };
+    driver.apply_config(&scaling).unwrap();
+    let irun_digital = (register(&SCALING_DEVICE, IHOLD_IRUN) >> 8) & 0x1F;
+    assert!(irun_digital > 0);
+    let saved_rms_current = driver.get_saved_config().rms_current;
+    assert!(
+        saved_rms_current.abs_diff(800) < 60,
+        "{}",
+        saved_rms_current
+    );
+}
+
+static HOLD_DEVICE: SharedDevice = Mutex::new(RefCell::new(None));
+
+#[test]
+fn hold_current_needs_known_run_current() {
+    connect(&HOLD_DEVICE);
+    let mut driver =
+        TMC2209UART::new(&HOLD_DEVICE, TMC2209_BaseConfig::default());
+
+    for config in [
+        TMC2209_Config {
+            hold_current: Some(300),
+            ..Default::default()
+        },
+        TMC2209_Config {
+            ihold_delay: Some(4),
+            ..Default::default()
+        },
+    ] {
+        assert_eq!(
+            driver.apply_config(&config),
+            Err(ApplyConfigError::UnknownRunCurrent)
+        );
+    }
+    assert_eq!(register(&HOLD_DEVICE, IHOLD_IRUN), IHOLD_IRUN_UNTOUCHED);
+
+    // Multiplier is kept for the next run current
+    let multiplier = TMC2209_Config {
+        ihold_multiplier: Some(0.25),
+        ..Default::default()
+    };
+    driver.apply_config(&multiplier).unwrap();
+    assert_eq!(register(&HOLD_DEVICE, IHOLD_IRUN), IHOLD_IRUN_UNTOUCHED);
+    assert_eq!(driver.get_base_config().ihold_multiplier, 0.25);
+
+    let run_current = driver.set_run_current(800).unwrap();
+    let ihold_irun = register(&HOLD_DEVICE, IHOLD_IRUN);
+    let irun = (ihold_irun >> 8) & 0x1F;
+    assert_eq!(ihold_irun & 0x1F, irun / 4);
+    assert!(run_current.abs_diff(800) < 30, "{}", run_current);
+}
+
+static KEEP_HOLD_DEVICE: SharedDevice = Mutex::new(RefCell::new(None));
+
+#[test]
+fn run_current_change_keeps_hold_current() {
+    connect(&KEEP_HOLD_DEVICE);
+    let mut driver =
+        TMC2209UART::new(&KEEP_HOLD_DEVICE, TMC2209_BaseConfig::default());
+
+    driver.set_run_current(1000).unwrap();
+    let hold_current = driver.set_hold_current(300).unwrap();
+    assert!(hold_current.abs_diff(300) < 30, "{}", hold_current);
+
+    // Same current, CS may differ a step with other vsense
+    driver.set_run_current(800).unwrap();
+    let kept = driver.get_saved_config().hold_current;
+    assert!(kept.abs_diff(hold_current) < 30, "{}", kept);
+
+    // Hold current is not kept above run current
+    let run_current = driver.set_run_current(200).unwrap();
+    assert_eq!(driver.get_saved_config().hold_current, run_current);
+    let ihold_irun = register(&KEEP_HOLD_DEVICE, IHOLD_IRUN);
+    assert_eq!(ihold_irun & 0x1F, (ihold_irun >> 8) & 0x1F);
+}
+
+static VSENSE_DEVICE: SharedDevice = Mutex::new(RefCell::new(None));
+
+#[test]
+fn raw_vsense_write_rescales_saved_currents() {
+    connect(&VSENSE_DEVICE);
+    let mut driver =
+        TMC2209UART::new(&VSENSE_DEVICE, TMC2209_BaseConfig::default());
+    let config = TMC2209_Config {
+        rms_current: Some(800),
+        hold_current: Some(400),
+        ..Default::default()
+    };
+    driver.apply_config(&config).unwrap();
+    let ihold_irun = register(&VSENSE_DEVICE, IHOLD_IRUN);
+    let irun = ((ihold_irun >> 8) & 0x1F) as u8;
+    let ihold = (ihold_irun & 0x1F) as u8;
+
+    // IRUN and IHOLD stay, full scale current changes with VSENSE
+    let chopconf = register(&VSENSE_DEVICE, CHOPCONF) ^ VSENSE;
+    driver.write_raw(CHOPCONF, chopconf).unwrap();
+    assert_eq!(register(&VSENSE_DEVICE, IHOLD_IRUN), ihold_irun);
+    let saved = driver.get_saved_config();
+    let vsense = chopconf & VSENSE != 0;
+    assert_eq!(saved.vsense, vsense);
+    let CurrentScaling {
+        r_sense,
+        analog_scale,
+    } = driver
+        .get_base_config()
+        .current_scaling(saved.i_scale_analog, saved.internal_rsense);
+    let current = |cs| {
+        (cs_to_rms_current(cs, vsense, r_sense, analog_scale) + 0.5) as u16
+    };
+    assert_eq!(saved.rms_current, current(irun));
+    assert_eq!(saved.hold_current, current(ihold));
+    assert!(
+        saved.rms_current.abs_diff(800) > 200,
+        "{}",
+        saved.rms_current
+    );
+}
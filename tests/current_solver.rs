@@ -0,0 +1,100 @@
+extern crate tmc2209uart;
+
+use tmc2209uart::utils::calc::{
+    cs_to_rms_current, internal_rsense_equivalent, solve_rms_current,
+    CurrentRange,
+};
+
+// With 0.11 Ohm full scale (CS = 31) is 1768 mA with vsense = 0 and
+// 979 mA with vsense = 1, one CS step is 55 mA and 31 mA
+const R_SENSE: f32 = 0.11;
+
+#[test]
+fn smaller_error_wins() {
+    // vsense = 0: CS 17 = 994 mA, vsense = 1 can not reach 1 A
+    let solution = solve_rms_current(1000, R_SENSE, 1.0);
+    assert_eq!((solution.cs, solution.vsense), (17, false));
+    assert_eq!(solution.rms_current, 994);
+    assert_eq!(solution.error, -6);
+    assert!((solution.error_percent + 0.6).abs() < 1e-3);
+    assert_eq!(solution.range, CurrentRange::InRange);
+
+    // vsense = 0: CS 8 = 497 mA, vsense = 1: CS 15 = 490 mA
+    let solution = solve_rms_current(500, R_SENSE, 1.0);
+    assert_eq!((solution.cs, solution.vsense), (8, false));
+    assert_eq!(solution.rms_current, 497);
+
+    // vsense = 1: CS 9 = 306 mA, vsense = 0: CS 5 = 331 mA
+    let solution = solve_rms_current(300, R_SENSE, 1.0);
+    assert_eq!((solution.cs, solution.vsense), (9, true));
+    assert_eq!(solution.error, 6);
+}
+
+#[test]
+fn close_errors_prefer_higher_cs() {
+    // vsense = 0: CS 4 = 276.2 mA is closer than vsense = 1: CS 8 =
+    // 275.4 mA, but the difference is below 1% of 276 mA
+    let solution = solve_rms_current(276, R_SENSE, 1.0);
+    assert_eq!((solution.cs, solution.vsense), (8, true));
+    assert_eq!(solution.rms_current, 275);
+
+    // 331.5 mA (CS 5) against 336.6 mA (CS 10): 2.1 mA apart, tie is 3.3
+    let solution = solve_rms_current(333, R_SENSE, 1.0);
+    assert_eq!((solution.cs, solution.vsense), (10, true));
+    assert_eq!(solution.error, 4);
+}
+
+#[test]
+fn out_of_range_requests_saturate() {
+    let minimum = cs_to_rms_current(0, true, R_SENSE, 1.0);
+    assert!((minimum - 30.6).abs() < 0.1, "{}", minimum);
+    let maximum = cs_to_rms_current(31, false, R_SENSE, 1.0);
+    assert!((maximum - 1767.8).abs() < 0.1, "{}", maximum);
+
+    let solution = solve_rms_current(31, R_SENSE, 1.0);
+    assert_eq!((solution.cs, solution.vsense), (0, true));
+    assert_eq!(solution.range, CurrentRange::InRange);
+
+    let solution = solve_rms_current(10, R_SENSE, 1.0);
+    assert_eq!((solution.cs, solution.vsense), (0, true));
+    assert_eq!(solution.rms_current, 31);
+    assert_eq!(solution.error, 21);
+    assert_eq!(solution.range, CurrentRange::BelowMinimum);
+
+    let solution = solve_rms_current(0, R_SENSE, 1.0);
+    assert_eq!(solution.range, CurrentRange::BelowMinimum);
+    assert_eq!(solution.error_percent, 0.0);
+
+    let solution = solve_rms_current(3000, R_SENSE, 1.0);
+    assert_eq!((solution.cs, solution.vsense), (31, false));
+    assert_eq!(solution.rms_current, 1768);
+    assert_eq!(solution.error, -1232);
+    assert_eq!(solution.range, CurrentRange::AboveMaximum);
+}
+
+#[test]
+fn analog_scale_reduces_full_scale() {
+    // VREF 1.25 V halves the current of each CS: vsense = 0 CS 17 is
+    // 497 mA
+    let solution = solve_rms_current(500, R_SENSE, 0.5);
+    assert_eq!((solution.cs, solution.vsense), (17, false));
+    assert_eq!(solution.rms_current, 497);
+    assert_eq!(
+        solve_rms_current(1000, R_SENSE, 0.5).range,
+        CurrentRange::AboveMaximum
+    );
+}
+
+#[test]
+fn internal_rsense_needs_rref_in_range() {
+    // 0.325 V * 13.3k / 13.3k Ohm - 20 mOhm
+    let r_sense = internal_rsense_equivalent(13_300.0).unwrap();
+    assert!((r_sense - 0.305).abs() < 1e-4, "{}", r_sense);
+    assert!(internal_rsense_equivalent(5_600.0).is_some());
+    assert!(internal_rsense_equivalent(22_000.0).is_some());
+
+    assert_eq!(internal_rsense_equivalent(0.0), None);
+    assert_eq!(internal_rsense_equivalent(1_000.0), None);
+    assert_eq!(internal_rsense_equivalent(100_000.0), None);
+    assert_eq!(internal_rsense_equivalent(f32::NAN), None);
+}
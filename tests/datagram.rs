@@ -0,0 +1,89 @@
+extern crate tmc2209uart;
+
+use tmc2209uart::utils::capture::Direction;
+use tmc2209uart::utils::checksum::crc8_tmc;
+use tmc2209uart::utils::datagram::{Datagram, DatagramDecoder, DatagramKind};
+
+const CHOPCONF: u8 = 0x6C;
+
+fn with_crc(bytes: &[u8]) -> Vec<u8> {
+    let mut datagram = bytes.to_vec();
+    datagram.push(crc8_tmc(bytes));
+    datagram
+}
+
+/// Feed bytes 10 µs apart, starting at 0
+fn decode(decoder: &mut DatagramDecoder, bytes: &[u8]) -> Vec<Datagram> {
+    bytes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, byte)| decoder.push(i as u64 * 10, *byte))
+        .collect()
+}
+
+fn write_chopconf(timestamp_us: u64) -> Datagram {
+    Datagram {
+        timestamp_us,
+        direction: Direction::Tx,
+        kind: DatagramKind::WriteRequest,
+        address: 0,
+        register: CHOPCONF,
+        value: Some(0x1001_0053),
+    }
+}
+
+#[test]
+fn stray_sync_byte_does_not_swallow_datagram() {
+    let write =
+        with_crc(&[0x05, 0x00, CHOPCONF | 0x80, 0x10, 0x01, 0x00, 0x53]);
+
+    // 0x05 before the write turns it into a read request with bad CRC
+    let mut bytes = vec![0x05];
+    bytes.extend(&write);
+    let mut decoder = DatagramDecoder::new(Direction::Tx);
+    assert_eq!(decode(&mut decoder, &bytes), [write_chopconf(10)]);
+    assert_eq!(decoder.skipped(), 1);
+
+    // Noise and a sync nibble in the upper bits of noise
+    let mut bytes = vec![0xFF, 0x35, 0x00];
+    bytes.extend(&write);
+    let mut decoder = DatagramDecoder::new(Direction::Tx);
+    assert_eq!(decode(&mut decoder, &bytes), [write_chopconf(30)]);
+    assert_eq!(decoder.skipped(), 3);
+}
+
+#[test]
+fn corrupted_datagram_is_skipped() {
+    let read = with_crc(&[0x05, 0x01, CHOPCONF]);
+    let mut corrupted =
+        with_crc(&[0x05, 0x00, CHOPCONF | 0x80, 0x10, 0x01, 0x00, 0x53]);
+    corrupted[4] ^= 0x01;
+
+    let mut bytes = corrupted;
+    bytes.extend(&read);
+    let mut decoder = DatagramDecoder::new(Direction::Tx);
+    assert_eq!(
+        decode(&mut decoder, &bytes),
+        [Datagram {
+            timestamp_us: 80,
+            direction: Direction::Tx,
+            kind: DatagramKind::ReadRequest,
+            address: 1,
+            register: CHOPCONF,
+            value: None,
+        }]
+    );
+    assert_eq!(decoder.skipped(), 8);
+
+    // Reply right after a read request
+    let reply = with_crc(&[0x05, 0xFF, CHOPCONF, 0x10, 0x01, 0x00, 0x53]);
+    let mut bytes = read.clone();
+    bytes.extend(&reply);
+    let mut decoder = DatagramDecoder::new(Direction::Rx);
+    let datagrams = decode(&mut decoder, &bytes);
+    assert_eq!(datagrams.len(), 2);
+    assert_eq!(datagrams[1].kind, DatagramKind::Reply);
+    assert_eq!(datagrams[1].timestamp_us, 40);
+    assert_eq!(datagrams[1].value, Some(0x1001_0053));
+    assert_eq!(decoder.skipped(), 0);
+}
@@ -0,0 +1,94 @@
+extern crate critical_section;
+extern crate tmc2209uart;
+
+use critical_section::Mutex;
+use std::cell::RefCell;
+use tmc2209uart::structures::{
+    base_config::TMC2209_BaseConfig, config::TMC2209_Config,
+};
+use tmc2209uart::utils::simulated_device::{SimulatedBus, SimulatedTmc2209};
+use tmc2209uart::TMC2209UART;
+
+const FACTORY_CONF: u8 = 0x07;
+
+fn bus_with_all_addresses() -> SimulatedBus {
+    let mut bus = SimulatedBus::new();
+    for address in 0..4 {
+        let mut device = SimulatedTmc2209::new(address);
+        // Different trim per chip, so reads from a wrong node are visible
+        device.set_register(FACTORY_CONF, 0x0100 | (10 + address as u32));
+        bus.connect(address as usize, device);
+    }
+    bus
+}
+
+fn factory_conf(
+    bus: &Mutex<RefCell<Option<SimulatedBus>>>,
+    slot: usize,
+) -> u32 {
+    critical_section::with(|cs| {
+        let bus = bus.borrow_ref(cs);
+        bus.as_ref()
+            .unwrap()
+            .device(slot)
+            .unwrap()
+            .register(FACTORY_CONF)
+    })
+}
+
+static WRITE_BUS: Mutex<RefCell<Option<SimulatedBus>>> =
+    Mutex::new(RefCell::new(None));
+
+#[test]
+fn factory_conf_is_written_to_configured_address() {
+    critical_section::with(|cs| {
+        WRITE_BUS
+            .borrow_ref_mut(cs)
+            .replace(bus_with_all_addresses());
+    });
+
+    for address in 1..4 {
+        let base_config = TMC2209_BaseConfig {
+            uart_address: address,
+            ..Default::default()
+        };
+        let mut driver = TMC2209UART::new(&WRITE_BUS, base_config);
+        let config = TMC2209_Config {
+            fclktrim: Some(20 + address),
+            ..Default::default()
+        };
+        driver.apply_config(&config).unwrap();
+    }
+
+    // Node 0 keeps its trim
+    assert_eq!(factory_conf(&WRITE_BUS, 0), 0x0100 | 10);
+    for address in 1..4 {
+        let value = factory_conf(&WRITE_BUS, address);
+        assert_eq!(value & 0x1F, 20 + address as u32);
+        // OTTRIM read from the same node is kept
+        assert_eq!(value & 0x0300, 0x0100);
+    }
+}
+
+static READ_BUS: Mutex<RefCell<Option<SimulatedBus>>> =
+    Mutex::new(RefCell::new(None));
+
+#[test]
+fn factory_conf_is_read_from_configured_address() {
+    critical_section::with(|cs| {
+        READ_BUS
+            .borrow_ref_mut(cs)
+            .replace(bus_with_all_addresses());
+    });
+
+    for address in 0..4 {
+        let base_config = TMC2209_BaseConfig {
+            uart_address: address,
+            ..Default::default()
+        };
+        let mut driver = TMC2209UART::new(&READ_BUS, base_config);
+        let debug_config = driver.debug_read_config_from_driver().unwrap();
+        assert_eq!(debug_config.fclktrim as u32, 10 + address as u32);
+        assert_eq!(debug_config.ottrim as u32, 1);
+    }
+}
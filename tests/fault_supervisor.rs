@@ -0,0 +1,61 @@
+extern crate critical_section;
+extern crate tmc2209uart;
+
+use critical_section::Mutex;
+use std::cell::RefCell;
+use tmc2209uart::structures::{
+    base_config::TMC2209_BaseConfig,
+    fault_supervisor::{
+        FaultClass, FaultPolicies, FaultPolicy, FaultStatus, FaultSupervisor,
+    },
+};
+use tmc2209uart::utils::simulated_device::SimulatedTmc2209;
+use tmc2209uart::TMC2209UART;
+
+const CHOPCONF: u8 = 0x6C;
+const DRV_STATUS: u8 = 0x6F;
+const S2GA: u32 = 1 << 2;
+
+type SharedDevice = Mutex<RefCell<Option<SimulatedTmc2209>>>;
+
+fn with_device<R>(
+    device: &SharedDevice,
+    f: impl FnOnce(&mut SimulatedTmc2209) -> R,
+) -> R {
+    critical_section::with(|cs| f(device.borrow_ref_mut(cs).as_mut().unwrap()))
+}
+
+static DEVICE: SharedDevice = Mutex::new(RefCell::new(None));
+
+#[test]
+fn output_is_restored_with_saved_toff() {
+    critical_section::with(|cs| {
+        DEVICE.borrow_ref_mut(cs).replace(SimulatedTmc2209::new(0));
+    });
+    let mut driver = TMC2209UART::new(&DEVICE, TMC2209_BaseConfig::default());
+    let mut faults = FaultSupervisor::new(FaultPolicies {
+        short_to_ground: FaultPolicy::DisableOutput,
+        ..Default::default()
+    });
+    with_device(&DEVICE, |device| device.set_register(DRV_STATUS, S2GA));
+    let chopconf = with_device(&DEVICE, |device| device.register(CHOPCONF));
+
+    // Saved config not loaded, TOFF to restore is unknown
+    assert_eq!(faults.poll(&mut driver, 0), Err(()));
+    assert_eq!(faults.status(), FaultStatus::Running);
+    assert_eq!(with_device(&DEVICE, |d| d.register(CHOPCONF)), chopconf);
+
+    driver.init_saved_config().unwrap();
+    assert_eq!(
+        faults.poll(&mut driver, 10),
+        Ok(FaultStatus::OutputDisabled {
+            class: FaultClass::ShortToGround
+        })
+    );
+    assert_eq!(with_device(&DEVICE, |d| d.register(CHOPCONF)) & 0x0F, 0);
+
+    with_device(&DEVICE, |device| device.set_register(DRV_STATUS, 0));
+    faults.resume(&mut driver).unwrap();
+    assert_eq!(faults.status(), FaultStatus::Running);
+    assert_eq!(with_device(&DEVICE, |d| d.register(CHOPCONF)), chopconf);
+}
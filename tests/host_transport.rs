@@ -0,0 +1,59 @@
+// Serial ports of the host tools exist only with `std` on unix
+#![cfg(all(feature = "std", unix))]
+
+extern crate tmc2209uart;
+
+use tmc2209uart::utils::host_transport::{parse_number, PortOptions};
+
+#[test]
+fn numbers_are_decimal_or_hex() {
+    assert_eq!(parse_number::<u32>("500"), Ok(500));
+    assert_eq!(parse_number::<u32>("0x1F4"), Ok(500));
+    assert_eq!(parse_number::<u8>("0xff"), Ok(255));
+    assert!(parse_number::<u8>("256").is_err());
+    assert!(parse_number::<u32>("0x").is_err());
+    assert!(parse_number::<u32>("-1").is_err());
+}
+
+#[test]
+fn port_options_from_args() {
+    let args = ["--port", "/dev/pts/3", "--baud", "0xE100", "--echo"];
+    let mut options = PortOptions::default();
+    let mut rest = Vec::new();
+    let mut args = args.iter().chain(["--node", "2", "--count"].iter());
+    while let Some(arg) = args.next() {
+        let value = |name: &str| {
+            args.next()
+                .map(|value| value.to_string())
+                .ok_or_else(|| format!("{} needs a value", name))
+        };
+        if !options.parse_arg(arg, value).unwrap() {
+            rest.push(*arg);
+        }
+    }
+    assert_eq!(options.port, "/dev/pts/3");
+    assert_eq!(options.baud_rate, 57_600);
+    assert_eq!(options.node, 2);
+    assert!(options.echo);
+    assert_eq!(rest, vec!["--count"]);
+
+    let base_config = options.base_config();
+    assert_eq!(base_config.uart_address, 2);
+    assert_eq!(base_config.baud_rate, 57_600);
+
+    let mut options = PortOptions::default();
+    assert!(options
+        .parse_arg("--node", |_| Ok("4".to_string()))
+        .is_err());
+    assert!(options
+        .parse_arg("--baud", |name| Err(format!("{} needs a value", name)))
+        .is_err());
+
+    options.port = "/nonexistent/tty".to_string();
+    let error = options.open().err().unwrap();
+    assert!(
+        error.starts_with("can not open /nonexistent/tty"),
+        "{}",
+        error
+    );
+}
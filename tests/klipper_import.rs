@@ -0,0 +1,202 @@
+// Klipper import exists only with `std`
+#![cfg(feature = "std")]
+
+extern crate tmc2209uart;
+
+use tmc2209uart::structures::klipper_import::{
+    KlipperImportError, KlipperImportOptions,
+};
+use tmc2209uart::utils::calc::{
+    rpm_to_tstep, tstep_to_rpm, velocity_to_tstep, TSTEP_MAX,
+};
+use tmc2209uart::utils::klipper_import::import_klipper_config;
+
+const PRINTER_CFG: &str = "
+[stepper_x]
+step_pin: PB13
+microsteps: 16
+rotation_distance: 40
+full_steps_per_rotation: 200
+
+[tmc2209 stepper_x]
+uart_pin: PC11
+tx_pin: PC10
+uart_address: 1
+run_current: 0.8   # A RMS
+diag_pin: ^PC0
+
+[tmc2209 stepper_y]
+uart_pin: PC11
+uart_address: 2
+run_current: 0.6
+hold_current: 0.3
+sense_resistor: 0.150
+stealthchop_threshold: 100
+driver_sgthrs: 80
+driver_toff: 4
+driver_foo: 1
+driver_coolstep_current: 7
+
+[stepper_y]
+microsteps: 32
+rotation_distance: 40
+";
+
+#[test]
+fn section_without_overrides_gets_klipper_defaults() {
+    let imports =
+        import_klipper_config(PRINTER_CFG, &KlipperImportOptions::default())
+            .unwrap();
+    assert_eq!(imports.len(), 2);
+
+    let x = &imports[0];
+    assert_eq!(x.stepper, "stepper_x");
+    assert!(x.unsupported_keys.is_empty(), "{:?}", x.unsupported_keys);
+    assert_eq!(x.base_config.uart_address, 1);
+    assert_eq!(x.base_config.full_steps_per_rotation, 200);
+
+    let config = &x.config;
+    assert_eq!(config.uart_address, Some(1));
+    assert_eq!(config.microsteps, Some(16));
+    assert_eq!(config.rms_current, Some(800));
+    // No current reduction at standstill unless hold_current is set
+    assert_eq!(config.hold_current, Some(800));
+    assert_eq!(config.ihold_multiplier, Some(1.0));
+    assert_eq!(config.interpolation, Some(true));
+    assert_eq!(config.toff, Some(3));
+    assert_eq!(config.blank_time, Some(2));
+    assert_eq!(config.hysteresis_start, Some(5));
+    assert_eq!(config.hysteresis_end, Some(0));
+    assert_eq!(config.ihold_delay, Some(8));
+    assert_eq!(config.tpowerdown, Some(20));
+    assert_eq!(config.pwm_ofs, Some(36));
+    assert_eq!(config.pwm_grad, Some(14));
+    assert_eq!(config.pwm_lim, Some(12));
+    assert_eq!(config.pwm_reg, Some(8));
+    assert_eq!(config.pwm_autoscale, Some(true));
+    assert_eq!(config.pwm_autograd, Some(true));
+    assert_eq!(config.sgthrs, Some(0));
+    // Without stealthchop_threshold Klipper runs spreadCycle only
+    assert_eq!(config.en_spreadcycle, Some(true));
+    assert_eq!(config.tpwmthrs, Some(TSTEP_MAX));
+    assert_eq!(config.tcoolthrs, Some(0));
+    // Sense resistor is not overridden
+    assert_eq!(config.r_sense, None);
+    assert_eq!(x.base_config.r_sense, 0.11);
+}
+
+#[test]
+fn section_overrides_and_unsupported_keys() {
+    let imports =
+        import_klipper_config(PRINTER_CFG, &KlipperImportOptions::default())
+            .unwrap();
+    let y = &imports[1];
+    assert_eq!(y.stepper, "stepper_y");
+    assert_eq!(
+        y.unsupported_keys,
+        vec![
+            "driver_foo".to_string(),
+            "driver_coolstep_current".to_string()
+        ]
+    );
+
+    let config = &y.config;
+    assert_eq!(config.microsteps, Some(32));
+    assert_eq!(config.rms_current, Some(600));
+    assert_eq!(config.hold_current, Some(300));
+    assert_eq!(config.ihold_multiplier, Some(0.5));
+    assert_eq!(y.base_config.ihold_multiplier, 0.5);
+    assert_eq!(config.r_sense, Some(0.15));
+    assert_eq!(y.base_config.r_sense, 0.15);
+    assert_eq!(config.sgthrs, Some(80));
+    assert_eq!(config.toff, Some(4));
+    // 100 mm/s at 40 mm per rotation
+    assert_eq!(config.en_spreadcycle, Some(false));
+    assert_eq!(
+        config.tpwmthrs,
+        Some(velocity_to_tstep(100.0, 40.0, 200, 12e6))
+    );
+}
+
+#[test]
+fn rotation_distance_option_overrides_stepper_section() {
+    let options = KlipperImportOptions {
+        rotation_distance: Some(8.0),
+        ..Default::default()
+    };
+    let imports = import_klipper_config(PRINTER_CFG, &options).unwrap();
+    assert_eq!(
+        imports[1].config.tpwmthrs,
+        Some(velocity_to_tstep(100.0, 8.0, 200, 12e6))
+    );
+}
+
+#[test]
+fn zero_stealthchop_threshold_keeps_spreadcycle() {
+    // No rotation_distance needed, the threshold is not converted
+    let disabled = "
+[tmc2209 extruder]
+uart_pin: PC11
+run_current: 0.5
+stealthchop_threshold: 0
+";
+    let imports =
+        import_klipper_config(disabled, &KlipperImportOptions::default())
+            .unwrap();
+    assert_eq!(imports[0].config.en_spreadcycle, Some(true));
+    assert_eq!(imports[0].config.tpwmthrs, Some(TSTEP_MAX));
+}
+
+#[test]
+fn import_errors() {
+    let no_rotation_distance = "
+[tmc2209 extruder]
+uart_pin: PC11
+stealthchop_threshold: 5
+";
+    assert_eq!(
+        import_klipper_config(
+            no_rotation_distance,
+            &KlipperImportOptions::default()
+        )
+        .err(),
+        Some(KlipperImportError::MissingRotationDistance {
+            stepper: "extruder".to_string()
+        })
+    );
+
+    let out_of_range = "[tmc2209 stepper_z]\nuart_pin: PC11\ndriver_toff: 16\n";
+    assert_eq!(
+        import_klipper_config(out_of_range, &KlipperImportOptions::default())
+            .err(),
+        Some(KlipperImportError::InvalidValue {
+            line: 3,
+            key: "driver_toff".to_string()
+        })
+    );
+
+    let broken = "[tmc2209 stepper_z]\nuart_pin PC11\n";
+    assert_eq!(
+        import_klipper_config(broken, &KlipperImportOptions::default()).err(),
+        Some(KlipperImportError::Syntax { line: 2 })
+    );
+}
+
+#[test]
+fn velocity_conversions() {
+    // 100 mm/s at 40 mm/rotation = 2.5 rps, 12 MHz / (2.5 * 200 * 256)
+    assert_eq!(velocity_to_tstep(100.0, 40.0, 200, 12e6), 94);
+    // 60 rpm = 1 rps, 12 MHz / (200 * 256) = 234.375
+    assert_eq!(rpm_to_tstep(60.0, 200, 12e6), 234);
+    assert_eq!(rpm_to_tstep(60.0, 400, 12e6), 117);
+
+    // Standstill and very slow velocities saturate
+    assert_eq!(velocity_to_tstep(0.0, 40.0, 200, 12e6), TSTEP_MAX);
+    assert_eq!(rpm_to_tstep(-10.0, 200, 12e6), TSTEP_MAX);
+    assert_eq!(rpm_to_tstep(0.001, 200, 12e6), TSTEP_MAX);
+
+    let rpm = tstep_to_rpm(234, 200, 12e6);
+    assert!((rpm - 60.1).abs() < 0.1, "{}", rpm);
+    assert_eq!(tstep_to_rpm(TSTEP_MAX, 200, 12e6), 0.0);
+    assert_eq!(tstep_to_rpm(0, 200, 12e6), 0.0);
+}
@@ -0,0 +1,154 @@
+extern crate critical_section;
+extern crate tmc2209uart;
+
+use critical_section::Mutex;
+use std::cell::RefCell;
+use tmc2209uart::structures::{
+    base_config::TMC2209_BaseConfig,
+    config::TMC2209_Config,
+    motion_monitor::{MotionEvent, MotionMonitor, MotionMonitorOptions},
+};
+use tmc2209uart::utils::calc::{rpm_to_tstep, TSTEP_MAX};
+use tmc2209uart::utils::simulated_device::SimulatedTmc2209;
+use tmc2209uart::TMC2209UART;
+
+const TSTEP: u8 = 0x12;
+const SG_RESULT: u8 = 0x41;
+const DRV_STATUS: u8 = 0x6F;
+const OLA: u32 = 1 << 6;
+
+type SharedDevice = Mutex<RefCell<Option<SimulatedTmc2209>>>;
+
+fn connect(device: &SharedDevice) {
+    critical_section::with(|cs| {
+        device.borrow_ref_mut(cs).replace(SimulatedTmc2209::new(0));
+    });
+}
+
+fn set_register(device: &SharedDevice, address: u8, value: u32) {
+    critical_section::with(|cs| {
+        let mut device = device.borrow_ref_mut(cs);
+        device.as_mut().unwrap().set_register(address, value);
+    });
+}
+
+/// TSTEP of the default base config (200 steps, 12 MHz) at `rpm`
+fn tstep(rpm: f32) -> u32 {
+    rpm_to_tstep(rpm, 200, 12_000_000.0)
+}
+
+/// Poll `count` times, all polls but the last must return no event
+fn poll_times(
+    monitor: &mut MotionMonitor,
+    driver: &mut TMC2209UART<SimulatedTmc2209>,
+    count: usize,
+) -> Option<MotionEvent> {
+    for _ in 1..count {
+        assert_eq!(monitor.poll(driver), Ok(None));
+    }
+    monitor.poll(driver).unwrap()
+}
+
+static OPEN_LOAD_DEVICE: SharedDevice = Mutex::new(RefCell::new(None));
+
+#[test]
+fn open_load_is_debounced_and_only_checked_while_moving() {
+    connect(&OPEN_LOAD_DEVICE);
+    let mut driver =
+        TMC2209UART::new(&OPEN_LOAD_DEVICE, TMC2209_BaseConfig::default());
+    let mut monitor = MotionMonitor::new(MotionMonitorOptions::default());
+    set_register(&OPEN_LOAD_DEVICE, TSTEP, tstep(60.0));
+    set_register(&OPEN_LOAD_DEVICE, DRV_STATUS, OLA);
+
+    // Standstill in between, the count starts again
+    assert_eq!(poll_times(&mut monitor, &mut driver, 2), None);
+    set_register(&OPEN_LOAD_DEVICE, TSTEP, TSTEP_MAX);
+    assert_eq!(poll_times(&mut monitor, &mut driver, 5), None);
+    set_register(&OPEN_LOAD_DEVICE, TSTEP, tstep(60.0));
+    assert_eq!(poll_times(&mut monitor, &mut driver, 2), None);
+    assert_eq!(
+        monitor.poll(&mut driver),
+        Ok(Some(MotionEvent::Disconnected {
+            phase_a: true,
+            phase_b: false
+        }))
+    );
+    assert!(monitor.is_disconnected());
+
+    // Above open_load_max_rpm back EMF gives false open load
+    set_register(&OPEN_LOAD_DEVICE, DRV_STATUS, 0);
+    set_register(&OPEN_LOAD_DEVICE, TSTEP, tstep(400.0));
+    assert_eq!(poll_times(&mut monitor, &mut driver, 5), None);
+    set_register(&OPEN_LOAD_DEVICE, TSTEP, tstep(60.0));
+    assert_eq!(
+        poll_times(&mut monitor, &mut driver, 3),
+        Some(MotionEvent::Reconnected)
+    );
+    assert!(!monitor.is_disconnected());
+}
+
+static STALL_DEVICE: SharedDevice = Mutex::new(RefCell::new(None));
+
+#[test]
+fn stall_is_only_checked_in_stealthchop_between_thresholds() {
+    connect(&STALL_DEVICE);
+    let mut driver =
+        TMC2209UART::new(&STALL_DEVICE, TMC2209_BaseConfig::default());
+    // StallGuard between 30 rpm (TCOOLTHRS) and 200 rpm (TPWMTHRS), stall
+    // level 2 * SGTHRS = 100
+    let config = TMC2209_Config {
+        en_spreadcycle: Some(false),
+        sgthrs: Some(50),
+        tcoolthrs: Some(tstep(30.0)),
+        tpwmthrs: Some(tstep(200.0)),
+        ..Default::default()
+    };
+    driver.apply_config(&config).unwrap();
+    let mut monitor = MotionMonitor::new(MotionMonitorOptions {
+        stall_min_rpm: 1.0,
+        ..Default::default()
+    });
+    set_register(&STALL_DEVICE, SG_RESULT, 80);
+
+    // Slower than TCOOLTHRS and faster than TPWMTHRS
+    for rpm in [20.0, 250.0] {
+        set_register(&STALL_DEVICE, TSTEP, tstep(rpm));
+        assert_eq!(poll_times(&mut monitor, &mut driver, 5), None, "{}", rpm);
+    }
+
+    set_register(&STALL_DEVICE, TSTEP, tstep(60.0));
+    match poll_times(&mut monitor, &mut driver, 3) {
+        Some(MotionEvent::Stalled {
+            sg_result,
+            velocity_rpm,
+        }) => {
+            assert_eq!(sg_result, 80);
+            assert!((velocity_rpm - 60.0).abs() < 1.0, "{}", velocity_rpm);
+        }
+        event => panic!("{:?}", event),
+    }
+    assert!(monitor.is_stalled());
+
+    // A single good sample does not clear the stall
+    set_register(&STALL_DEVICE, SG_RESULT, 300);
+    assert_eq!(monitor.poll(&mut driver), Ok(None));
+    set_register(&STALL_DEVICE, SG_RESULT, 80);
+    assert_eq!(monitor.poll(&mut driver), Ok(None));
+    set_register(&STALL_DEVICE, SG_RESULT, 300);
+    assert_eq!(
+        poll_times(&mut monitor, &mut driver, 3),
+        Some(MotionEvent::StallCleared)
+    );
+
+    // StallGuard4 does not work in spreadCycle
+    let spreadcycle = TMC2209_Config {
+        en_spreadcycle: Some(true),
+        ..Default::default()
+    };
+    driver.apply_config(&spreadcycle).unwrap();
+    set_register(&STALL_DEVICE, SG_RESULT, 0);
+    assert_eq!(poll_times(&mut monitor, &mut driver, 5), None);
+
+    monitor.reset();
+    assert!(!monitor.is_stalled() && !monitor.is_disconnected());
+}
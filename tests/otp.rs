@@ -0,0 +1,295 @@
+extern crate critical_section;
+extern crate embedded_hal;
+extern crate embedded_io;
+extern crate tmc2209uart;
+
+use critical_section::Mutex;
+use embedded_hal::delay::DelayNs;
+use embedded_io::{ErrorType, Read, ReadReady, Write};
+use std::cell::RefCell;
+use tmc2209uart::structures::{
+    base_config::TMC2209_BaseConfig,
+    otp::{
+        OtpBit, OtpConfirmToken, OtpError, OtpMode, TMC2209_Otp,
+        TMC2209_OtpPowerUpConfig, OTP_MAGIC,
+    },
+};
+use tmc2209uart::utils::capture::{CaptureUart, Direction};
+use tmc2209uart::utils::datagram::{DatagramDecoder, DatagramKind};
+use tmc2209uart::utils::simulated_device::SimulatedTmc2209;
+use tmc2209uart::utils::timing::UartTransport;
+use tmc2209uart::TMC2209UART;
+
+const OTP_PROG: u8 = 0x04;
+
+struct NoDelay;
+
+impl DelayNs for NoDelay {
+    fn delay_ns(&mut self, _ns: u32) {}
+}
+
+fn now_us() -> u64 {
+    0
+}
+
+fn token(bit: OtpBit) -> OtpConfirmToken {
+    OtpConfirmToken::new(bit, OTP_MAGIC).unwrap()
+}
+
+type CapturedDevice = Mutex<RefCell<Option<CaptureUart<SimulatedTmc2209>>>>;
+
+fn connect(uart: &CapturedDevice) {
+    critical_section::with(|cs| {
+        uart.borrow_ref_mut(cs)
+            .replace(CaptureUart::new(SimulatedTmc2209::new(0), now_us));
+    });
+}
+
+fn otp(uart: &CapturedDevice) -> [u8; 3] {
+    critical_section::with(|cs| {
+        uart.borrow_ref(cs).as_ref().unwrap().inner().otp()
+    })
+}
+
+/// Values of OTP_PROG writes sent so far
+fn otp_prog_writes(uart: &CapturedDevice) -> Vec<u32> {
+    let mut decoder = DatagramDecoder::new(Direction::Tx);
+    critical_section::with(|cs| {
+        uart.borrow_ref(cs)
+            .as_ref()
+            .unwrap()
+            .iter()
+            .filter(|captured| captured.direction == Direction::Tx)
+            .filter_map(|captured| {
+                decoder.push(captured.timestamp_us, captured.byte)
+            })
+            .filter(|datagram| {
+                datagram.kind == DatagramKind::WriteRequest
+                    && datagram.register == OTP_PROG
+            })
+            .filter_map(|datagram| datagram.value)
+            .collect()
+    })
+}
+
+#[test]
+fn programming_needs_magic_and_matching_token() {
+    assert_eq!(
+        OtpConfirmToken::new(OtpBit::TBL, 0).err(),
+        Some(OtpError::InvalidMagic)
+    );
+    assert_eq!(
+        OtpConfirmToken::new(OtpBit::TBL, OTP_MAGIC - 1).err(),
+        Some(OtpError::InvalidMagic)
+    );
+    assert_eq!(OtpBit::new(3, 0).err(), Some(OtpError::InvalidBit));
+    assert_eq!(OtpBit::new(0, 8).err(), Some(OtpError::InvalidBit));
+
+    static UART: CapturedDevice = Mutex::new(RefCell::new(None));
+    connect(&UART);
+    let mut driver = TMC2209UART::new(&UART, TMC2209_BaseConfig::default());
+    assert_eq!(
+        driver.program_otp_bit(
+            &mut NoDelay,
+            OtpBit::PWM_OFS,
+            token(OtpBit::TBL),
+            OtpMode::Program,
+        ),
+        Err(OtpError::WrongToken)
+    );
+    assert_eq!(otp(&UART), [0, 0, 0]);
+    assert!(otp_prog_writes(&UART).is_empty());
+}
+
+#[test]
+fn dry_run_writes_nothing() {
+    static UART: CapturedDevice = Mutex::new(RefCell::new(None));
+    connect(&UART);
+    let mut driver = TMC2209UART::new(&UART, TMC2209_BaseConfig::default());
+
+    let report = driver
+        .program_otp_bit(
+            &mut NoDelay,
+            OtpBit::TBL,
+            token(OtpBit::TBL),
+            OtpMode::DryRun,
+        )
+        .unwrap();
+    assert!(!report.programmed);
+    assert_eq!(report.before.bytes, [0, 0, 0]);
+    assert_eq!(report.after.bytes, [0x80, 0, 0]);
+    assert_eq!(report.power_up_config.blank_time, 1);
+
+    assert_eq!(otp(&UART), [0, 0, 0]);
+    assert!(otp_prog_writes(&UART).is_empty());
+}
+
+#[test]
+fn program_writes_otp_prog_and_verifies() {
+    static UART: CapturedDevice = Mutex::new(RefCell::new(None));
+    connect(&UART);
+    let mut driver = TMC2209UART::new(&UART, TMC2209_BaseConfig::default());
+
+    let bit = OtpBit::PWM_REG;
+    let report = driver
+        .program_otp_bit(&mut NoDelay, bit, token(bit), OtpMode::Program)
+        .unwrap();
+    // OTPBIT = 1, OTPBYTE = 2, OTPMAGIC = 0xBD
+    assert_eq!(otp_prog_writes(&UART), vec![0xBD21]);
+    assert!(report.programmed);
+    assert_eq!(report.after.bytes, [0, 0, 0x02]);
+    assert_eq!(report.power_up_config.pwm_reg, 2);
+    assert_eq!(otp(&UART), [0, 0, 0x02]);
+
+    // Bit already set, nothing to burn
+    let report = driver
+        .program_otp_bit(&mut NoDelay, bit, token(bit), OtpMode::Program)
+        .unwrap();
+    assert!(!report.programmed);
+    assert_eq!(otp_prog_writes(&UART).len(), 1);
+
+    let bit = OtpBit::new(0, 3).unwrap();
+    driver
+        .program_otp_bit(&mut NoDelay, bit, token(bit), OtpMode::Program)
+        .unwrap();
+    assert_eq!(otp_prog_writes(&UART), vec![0xBD21, 0xBD03]);
+    assert_eq!(driver.read_otp().unwrap().bytes, [0x08, 0, 0x02]);
+}
+
+/// Chip that does not burn OTP (for example supply too low): OTP_PROG
+/// write datagrams are swallowed, everything else is passed through
+struct BurnFailure {
+    device: SimulatedTmc2209,
+    pending: Vec<u8>,
+}
+
+impl ErrorType for BurnFailure {
+    type Error = <SimulatedTmc2209 as ErrorType>::Error;
+}
+
+impl Read for BurnFailure {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.device.read(buf)
+    }
+}
+
+impl ReadReady for BurnFailure {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        self.device.read_ready()
+    }
+}
+
+impl Write for BurnFailure {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.pending.extend_from_slice(buf);
+        while self.pending.len() >= 3 {
+            let is_write = self.pending[2] & 0x80 != 0;
+            let len = if is_write { 8 } else { 4 };
+            if self.pending.len() < len {
+                break;
+            }
+            let datagram: Vec<u8> = self.pending.drain(..len).collect();
+            if !(is_write && datagram[2] & 0x7F == OTP_PROG) {
+                self.device.write_all(&datagram)?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.device.flush()
+    }
+}
+
+impl UartTransport for BurnFailure {
+    fn delay_us(&mut self, _us: u32) {}
+}
+
+#[test]
+fn verify_fails_when_bit_is_not_burned() {
+    static UART: Mutex<RefCell<Option<BurnFailure>>> =
+        Mutex::new(RefCell::new(None));
+    critical_section::with(|cs| {
+        UART.borrow_ref_mut(cs).replace(BurnFailure {
+            device: SimulatedTmc2209::new(0),
+            pending: Vec::new(),
+        });
+    });
+    let mut driver = TMC2209UART::new(&UART, TMC2209_BaseConfig::default());
+
+    let bit = OtpBit::EN_SPREADCYCLE;
+    assert_eq!(
+        driver.program_otp_bit(&mut NoDelay, bit, token(bit), OtpMode::Program),
+        Err(OtpError::VerifyFailed)
+    );
+}
+
+#[test]
+fn power_up_config_decode() {
+    // Blank OTP gives datasheet reset defaults
+    assert_eq!(
+        TMC2209_Otp { bytes: [0, 0, 0] }.power_up_config(),
+        TMC2209_OtpPowerUpConfig {
+            fclktrim: 0,
+            ottrim: 0,
+            internal_rsense: false,
+            blank_time: 2,
+            pwm_grad: 14,
+            pwm_autograd: true,
+            tpwmthrs: 0,
+            pwm_ofs: 36,
+            pwm_reg: 1,
+            pwm_freq: 1,
+            ihold_delay: 1,
+            ihold: 16,
+            en_spreadcycle: false,
+        }
+    );
+
+    assert_eq!(
+        TMC2209_Otp {
+            bytes: [0xEF, 0xBF, 0xFF]
+        }
+        .power_up_config(),
+        TMC2209_OtpPowerUpConfig {
+            fclktrim: 15,
+            ottrim: 1,
+            internal_rsense: true,
+            blank_time: 1,
+            pwm_grad: 100,
+            pwm_autograd: false,
+            tpwmthrs: 800,
+            pwm_ofs: 0,
+            pwm_reg: 2,
+            pwm_freq: 2,
+            ihold_delay: 8,
+            ihold: 24,
+            en_spreadcycle: true,
+        }
+    );
+
+    // OTP1.0..3 PWM_GRAD, OTP2.3..4 IHOLDDELAY and OTP2.5..6 IHOLD are
+    // table indexes
+    let otp = TMC2209_Otp {
+        bytes: [0, 0x25, 0x28],
+    };
+    let config = otp.power_up_config();
+    assert_eq!(config.pwm_grad, 27);
+    assert_eq!(config.tpwmthrs, 200);
+    assert_eq!(config.ihold_delay, 2);
+    assert_eq!(config.ihold, 2);
+
+    // Read from the chip
+    static UART: CapturedDevice = Mutex::new(RefCell::new(None));
+    connect(&UART);
+    critical_section::with(|cs| {
+        let mut uart = UART.borrow_ref_mut(cs);
+        let device = uart.as_mut().unwrap().inner_mut();
+        device.set_otp([0x4C, 0x00, 0x80]);
+    });
+    let mut driver = TMC2209UART::new(&UART, TMC2209_BaseConfig::default());
+    let config = driver.read_otp_power_up_config().unwrap();
+    assert_eq!(config.fclktrim, 12);
+    assert!(config.internal_rsense);
+    assert!(config.en_spreadcycle);
+}
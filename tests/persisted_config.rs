@@ -0,0 +1,514 @@
+extern crate critical_section;
+extern crate embedded_storage;
+extern crate tmc2209uart;
+
+use critical_section::Mutex;
+use embedded_storage::nor_flash::{
+    ErrorType, NorFlash, NorFlashErrorKind, ReadNorFlash,
+};
+use std::cell::RefCell;
+use tmc2209uart::structures::{
+    apply_config_error::ApplyConfigError,
+    base_config::TMC2209_BaseConfig,
+    chip_variant::ChipVariant,
+    config::TMC2209_Config,
+    config_violations::ConfigViolation,
+    persisted_config::{PersistedConfigError, TMC2209_PersistedConfig},
+    saved_config::TMC2209_SavedConfig,
+};
+use tmc2209uart::utils::checksum::crc32;
+use tmc2209uart::utils::simulated_device::SimulatedTmc2209;
+use tmc2209uart::TMC2209UART;
+
+const SIZE: usize = TMC2209_PersistedConfig::ENCODED_SIZE;
+
+/// Payload length written by each version (index = version)
+const PAYLOAD_LEN: [usize; 8] = [0, 45, 51, 52, 54, 62, 66, 68];
+
+fn tuned_config() -> TMC2209_PersistedConfig {
+    let base_config = TMC2209_BaseConfig {
+        uart_address: 2,
+        r_sense: 0.15,
+        ihold_multiplier: 0.3,
+        fclk: 12_500_000.0,
+        full_steps_per_rotation: 400,
+        chip_variant: ChipVariant::Tmc2226,
+        vref: 1.8,
+        rref: 5600.0,
+        baud_rate: 230_400,
+    };
+    let mut saved_config = TMC2209_SavedConfig::new();
+    saved_config.rms_current = 850;
+    saved_config.hold_current = 300;
+    saved_config.ihold_delay = 6;
+    saved_config.microsteps = 16;
+    saved_config.interpolation = true;
+    saved_config.blank_time = 2;
+    saved_config.hysteresis_end = 3;
+    saved_config.hysteresis_start = 4;
+    saved_config.tpowerdown = 20;
+    saved_config.tpwmthrs = 0x1234;
+    saved_config.sgthrs = 90;
+    saved_config.tcoolthrs = 0xF_FFFF;
+    saved_config.pwm_ofs = 36;
+    saved_config.pwm_grad = 14;
+    saved_config.pwm_autoscale = true;
+    saved_config.senddelay = 2;
+    saved_config.semin = 5;
+    saved_config.toff = 3;
+    saved_config.shaft = true;
+    saved_config.known_registers.chopconf = true;
+    saved_config.known_registers.ihold_irun = true;
+    saved_config.known_registers.sgthrs = true;
+    TMC2209_PersistedConfig::new(&base_config, &saved_config)
+}
+
+/// Block as written by an older `version`: payload cut to the fields that
+/// version had, rest of the block erased
+fn old_block(config: &TMC2209_PersistedConfig, version: u8) -> [u8; SIZE] {
+    let mut buf = [0u8; SIZE];
+    config.encode(&mut buf);
+    let payload_len = PAYLOAD_LEN[version as usize];
+    buf[2] = version;
+    buf[3] = payload_len as u8;
+    for byte in buf[4 + payload_len..].iter_mut() {
+        *byte = 0xFF;
+    }
+    reseal(&mut buf);
+    buf
+}
+
+fn reseal(buf: &mut [u8; SIZE]) {
+    let crc = crc32(&buf[..SIZE - 4]);
+    buf[SIZE - 4..].copy_from_slice(&crc.to_le_bytes());
+}
+
+#[test]
+fn encode_decode_round_trip() {
+    let mut buf = [0u8; SIZE];
+    tuned_config().encode(&mut buf);
+    assert_eq!(&buf[0..3], &[b'T', b'9', TMC2209_PersistedConfig::VERSION]);
+    assert_eq!(buf[3] as usize, PAYLOAD_LEN[7]);
+
+    let decoded = TMC2209_PersistedConfig::decode(&buf).unwrap();
+    let base = &decoded.base_config;
+    assert_eq!(base.uart_address, 2);
+    assert_eq!(base.r_sense, 0.15);
+    assert_eq!(base.ihold_multiplier, 0.3);
+    assert_eq!(base.fclk, 12_500_000.0);
+    assert_eq!(base.full_steps_per_rotation, 400);
+    assert_eq!(base.chip_variant, ChipVariant::Tmc2226);
+    assert_eq!(base.vref, 1.8);
+    assert_eq!(base.rref, 5600.0);
+    assert_eq!(base.baud_rate, 230_400);
+
+    let saved = &decoded.saved_config;
+    assert_eq!(saved.rms_current, 850);
+    assert_eq!(saved.hold_current, 300);
+    assert_eq!(saved.ihold_delay, 6);
+    assert_eq!(saved.microsteps, 16);
+    assert!(saved.interpolation);
+    assert_eq!(saved.blank_time, 2);
+    assert_eq!(saved.hysteresis_end, 3);
+    assert_eq!(saved.hysteresis_start, 4);
+    assert_eq!(saved.tpowerdown, 20);
+    assert_eq!(saved.tpwmthrs, 0x1234);
+    assert_eq!(saved.sgthrs, 90);
+    assert_eq!(saved.tcoolthrs, 0xF_FFFF);
+    assert_eq!(saved.pwm_ofs, 36);
+    assert_eq!(saved.pwm_grad, 14);
+    assert!(saved.pwm_autoscale);
+    assert!(!saved.pwm_autograd);
+    assert_eq!(saved.senddelay, 2);
+    assert_eq!(saved.semin, 5);
+    assert_eq!(saved.toff, 3);
+    assert!(saved.shaft);
+    assert!(!saved.en_spreadcycle);
+
+    let known = &saved.known_registers;
+    assert!(known.chopconf && known.ihold_irun && known.sgthrs);
+    assert!(!known.gconf && !known.factory_conf && !known.pwmconf);
+}
+
+#[test]
+fn corrupted_block_is_rejected() {
+    let mut buf = [0u8; SIZE];
+    tuned_config().encode(&mut buf);
+
+    let mut corrupted = buf;
+    corrupted[10] ^= 0x01;
+    assert_eq!(
+        TMC2209_PersistedConfig::decode(&corrupted).err(),
+        Some(PersistedConfigError::CrcMismatch)
+    );
+
+    let mut crc_damaged = buf;
+    crc_damaged[SIZE - 1] ^= 0x80;
+    assert_eq!(
+        TMC2209_PersistedConfig::decode(&crc_damaged).err(),
+        Some(PersistedConfigError::CrcMismatch)
+    );
+
+    assert_eq!(
+        TMC2209_PersistedConfig::decode(&[0xFF; SIZE]).err(),
+        Some(PersistedConfigError::BadMagic)
+    );
+
+    let mut newer = buf;
+    newer[2] = TMC2209_PersistedConfig::VERSION + 1;
+    reseal(&mut newer);
+    assert_eq!(
+        TMC2209_PersistedConfig::decode(&newer).err(),
+        Some(PersistedConfigError::UnsupportedVersion(
+            TMC2209_PersistedConfig::VERSION + 1
+        ))
+    );
+
+    let mut too_long = buf;
+    too_long[3] = 200;
+    reseal(&mut too_long);
+    assert_eq!(
+        TMC2209_PersistedConfig::decode(&too_long).err(),
+        Some(PersistedConfigError::BadLength)
+    );
+}
+
+#[test]
+fn old_versions_are_migrated() {
+    let config = tuned_config();
+    let default_base = TMC2209_BaseConfig::default();
+
+    for version in 1..TMC2209_PersistedConfig::VERSION {
+        let decoded =
+            TMC2209_PersistedConfig::decode(&old_block(&config, version))
+                .unwrap();
+        let base = &decoded.base_config;
+        let saved = &decoded.saved_config;
+
+        // Version 1 fields are always there
+        assert_eq!(base.uart_address, 2);
+        assert_eq!(base.r_sense, 0.15);
+        assert_eq!(saved.rms_current, 850);
+        assert_eq!(saved.tcoolthrs, 0xF_FFFF);
+        assert!(saved.shaft);
+
+        let expect = |since: u8| version >= since;
+        assert_eq!(
+            base.fclk,
+            if expect(2) {
+                12_500_000.0
+            } else {
+                default_base.fclk
+            },
+            "version {}",
+            version
+        );
+        assert_eq!(
+            base.full_steps_per_rotation,
+            if expect(2) { 400 } else { 200 },
+            "version {}",
+            version
+        );
+        assert_eq!(
+            base.chip_variant,
+            if expect(3) {
+                ChipVariant::Tmc2226
+            } else {
+                default_base.chip_variant
+            },
+            "version {}",
+            version
+        );
+        assert_eq!(
+            saved.hold_current,
+            if expect(4) { 300 } else { 0 },
+            "version {}",
+            version
+        );
+        assert_eq!(
+            base.vref,
+            if expect(5) { 1.8 } else { default_base.vref },
+            "version {}",
+            version
+        );
+        assert_eq!(
+            base.rref,
+            if expect(5) { 5600.0 } else { default_base.rref },
+            "version {}",
+            version
+        );
+        assert_eq!(
+            base.baud_rate,
+            if expect(6) {
+                230_400
+            } else {
+                default_base.baud_rate
+            },
+            "version {}",
+            version
+        );
+
+        // Known registers are guessed, FACTORY_CONF is never restored
+        let known = &saved.known_registers;
+        assert!(known.gconf && known.chopconf && known.ihold_irun);
+        assert!(!known.factory_conf, "version {}", version);
+    }
+
+    // Block stored before any register was read or written
+    let mut empty = TMC2209_PersistedConfig::new(
+        &TMC2209_BaseConfig::default(),
+        &TMC2209_SavedConfig::new(),
+    );
+    empty.saved_config.known_registers.factory_conf = true;
+    let decoded =
+        TMC2209_PersistedConfig::decode(&old_block(&empty, 6)).unwrap();
+    let known = &decoded.saved_config.known_registers;
+    assert!(!known.gconf && !known.chopconf && !known.pwmconf);
+    assert!(!known.ihold_irun && !known.sgthrs && !known.factory_conf);
+}
+
+/// NOR flash in RAM, erased bytes are 0xFF and writes can only clear bits
+struct RamFlash {
+    data: [u8; 1024],
+}
+
+impl ErrorType for RamFlash {
+    type Error = NorFlashErrorKind;
+}
+
+impl ReadNorFlash for RamFlash {
+    const READ_SIZE: usize = 1;
+
+    fn read(
+        &mut self,
+        offset: u32,
+        bytes: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        let start = offset as usize;
+        let data = self
+            .data
+            .get(start..start + bytes.len())
+            .ok_or(NorFlashErrorKind::OutOfBounds)?;
+        bytes.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.data.len()
+    }
+}
+
+impl NorFlash for RamFlash {
+    const WRITE_SIZE: usize = 4;
+    const ERASE_SIZE: usize = 256;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        let data = self
+            .data
+            .get_mut(from as usize..to as usize)
+            .ok_or(NorFlashErrorKind::OutOfBounds)?;
+        data.fill(0xFF);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        let start = offset as usize;
+        let data = self
+            .data
+            .get_mut(start..start + bytes.len())
+            .ok_or(NorFlashErrorKind::OutOfBounds)?;
+        for (stored, byte) in data.iter_mut().zip(bytes) {
+            *stored &= *byte;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn flash_round_trip() {
+    let mut flash = RamFlash { data: [0; 1024] };
+    tuned_config().write_to_flash(&mut flash, 256).unwrap();
+
+    let loaded =
+        TMC2209_PersistedConfig::read_from_flash(&mut flash, 256).unwrap();
+    assert_eq!(loaded.saved_config.rms_current, 850);
+    assert_eq!(loaded.base_config.baud_rate, 230_400);
+
+    assert_eq!(
+        tuned_config().write_to_flash(&mut flash, 100).err(),
+        Some(PersistedConfigError::NotAligned)
+    );
+    // Never written
+    assert_eq!(
+        TMC2209_PersistedConfig::read_from_flash(&mut flash, 512).err(),
+        Some(PersistedConfigError::BadMagic)
+    );
+}
+
+const FACTORY_CONF: u8 = 0x07;
+const IHOLD_IRUN: u8 = 0x10;
+const TPWMTHRS: u8 = 0x13;
+const SGTHRS: u8 = 0x40;
+const CHOPCONF: u8 = 0x6C;
+const PWMCONF: u8 = 0x70;
+// Not factory trimmed, OTP is blank in SimulatedTmc2209
+const FCLKTRIM: u32 = 0x0C;
+
+type SharedDevice = Mutex<RefCell<Option<SimulatedTmc2209>>>;
+
+fn connect(device: &SharedDevice) {
+    let mut chip = SimulatedTmc2209::new(0);
+    chip.set_register(FACTORY_CONF, FCLKTRIM);
+    critical_section::with(|cs| {
+        device.borrow_ref_mut(cs).replace(chip);
+    });
+}
+
+fn register(device: &SharedDevice, address: u8) -> u32 {
+    critical_section::with(|cs| {
+        device.borrow_ref(cs).as_ref().unwrap().register(address)
+    })
+}
+
+/// Encoded and decoded again, as if read from flash at boot
+fn stored(persisted: TMC2209_PersistedConfig) -> TMC2209_PersistedConfig {
+    let mut buf = [0u8; SIZE];
+    persisted.encode(&mut buf);
+    TMC2209_PersistedConfig::decode(&buf).unwrap()
+}
+
+static UNTOUCHED_DEVICE: SharedDevice = Mutex::new(RefCell::new(None));
+
+#[test]
+fn restore_skips_registers_never_read_or_written() {
+    let saved_before_init = stored(TMC2209_PersistedConfig::new(
+        &TMC2209_BaseConfig::default(),
+        &TMC2209_SavedConfig::new(),
+    ));
+
+    connect(&UNTOUCHED_DEVICE);
+    let chopconf = register(&UNTOUCHED_DEVICE, CHOPCONF);
+    let pwmconf = register(&UNTOUCHED_DEVICE, PWMCONF);
+    let mut driver =
+        TMC2209UART::new(&UNTOUCHED_DEVICE, TMC2209_BaseConfig::default());
+    driver.restore_persisted_config(&saved_before_init).unwrap();
+
+    // TOFF, MRES and the factory clock trim are not zeroed
+    assert_eq!(register(&UNTOUCHED_DEVICE, CHOPCONF), chopconf);
+    assert_eq!(register(&UNTOUCHED_DEVICE, PWMCONF), pwmconf);
+    assert_eq!(register(&UNTOUCHED_DEVICE, FACTORY_CONF), FCLKTRIM);
+    assert_eq!(driver.get_saved_config().rms_current, 0);
+}
+
+static TUNED_DEVICE: SharedDevice = Mutex::new(RefCell::new(None));
+static RESTORED_DEVICE: SharedDevice = Mutex::new(RefCell::new(None));
+
+#[test]
+fn restore_sends_known_registers_and_base_config() {
+    connect(&TUNED_DEVICE);
+    let base_config = TMC2209_BaseConfig {
+        fclk: 12_500_000.0,
+        full_steps_per_rotation: 400,
+        chip_variant: ChipVariant::Tmc2226,
+        vref: 1.8,
+        rref: 5600.0,
+        baud_rate: 57_600,
+        ..Default::default()
+    };
+    let mut tuned = TMC2209UART::new(&TUNED_DEVICE, base_config);
+    // GCONF and FACTORY_CONF are not written, they are known from here
+    tuned.init_saved_config().unwrap();
+    let config = TMC2209_Config {
+        rms_current: Some(800),
+        hold_current: Some(400),
+        microsteps: Some(16),
+        toff: Some(4),
+        sgthrs: Some(60),
+        tpwmthrs: Some(500),
+        pwm_ofs: Some(40),
+        ..Default::default()
+    };
+    tuned.apply_config(&config).unwrap();
+    let persisted = stored(tuned.get_persisted_config());
+    assert!(persisted.saved_config.known_registers.factory_conf);
+    assert!(!persisted.saved_config.known_registers.coolconf);
+
+    // Other board: chip not trimmed yet, default base config
+    connect(&RESTORED_DEVICE);
+    critical_section::with(|cs| {
+        let mut device = RESTORED_DEVICE.borrow_ref_mut(cs);
+        device.as_mut().unwrap().set_register(FACTORY_CONF, 0);
+    });
+    let mut restored =
+        TMC2209UART::new(&RESTORED_DEVICE, TMC2209_BaseConfig::default());
+    restored.restore_persisted_config(&persisted).unwrap();
+
+    for address in [CHOPCONF, PWMCONF, IHOLD_IRUN, SGTHRS, TPWMTHRS] {
+        assert_eq!(
+            register(&RESTORED_DEVICE, address),
+            register(&TUNED_DEVICE, address),
+            "register {:#x}",
+            address
+        );
+    }
+    // Trim read from the tuned chip
+    assert_eq!(register(&RESTORED_DEVICE, FACTORY_CONF), FCLKTRIM);
+
+    let base = restored.get_base_config();
+    assert_eq!(base.fclk, 12_500_000.0);
+    assert_eq!(base.full_steps_per_rotation, 400);
+    assert_eq!(base.chip_variant, ChipVariant::Tmc2226);
+    assert_eq!(base.vref, 1.8);
+    assert_eq!(base.rref, 5600.0);
+    assert_eq!(base.baud_rate, 57_600);
+    let saved = restored.get_saved_config();
+    assert_eq!(saved.rms_current, tuned.get_saved_config().rms_current);
+    assert_eq!(saved.hold_current, tuned.get_saved_config().hold_current);
+    assert_eq!(saved.microsteps, 16);
+    assert_eq!(saved.toff, 4);
+}
+
+static REFUSED_DEVICE: SharedDevice = Mutex::new(RefCell::new(None));
+
+#[test]
+fn refused_restore_is_not_a_uart_error() {
+    // PWM_REG = 0 is out of range, validate() refuses it
+    let mut saved_config = TMC2209_SavedConfig::new();
+    saved_config.known_registers.pwmconf = true;
+    let mut flash = RamFlash { data: [0; 1024] };
+    TMC2209_PersistedConfig::new(&TMC2209_BaseConfig::default(), &saved_config)
+        .write_to_flash(&mut flash, 0)
+        .unwrap();
+
+    connect(&REFUSED_DEVICE);
+    let pwmconf = register(&REFUSED_DEVICE, PWMCONF);
+    let mut driver =
+        TMC2209UART::new(&REFUSED_DEVICE, TMC2209_BaseConfig::default());
+    match driver.restore_config_from_flash(&mut flash, 0) {
+        Err(PersistedConfigError::Apply(ApplyConfigError::Invalid(
+            violations,
+        ))) => {
+            assert!(violations.iter().any(|violation| matches!(
+                violation,
+                ConfigViolation::OutOfRange {
+                    field: "pwm_reg",
+                    ..
+                }
+            )));
+        }
+        other => panic!("{:?}", other.err()),
+    }
+    assert_eq!(register(&REFUSED_DEVICE, PWMCONF), pwmconf);
+
+    // Nothing answers on the bus
+    critical_section::with(|cs| REFUSED_DEVICE.borrow_ref_mut(cs).take());
+    let valid = TMC2209_PersistedConfig::new(
+        &TMC2209_BaseConfig::default(),
+        &TMC2209_SavedConfig::new(),
+    );
+    valid.write_to_flash(&mut flash, 0).unwrap();
+    assert_eq!(
+        driver.restore_config_from_flash(&mut flash, 0).err(),
+        Some(PersistedConfigError::Apply(ApplyConfigError::Uart))
+    );
+}
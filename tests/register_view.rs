@@ -0,0 +1,99 @@
+extern crate tmc2209uart;
+
+use tmc2209uart::structures::register_view::{
+    FieldChange, FieldValue, RegisterView,
+};
+
+const CHOPCONF: u8 = 0x6C;
+const PWM_SCALE: u8 = 0x71;
+
+#[test]
+fn signed_fields_are_shown_signed() {
+    // PWM_SCALE_AUTO = -3 (9 bit), PWM_SCALE_SUM = 90
+    let view = RegisterView::new(PWM_SCALE, 0x1FD << 16 | 90);
+    assert_eq!(view.field("PWM_SCALE_AUTO"), Some(FieldValue::Int(-3)));
+    assert_eq!(view.field("pwm_scale_sum"), Some(FieldValue::Uint(90)));
+    assert_eq!(
+        view.to_string(),
+        "PWM_SCALE = 0x01FD005A\n  PWM_SCALE_SUM=90\n  PWM_SCALE_AUTO=-3"
+    );
+
+    // Sign bit clear
+    let view = RegisterView::new(PWM_SCALE, 0x0FF << 16);
+    assert_eq!(view.field("PWM_SCALE_AUTO"), Some(FieldValue::Int(255)));
+}
+
+#[test]
+fn mres_shows_microsteps() {
+    let view =
+        |mres: u32| RegisterView::new(CHOPCONF, 0x1001_0053 | mres << 24);
+    assert_eq!(view(0).field("MRES"), Some(FieldValue::Uint(0)));
+    assert!(view(0).to_string().contains("\n  MRES=0 (256 µsteps)\n"));
+    assert!(view(4).to_string().contains("\n  MRES=4 (16 µsteps)\n"));
+    assert!(view(8).to_string().contains("\n  MRES=8 (fullstep)\n"));
+    // MRES above 8 is fullstep too
+    assert!(view(15).to_string().contains("\n  MRES=15 (fullstep)\n"));
+
+    assert_eq!(
+        view(0).to_string(),
+        "CHOPCONF = 0x10010053\n  \
+         TOFF=3 (chopper enabled)\n  \
+         HSTRT=5 (+6)\n  \
+         HEND=0 (-3)\n  \
+         TBL=2 (32 clocks)\n  \
+         VSENSE=0\n  \
+         MRES=0 (256 µsteps)\n  \
+         INTPOL=1\n  \
+         DEDGE=0\n  \
+         DISS2G=0\n  \
+         DISS2VS=0"
+    );
+}
+
+#[test]
+fn diff_lists_changed_fields_in_register_order() {
+    let before = RegisterView::new(CHOPCONF, 0x1001_0053);
+    // TOFF 3 -> 0, VSENSE on, MRES 0 -> 4
+    let after = RegisterView::new(CHOPCONF, 0x1403_0050);
+    let changes = before.diff(&after);
+    assert_eq!(changes.len(), 3);
+    assert_eq!(
+        changes.iter().copied().collect::<Vec<_>>(),
+        [
+            FieldChange {
+                field: "TOFF",
+                old: FieldValue::Uint(3),
+                new: FieldValue::Uint(0),
+            },
+            FieldChange {
+                field: "VSENSE",
+                old: FieldValue::Bool(false),
+                new: FieldValue::Bool(true),
+            },
+            FieldChange {
+                field: "MRES",
+                old: FieldValue::Uint(0),
+                new: FieldValue::Uint(4),
+            },
+        ]
+    );
+    assert_eq!(
+        changes.to_string(),
+        "TOFF: 3 -> 0\nVSENSE: 0 -> 1\nMRES: 0 -> 4"
+    );
+
+    // Signed values are compared signed
+    let changes = RegisterView::new(PWM_SCALE, 0x1FD << 16)
+        .diff(&RegisterView::new(PWM_SCALE, 0x003 << 16));
+    assert_eq!(changes.to_string(), "PWM_SCALE_AUTO: -3 -> 3");
+
+    // Same value, unknown register and bits outside fields
+    assert!(before.diff(&before).is_empty());
+    assert!(RegisterView::new(0x7F, 0)
+        .diff(&RegisterView::new(0x7F, 1))
+        .is_empty());
+    assert!(before
+        .diff(&RegisterView::new(CHOPCONF, 0x1001_0053 | 1 << 20))
+        .is_empty());
+    assert_eq!(RegisterView::address_by_name("chopconf"), Some(CHOPCONF));
+}
@@ -0,0 +1,170 @@
+extern crate critical_section;
+extern crate embedded_hal;
+extern crate tmc2209uart;
+
+use critical_section::Mutex;
+use embedded_hal::delay::DelayNs;
+use std::cell::RefCell;
+use tmc2209uart::structures::{
+    base_config::TMC2209_BaseConfig,
+    config::TMC2209_Config,
+    stealthchop_autotune::{
+        StealthChopAutotuneError, StealthChopAutotuneOptions,
+        StealthChopAutotuneResult,
+    },
+};
+use tmc2209uart::utils::simulated_device::SimulatedTmc2209;
+use tmc2209uart::TMC2209UART;
+
+const IHOLD_IRUN: u8 = 0x10;
+const VACTUAL: u8 = 0x22;
+const PWM_SCALE: u8 = 0x71;
+const PWM_AUTO: u8 = 0x72;
+
+type SharedDevice = Mutex<RefCell<Option<SimulatedTmc2209>>>;
+
+/// Samples VACTUAL and IHOLD_IRUN of the device on every wait instead of
+/// waiting
+struct WatchingDelay {
+    device: &'static SharedDevice,
+    vactual: Vec<u32>,
+    ihold_irun: Vec<u32>,
+}
+
+impl WatchingDelay {
+    fn new(device: &'static SharedDevice) -> WatchingDelay {
+        WatchingDelay {
+            device,
+            vactual: Vec::new(),
+            ihold_irun: Vec::new(),
+        }
+    }
+}
+
+impl DelayNs for WatchingDelay {
+    fn delay_ns(&mut self, _ns: u32) {
+        self.vactual.push(register(self.device, VACTUAL));
+        self.ihold_irun.push(register(self.device, IHOLD_IRUN));
+    }
+}
+
+fn register(device: &SharedDevice, address: u8) -> u32 {
+    critical_section::with(|cs| {
+        device.borrow_ref(cs).as_ref().unwrap().register(address)
+    })
+}
+
+/// Chip with PWM_OFS_AUTO = 30, PWM_GRAD_AUTO = 12, PWM_SCALE_SUM = 80 and
+/// the given 9 bit PWM_SCALE_AUTO
+fn connect(device: &SharedDevice, pwm_scale_auto: u32) {
+    let mut chip = SimulatedTmc2209::new(0);
+    chip.set_register(PWM_AUTO, 12 << 16 | 30);
+    chip.set_register(PWM_SCALE, (pwm_scale_auto & 0x1FF) << 16 | 80);
+    critical_section::with(|cs| {
+        device.borrow_ref_mut(cs).replace(chip);
+    });
+}
+
+fn stealthchop_config() -> TMC2209_Config {
+    TMC2209_Config {
+        rms_current: Some(800),
+        hold_current: Some(400),
+        en_spreadcycle: Some(false),
+        pwm_autoscale: Some(true),
+        pwm_autograd: Some(true),
+        ..Default::default()
+    }
+}
+
+static TUNED_DEVICE: SharedDevice = Mutex::new(RefCell::new(None));
+
+#[test]
+fn autotune_learns_pwm_values_and_restores_hold_current() {
+    // PWM_SCALE_AUTO = -1, within the default tolerance
+    connect(&TUNED_DEVICE, 0x1FF);
+    let mut driver =
+        TMC2209UART::new(&TUNED_DEVICE, TMC2209_BaseConfig::default());
+    driver.apply_config(&stealthchop_config()).unwrap();
+    let ihold_irun = register(&TUNED_DEVICE, IHOLD_IRUN);
+    let irun = (ihold_irun >> 8) & 0x1F;
+    assert!(ihold_irun & 0x1F < irun);
+
+    let options = StealthChopAutotuneOptions::default();
+    let mut delay = WatchingDelay::new(&TUNED_DEVICE);
+    let result = driver.autotune_stealthchop(&mut delay, &options).unwrap();
+    assert_eq!(
+        result,
+        StealthChopAutotuneResult {
+            pwm_ofs: 30,
+            pwm_grad: 12,
+            pwm_scale_sum: 80,
+            pwm_scale_auto: -1,
+        }
+    );
+
+    // Run current at standstill (AT#1) and during motion (AT#2)
+    assert!(delay.ihold_irun.iter().all(|value| value & 0x1F == irun));
+    assert_eq!(delay.vactual.first(), Some(&0));
+    assert_eq!(delay.vactual.last(), Some(&(options.velocity as u32)));
+    // Stopped, hold current back
+    assert_eq!(register(&TUNED_DEVICE, VACTUAL), 0);
+    assert_eq!(register(&TUNED_DEVICE, IHOLD_IRUN), ihold_irun);
+
+    let mut config = TMC2209_Config::default();
+    result.apply_to(&mut config);
+    assert_eq!(config.pwm_ofs, Some(30));
+    assert_eq!(config.pwm_grad, Some(12));
+}
+
+static UNSETTLED_DEVICE: SharedDevice = Mutex::new(RefCell::new(None));
+
+#[test]
+fn autotune_times_out_and_stops_motor() {
+    // PWM_SCALE_AUTO never gets close to zero
+    connect(&UNSETTLED_DEVICE, 40);
+    let mut driver =
+        TMC2209UART::new(&UNSETTLED_DEVICE, TMC2209_BaseConfig::default());
+    driver.apply_config(&stealthchop_config()).unwrap();
+    let ihold_irun = register(&UNSETTLED_DEVICE, IHOLD_IRUN);
+
+    let options = StealthChopAutotuneOptions {
+        timeout_ms: 200,
+        ..Default::default()
+    };
+    let mut delay = WatchingDelay::new(&UNSETTLED_DEVICE);
+    assert_eq!(
+        driver.autotune_stealthchop(&mut delay, &options),
+        Err(StealthChopAutotuneError::MotionTimeout)
+    );
+    assert_eq!(register(&UNSETTLED_DEVICE, VACTUAL), 0);
+    assert_eq!(register(&UNSETTLED_DEVICE, IHOLD_IRUN), ihold_irun);
+}
+
+static UNCONFIGURED_DEVICE: SharedDevice = Mutex::new(RefCell::new(None));
+
+#[test]
+fn autotune_needs_stealthchop_and_run_current() {
+    connect(&UNCONFIGURED_DEVICE, 0);
+    let mut driver =
+        TMC2209UART::new(&UNCONFIGURED_DEVICE, TMC2209_BaseConfig::default());
+    let mut delay = WatchingDelay::new(&UNCONFIGURED_DEVICE);
+    let options = StealthChopAutotuneOptions::default();
+
+    // Run current never set
+    assert_eq!(
+        driver.autotune_stealthchop(&mut delay, &options),
+        Err(StealthChopAutotuneError::NotConfigured)
+    );
+
+    let spreadcycle = TMC2209_Config {
+        en_spreadcycle: Some(true),
+        ..stealthchop_config()
+    };
+    driver.apply_config(&spreadcycle).unwrap();
+    assert_eq!(
+        driver.autotune_stealthchop(&mut delay, &options),
+        Err(StealthChopAutotuneError::NotConfigured)
+    );
+    // Nothing was moved
+    assert!(delay.vactual.is_empty());
+}
@@ -0,0 +1,56 @@
+extern crate critical_section;
+extern crate tmc2209uart;
+
+use critical_section::Mutex;
+use std::cell::RefCell;
+use tmc2209uart::structures::{
+    base_config::TMC2209_BaseConfig, telemetry::TMC2209_PhaseCurrents,
+};
+use tmc2209uart::utils::simulated_device::SimulatedTmc2209;
+use tmc2209uart::TMC2209UART;
+
+const MSCURACT: u8 = 0x6B;
+const PWM_SCALE: u8 = 0x71;
+
+static DEVICE: Mutex<RefCell<Option<SimulatedTmc2209>>> =
+    Mutex::new(RefCell::new(None));
+
+fn set_register(address: u8, value: u32) {
+    critical_section::with(|cs| {
+        let mut device = DEVICE.borrow_ref_mut(cs);
+        device.as_mut().unwrap().set_register(address, value);
+    });
+}
+
+#[test]
+fn signed_9_bit_fields_are_sign_extended() {
+    critical_section::with(|cs| {
+        DEVICE.borrow_ref_mut(cs).replace(SimulatedTmc2209::new(0));
+    });
+    let mut driver = TMC2209UART::new(&DEVICE, TMC2209_BaseConfig::default());
+
+    // CUR_A = -200, CUR_B = 150
+    set_register(MSCURACT, 150 << 16 | (-200i32 as u32 & 0x1FF));
+    assert_eq!(
+        driver.read_phase_currents(),
+        Ok(TMC2209_PhaseCurrents {
+            cur_a: -200,
+            cur_b: 150
+        })
+    );
+    // Both phases at full negative scale
+    set_register(MSCURACT, 0x101 << 16 | 0x101);
+    assert_eq!(
+        driver.read_phase_currents(),
+        Ok(TMC2209_PhaseCurrents {
+            cur_a: -255,
+            cur_b: -255
+        })
+    );
+
+    // PWM_SCALE_AUTO = -3, PWM_SCALE_SUM = 90
+    set_register(PWM_SCALE, 0x1FD << 16 | 90);
+    let pwm_scale = driver.read_pwm_scale().unwrap();
+    assert_eq!(pwm_scale.sum, 90);
+    assert_eq!(pwm_scale.auto, -3);
+}
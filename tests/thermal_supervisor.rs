@@ -0,0 +1,139 @@
+extern crate critical_section;
+extern crate tmc2209uart;
+
+use critical_section::Mutex;
+use std::cell::RefCell;
+use tmc2209uart::structures::{
+    base_config::TMC2209_BaseConfig,
+    config::TMC2209_Config,
+    drv_status::TemperatureLevel,
+    thermal_supervisor::{
+        ThermalEvent, ThermalSupervisor, ThermalSupervisorOptions,
+    },
+};
+use tmc2209uart::utils::simulated_device::SimulatedTmc2209;
+use tmc2209uart::TMC2209UART;
+
+const IHOLD_IRUN: u8 = 0x10;
+const DRV_STATUS: u8 = 0x6F;
+const T120: u32 = 1 << 8;
+const T143: u32 = 1 << 9;
+
+static DEVICE: Mutex<RefCell<Option<SimulatedTmc2209>>> =
+    Mutex::new(RefCell::new(None));
+
+fn set_drv_status(value: u32) {
+    critical_section::with(|cs| {
+        let mut device = DEVICE.borrow_ref_mut(cs);
+        device.as_mut().unwrap().set_register(DRV_STATUS, value);
+    });
+}
+
+fn ihold_irun() -> u32 {
+    critical_section::with(|cs| {
+        DEVICE.borrow_ref(cs).as_ref().unwrap().register(IHOLD_IRUN)
+    })
+}
+
+/// Run current of the event, driver rounds it to the nearest CS step
+fn event_current(event: Option<ThermalEvent>) -> u16 {
+    match event.unwrap() {
+        ThermalEvent::Derated { run_current, .. }
+        | ThermalEvent::Restoring { run_current, .. }
+        | ThermalEvent::Restored { run_current } => run_current,
+    }
+}
+
+fn assert_near(current: u16, expected: u16) {
+    assert!(
+        current.abs_diff(expected) <= 30,
+        "{} mA, expected about {} mA",
+        current,
+        expected
+    );
+}
+
+#[test]
+fn derates_with_hysteresis_and_restores_nominal_current() {
+    critical_section::with(|cs| {
+        DEVICE.borrow_ref_mut(cs).replace(SimulatedTmc2209::new(0));
+    });
+    let mut driver = TMC2209UART::new(&DEVICE, TMC2209_BaseConfig::default());
+    let config = TMC2209_Config {
+        rms_current: Some(800),
+        hold_current: Some(500),
+        ..Default::default()
+    };
+    driver.apply_config(&config).unwrap();
+    let nominal = ihold_irun();
+    let nominal_run_current = driver.get_currents().run_current;
+
+    let mut thermal = ThermalSupervisor::new(
+        &driver,
+        ThermalSupervisorOptions {
+            derate_level: TemperatureLevel::Above143,
+            restore_level: TemperatureLevel::Normal,
+            step: 200,
+            min_current: 400,
+        },
+    );
+    assert_eq!(thermal.poll(&mut driver), Ok(None));
+    assert!(!thermal.is_derated());
+
+    // One step per poll down to min_current
+    set_drv_status(T143 | T120);
+    let event = thermal.poll(&mut driver).unwrap();
+    assert!(matches!(
+        event,
+        Some(ThermalEvent::Derated {
+            level: TemperatureLevel::Above143,
+            ..
+        })
+    ));
+    assert_near(event_current(event), nominal_run_current - 200);
+    assert_eq!(thermal.level(), TemperatureLevel::Above143);
+    assert!(thermal.is_derated());
+    assert_near(driver.get_currents().run_current, nominal_run_current - 200);
+
+    let event = thermal.poll(&mut driver).unwrap();
+    assert_near(event_current(event), 400);
+    assert_eq!(thermal.run_current(), 400);
+    // Hold current is never above the reduced run current
+    let currents = driver.get_currents();
+    assert!(currents.hold_current <= currents.run_current);
+    assert_eq!(thermal.poll(&mut driver), Ok(None));
+
+    // Between restore and derate level the current is kept
+    set_drv_status(T120);
+    assert_eq!(thermal.poll(&mut driver), Ok(None));
+    assert_eq!(thermal.level(), TemperatureLevel::Above120);
+    assert_eq!(thermal.run_current(), 400);
+
+    set_drv_status(0);
+    let event = thermal.poll(&mut driver).unwrap();
+    assert!(matches!(
+        event,
+        Some(ThermalEvent::Restoring {
+            level: TemperatureLevel::Normal,
+            ..
+        })
+    ));
+    assert_near(event_current(event), 600);
+    assert!(matches!(
+        thermal.poll(&mut driver),
+        Ok(Some(ThermalEvent::Restored { .. }))
+    ));
+    assert!(!thermal.is_derated());
+    assert_eq!(thermal.run_current(), nominal_run_current);
+    // Same IRUN and IHOLD as before derating
+    assert_eq!(ihold_irun(), nominal);
+    assert_eq!(thermal.poll(&mut driver), Ok(None));
+
+    // New nominal current is sent even if run current stays the same
+    thermal.set_nominal_current(nominal_run_current, 300);
+    assert!(matches!(
+        thermal.poll(&mut driver),
+        Ok(Some(ThermalEvent::Restored { .. }))
+    ));
+    assert_near(driver.get_currents().hold_current, 300);
+}
@@ -0,0 +1,155 @@
+extern crate tmc2209;
+extern crate tmc2209uart;
+
+#[cfg(feature = "log")]
+extern crate log;
+
+use std::cell::Cell;
+use tmc2209uart::utils::capture::{CaptureUart, Direction};
+use tmc2209uart::utils::datagram::{decode_capture, Datagram, DatagramKind};
+use tmc2209uart::utils::simulated_device::SimulatedTmc2209;
+use tmc2209uart::utils::timing::UartTiming;
+use tmc2209uart::utils::{tmc_read_write, trace};
+
+const CHOPCONF: u8 = 0x6C;
+
+thread_local! {
+    // Per test, tests run in parallel and share the trace clock
+    static CLOCK_US: Cell<u64> = const { Cell::new(0) };
+}
+
+fn now_us() -> u64 {
+    CLOCK_US.with(|clock| clock.replace(clock.get() + 100))
+}
+
+fn capture_time() -> u64 {
+    0
+}
+
+fn sent(uart: &CaptureUart<SimulatedTmc2209>) -> Vec<Datagram> {
+    decode_capture(uart.iter())
+        .filter(|datagram| datagram.direction == Direction::Tx)
+        .collect()
+}
+
+#[test]
+fn register_writes_send_one_datagram() {
+    trace::set_clock(now_us);
+    let timing = UartTiming::new(115_200, 0);
+    let mut uart = CaptureUart::new(SimulatedTmc2209::new(2), capture_time);
+
+    let chopconf = tmc2209::reg::CHOPCONF::from(0x1001_0053);
+    tmc_read_write::write_reg(&mut uart, 2, chopconf, &timing).unwrap();
+    tmc_read_write::write_raw(&mut uart, 2, CHOPCONF, 0x1401_0055, &timing)
+        .unwrap();
+    let write = |value| Datagram {
+        timestamp_us: 0,
+        direction: Direction::Tx,
+        kind: DatagramKind::WriteRequest,
+        address: 2,
+        register: CHOPCONF,
+        value: Some(value),
+    };
+    assert_eq!(sent(&uart), [write(0x1001_0053), write(0x1401_0055)]);
+    assert_eq!(uart.inner().register(CHOPCONF), 0x1401_0055);
+
+    // Reads still work with the clock set
+    uart.clear();
+    assert_eq!(
+        tmc_read_write::read_raw_blocking(&mut uart, 2, CHOPCONF, &timing),
+        Ok(0x1401_0055)
+    );
+    assert_eq!(sent(&uart).len(), 1);
+    assert_eq!(sent(&uart)[0].kind, DatagramKind::ReadRequest);
+}
+
+#[cfg(not(any(feature = "defmt", feature = "log")))]
+#[test]
+fn clock_is_not_read_without_tracing() {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static UNUSED_US: AtomicU64 = AtomicU64::new(0);
+    fn unused_clock() -> u64 {
+        UNUSED_US.fetch_add(1, Ordering::Relaxed)
+    }
+
+    trace::set_clock(unused_clock);
+    let timing = UartTiming::new(115_200, 0);
+    let mut device = SimulatedTmc2209::new(0);
+    tmc_read_write::write_raw(&mut device, 0, CHOPCONF, 5, &timing).unwrap();
+    assert_eq!(
+        tmc_read_write::read_raw_blocking(&mut device, 0, CHOPCONF, &timing),
+        Ok(5)
+    );
+    assert_eq!(UNUSED_US.load(Ordering::Relaxed), 0);
+    trace::set_clock(now_us);
+}
+
+#[cfg(feature = "log")]
+mod log_records {
+    use super::*;
+    use std::sync::Mutex;
+    use tmc2209uart::utils::tmc_read_write::ReadFailure;
+
+    static RECORDS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct Recorder;
+
+    impl log::Log for Recorder {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            let line = format!("{} {}", record.level(), record.args());
+            RECORDS.lock().unwrap().push(line);
+        }
+
+        fn flush(&self) {}
+    }
+
+    /// Records of node 3, other tests use other nodes
+    fn node_3_records() -> Vec<String> {
+        let records = RECORDS.lock().unwrap();
+        records
+            .iter()
+            .filter(|line| line.contains("node 3 "))
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn transactions_are_traced_with_latency() {
+        static LOGGER: Recorder = Recorder;
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+        trace::set_clock(now_us);
+        let timing = UartTiming::new(115_200, 0);
+        let mut device = SimulatedTmc2209::new(3);
+
+        let chopconf = tmc2209::reg::CHOPCONF::from(0x1001_0053);
+        tmc_read_write::write_reg(&mut device, 3, chopconf, &timing).unwrap();
+        tmc_read_write::read_raw_blocking(&mut device, 3, CHOPCONF, &timing)
+            .unwrap();
+        // Nobody answers on node 1
+        assert_eq!(
+            tmc_read_write::read_raw_blocking(
+                &mut device,
+                1,
+                CHOPCONF,
+                &timing
+            ),
+            Err(ReadFailure::NoReply)
+        );
+        assert_eq!(
+            node_3_records(),
+            [
+                "TRACE uart write node 3 reg 0x6c = 0x10010053, sent, 100 us",
+                "TRACE uart read node 3 reg 0x6c = 0x10010053, crc ok, 100 us",
+            ]
+        );
+        let records = RECORDS.lock().unwrap();
+        assert!(records.iter().any(|line| line
+            == "DEBUG uart read node 1 reg 0x6c failed: NoReply, 100 us"));
+    }
+}
@@ -0,0 +1,241 @@
+extern crate critical_section;
+extern crate embedded_hal;
+extern crate tmc2209uart;
+
+use critical_section::Mutex;
+use embedded_hal::delay::DelayNs;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tmc2209uart::structures::{
+    base_config::TMC2209_BaseConfig, config::TMC2209_Config,
+};
+use tmc2209uart::utils::capture::{CaptureUart, Direction};
+use tmc2209uart::utils::datagram::{Datagram, DatagramDecoder, DatagramKind};
+use tmc2209uart::utils::simulated_device::SimulatedTmc2209;
+use tmc2209uart::utils::timing::{
+    senddelay_bit_times, TimedUart, UartTiming, DEFAULT_BAUD_RATES,
+};
+use tmc2209uart::TMC2209UART;
+
+/// Counts waited µs instead of waiting
+struct CountingDelay {
+    waited_us: u32,
+}
+
+impl DelayNs for CountingDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.waited_us += ns.div_ceil(1000);
+    }
+}
+
+static CLOCK_US: AtomicU64 = AtomicU64::new(0);
+
+fn now_us() -> u64 {
+    CLOCK_US.load(Ordering::Relaxed)
+}
+
+/// Advances the capture clock instead of waiting, the wire takes no time
+struct ClockDelay;
+
+impl DelayNs for ClockDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        CLOCK_US.fetch_add(ns.div_ceil(1000) as u64, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn senddelay_and_reply_timeout() {
+    assert_eq!(senddelay_bit_times(0), 8);
+    assert_eq!(senddelay_bit_times(1), 8);
+    assert_eq!(senddelay_bit_times(2), 24);
+    assert_eq!(senddelay_bit_times(3), 24);
+    assert_eq!(senddelay_bit_times(15), 120);
+    // Only 4 bits of SENDDELAY are used
+    assert_eq!(senddelay_bit_times(0x12), 24);
+
+    // SENDDELAY + reply + one datagram of margin: 8 + 160 bit times
+    let timing = UartTiming::new(115_200, 0);
+    assert_eq!(timing.reply_timeout_us(), 1459);
+    assert_eq!(timing.read_time_us(), 1112);
+    assert_eq!(timing.write_time_us(), 695);
+    assert_eq!(timing.inter_frame_gap_us(), 105);
+    // 120 + 160 bit times
+    assert_eq!(UartTiming::new(115_200, 15).reply_timeout_us(), 2431);
+    // 8 + 160 bit times at 9600 baud, rounded up
+    assert_eq!(UartTiming::new(9_600, 0).reply_timeout_us(), 17_500);
+}
+
+type CountingUart = TimedUart<SimulatedTmc2209, CountingDelay>;
+
+fn counting_uart(address: u8) -> CountingUart {
+    TimedUart::new(
+        SimulatedTmc2209::new(address),
+        CountingDelay { waited_us: 0 },
+    )
+}
+
+/// µs waited by the transport
+fn transport_waited_us(uart: &Mutex<RefCell<Option<CountingUart>>>) -> u32 {
+    critical_section::with(|cs| {
+        uart.borrow_ref(cs).as_ref().unwrap().delay().waited_us
+    })
+}
+
+static DETECT_DEVICE: Mutex<RefCell<Option<CountingUart>>> =
+    Mutex::new(RefCell::new(None));
+
+#[test]
+fn baud_rate_detection() {
+    critical_section::with(|cs| {
+        DETECT_DEVICE.borrow_ref_mut(cs).replace(counting_uart(2));
+    });
+    let base_config = TMC2209_BaseConfig {
+        uart_address: 1,
+        baud_rate: 9_600,
+        ..Default::default()
+    };
+    let mut driver = TMC2209UART::new(&DETECT_DEVICE, base_config);
+
+    // No node at address 1, every rate times out
+    let mut delay = CountingDelay { waited_us: 0 };
+    let mut tried = Vec::new();
+    let result =
+        driver.detect_baud_rate(&mut delay, &DEFAULT_BAUD_RATES, |_, rate| {
+            tried.push(rate);
+            Ok(())
+        });
+    assert_eq!(result, Err(()));
+    assert_eq!(tried, DEFAULT_BAUD_RATES);
+    // Bus reset outside the critical section, reply timeout by the
+    // transport
+    let bus_reset_us: u32 = DEFAULT_BAUD_RATES
+        .iter()
+        .map(|rate| UartTiming::new(*rate, 0).bus_reset_us())
+        .sum();
+    assert_eq!(delay.waited_us, bus_reset_us);
+    let min_waited_us: u32 = DEFAULT_BAUD_RATES
+        .iter()
+        .map(|rate| UartTiming::new(*rate, 0).reply_timeout_us())
+        .sum();
+    let waited_us = transport_waited_us(&DETECT_DEVICE);
+    assert!(waited_us >= min_waited_us, "{}", waited_us);
+    assert_eq!(driver.get_base_config().baud_rate, 9_600);
+
+    // Host UART that can not switch
+    driver.set_uart_address(2).unwrap();
+    assert_eq!(
+        driver.detect_baud_rate(&mut delay, &[250_000], |_, _| Err(())),
+        Err(())
+    );
+
+    // The first rate answered is stored
+    let mut tried = Vec::new();
+    let result =
+        driver.detect_baud_rate(&mut delay, &[230_400, 57_600], |_, rate| {
+            tried.push(rate);
+            Ok(())
+        });
+    assert_eq!(result, Ok(230_400));
+    assert_eq!(tried, vec![230_400]);
+    assert_eq!(driver.uart_timing(), UartTiming::new(230_400, 0));
+}
+
+static TIMEOUT_DEVICE: Mutex<RefCell<Option<CountingUart>>> =
+    Mutex::new(RefCell::new(None));
+
+#[test]
+fn reads_time_out_after_senddelay_and_reply() {
+    critical_section::with(|cs| {
+        TIMEOUT_DEVICE.borrow_ref_mut(cs).replace(counting_uart(0));
+    });
+    let base_config = TMC2209_BaseConfig {
+        baud_rate: 57_600,
+        ..Default::default()
+    };
+    let mut driver = TMC2209UART::new(&TIMEOUT_DEVICE, base_config);
+    let config = TMC2209_Config {
+        senddelay: Some(7),
+        ..Default::default()
+    };
+    driver.apply_config(&config).unwrap();
+
+    // Node 0 gone, the read gives up after the SENDDELAY written above,
+    // the reply and the margin
+    critical_section::with(|cs| {
+        let mut uart = TIMEOUT_DEVICE.borrow_ref_mut(cs);
+        *uart.as_mut().unwrap().inner_mut() = SimulatedTmc2209::new(3);
+    });
+    let waited_before_us = transport_waited_us(&TIMEOUT_DEVICE);
+    assert_eq!(driver.read_drv_status(), Err(()));
+    let waited_us = transport_waited_us(&TIMEOUT_DEVICE) - waited_before_us;
+    let timing = UartTiming::new(57_600, 7);
+    assert!(waited_us >= timing.reply_timeout_us(), "{}", waited_us);
+    assert!(
+        waited_us
+            <= timing.reply_timeout_us()
+                + timing.byte_time_us()
+                + timing.inter_frame_gap_us(),
+        "{}",
+        waited_us
+    );
+}
+
+type ClockedUart = TimedUart<CaptureUart<SimulatedTmc2209>, ClockDelay>;
+
+static GAP_DEVICE: Mutex<RefCell<Option<ClockedUart>>> =
+    Mutex::new(RefCell::new(None));
+
+#[test]
+fn datagrams_are_separated_by_inter_frame_gap() {
+    critical_section::with(|cs| {
+        GAP_DEVICE.borrow_ref_mut(cs).replace(TimedUart::new(
+            CaptureUart::new(SimulatedTmc2209::new(0), now_us),
+            ClockDelay,
+        ));
+    });
+    let base_config = TMC2209_BaseConfig {
+        baud_rate: 57_600,
+        ..Default::default()
+    };
+    let mut driver = TMC2209UART::new(&GAP_DEVICE, base_config);
+    let config = TMC2209_Config {
+        rms_current: Some(800),
+        toff: Some(4),
+        ..Default::default()
+    };
+    driver.apply_config(&config).unwrap();
+    driver.read_drv_status().unwrap();
+
+    let requests = sent_datagrams();
+    assert!(requests.len() >= 4, "{:?}", requests);
+    assert!(requests
+        .iter()
+        .any(|datagram| datagram.kind == DatagramKind::WriteRequest));
+
+    let gap_us = UartTiming::new(57_600, 0).inter_frame_gap_us() as u64;
+    for pair in requests.windows(2) {
+        assert!(
+            pair[1].timestamp_us >= pair[0].timestamp_us + gap_us,
+            "{:?} too early after {:?}",
+            pair[1],
+            pair[0]
+        );
+    }
+}
+
+fn sent_datagrams() -> Vec<Datagram> {
+    let mut decoder = DatagramDecoder::new(Direction::Tx);
+    critical_section::with(|cs| {
+        GAP_DEVICE
+            .borrow_ref(cs)
+            .as_ref()
+            .unwrap()
+            .inner()
+            .iter()
+            .filter(|captured| captured.direction == Direction::Tx)
+            .filter_map(|captured| {
+                decoder.push(captured.timestamp_us, captured.byte)
+            })
+            .collect()
+    })
+}